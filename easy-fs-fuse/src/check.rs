@@ -0,0 +1,125 @@
+use easy_fs::{BlockDevice, EasyFileSystem, Inode};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Check the reachable inodes of an image against its bitmaps and return the
+/// problems found, which are fixed with `repair` if they can be.
+///
+/// Unreachable inodes and blocks are freed, but cross-linked blocks and bad
+/// directory entries are only reported.
+pub fn check(block_device: Arc<dyn BlockDevice>, repair: bool) -> Vec<String> {
+    if !EasyFileSystem::is_valid(&block_device) {
+        return vec![String::from("bad magic number of the super block")];
+    }
+    let efs = EasyFileSystem::open(block_device);
+    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    let inode_count = efs.lock().inode_count();
+    let data_area = efs.lock().data_area();
+    let mut problems = Vec::new();
+    // reachable inodes with the number of entries naming them,
+    // the root directory is named by nothing but starts with one link
+    let mut inodes: BTreeMap<u32, (Arc<Inode>, u32)> = BTreeMap::new();
+    inodes.insert(0, (Arc::clone(&root_inode), 1));
+    let mut dirs = vec![(String::from("/"), Arc::clone(&root_inode))];
+    while let Some((dir_path, dir)) = dirs.pop() {
+        if let Some(offset) = dir.bad_dirent() {
+            problems.push(format!("{}: bad entry at offset {}", dir_path, offset));
+        }
+        for name in dir.ls() {
+            if name == "." || name == ".." {
+                continue;
+            }
+            let path = format!("{}{}", dir_path, name);
+            let inode = dir.find(name.as_str()).unwrap();
+            let inode_id = inode.inode_id();
+            if inode_id >= inode_count {
+                problems.push(format!("{}: bad inode {}", path, inode_id));
+                continue;
+            }
+            if let Some((_, links)) = inodes.get_mut(&inode_id) {
+                *links += 1;
+                if inode.is_dir() {
+                    problems.push(format!("{}: directory linked more than once", path));
+                }
+                continue;
+            }
+            inodes.insert(inode_id, (Arc::clone(&inode), 1));
+            if inode.is_dir() {
+                let parent_id = inode.find("..").map(|parent| parent.inode_id());
+                if parent_id != Some(dir.inode_id()) {
+                    problems.push(format!("{}: bad entry ..", path));
+                }
+                dirs.push((format!("{}/", path), inode));
+            }
+        }
+    }
+
+    // the owner inode of each block in use
+    let mut owners: BTreeMap<u32, u32> = BTreeMap::new();
+    for (inode_id, (inode, links)) in inodes.iter() {
+        if !efs.lock().is_inode_allocated(*inode_id) {
+            problems.push(format!("inode {} is used but free in the bitmap", inode_id));
+            if repair {
+                efs.lock().set_inode_allocated(*inode_id, true);
+            }
+        }
+        let nlink = inode.metadata().nlink;
+        if nlink != *links {
+            problems.push(format!(
+                "inode {} has {} links but {} entries",
+                inode_id, nlink, links
+            ));
+            if repair {
+                inode.set_nlink(*links);
+            }
+        }
+        for block_id in inode.block_ids() {
+            if !data_area.contains(&block_id) {
+                problems.push(format!("inode {} has bad block {}", inode_id, block_id));
+                continue;
+            }
+            if let Some(owner) = owners.insert(block_id, *inode_id) {
+                problems.push(format!(
+                    "block {} is cross-linked by inodes {} and {}",
+                    block_id, owner, inode_id
+                ));
+            }
+            if !efs.lock().is_data_allocated(block_id) {
+                problems.push(format!("block {} is used but free in the bitmap", block_id));
+                if repair {
+                    efs.lock().set_data_allocated(block_id, true);
+                }
+            }
+        }
+    }
+
+    // allocated but unreachable
+    for inode_id in 0..inode_count {
+        if efs.lock().is_inode_allocated(inode_id) && !inodes.contains_key(&inode_id) {
+            problems.push(format!("inode {} is orphaned", inode_id));
+            if repair {
+                efs.lock().set_inode_allocated(inode_id, false);
+            }
+        }
+    }
+    let leaked: Vec<u32> = data_area
+        .filter(|block_id| {
+            efs.lock().is_data_allocated(*block_id) && !owners.contains_key(block_id)
+        })
+        .collect();
+    if !leaked.is_empty() {
+        problems.push(format!(
+            "{} blocks are allocated but not used",
+            leaked.len()
+        ));
+        if repair {
+            for block_id in leaked {
+                efs.lock().set_data_allocated(block_id, false);
+            }
+        }
+    }
+    if repair {
+        root_inode.sync();
+    }
+    problems
+}
@@ -1,9 +1,15 @@
-use clap::{App, Arg};
-use easy_fs::{BlockDevice, EasyFileSystem};
-use std::fs::{read_dir, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+mod check;
+
+use check::check;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use easy_fs::{set_clock, BlockDevice, EasyFileSystem, Inode, FEATURES, FEATURE_LONG_NAMES};
+use std::convert::TryFrom;
+use std::fs::{read_dir, read_link, symlink_metadata, File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::PermissionsExt;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const BLOCK_SZ: usize = 512;
 
@@ -29,17 +35,34 @@ impl BlockDevice for BlockFile {
     }
 }
 
-fn main() {
-    easy_fs_pack().expect("Error when packing easy-fs!");
+fn host_time() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32
 }
 
-fn easy_fs_pack() -> std::io::Result<()> {
+fn main() {
+    set_clock(host_time);
+    let image = || Arg::with_name("image").required(true).help("Image file");
+    let path = || {
+        Arg::with_name("path")
+            .required(true)
+            .help("Path in the image")
+    };
+    let host_path = || {
+        Arg::with_name("host_path")
+            .required(true)
+            .help("Path on the host")
+    };
     let matches = App::new("EasyFileSystem packer")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("source")
                 .short("s")
                 .long("source")
                 .takes_value(true)
+                .required(true)
                 .help("Executable source dir(with backslash)"),
         )
         .arg(
@@ -47,11 +70,325 @@ fn easy_fs_pack() -> std::io::Result<()> {
                 .short("t")
                 .long("target")
                 .takes_value(true)
+                .required(true)
                 .help("Executable target dir(with backslash)"),
         )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .default_value("16M")
+                .help("Size of the image, with an optional K, M or G suffix"),
+        )
+        .arg(
+            Arg::with_name("inodes")
+                .long("inodes")
+                .default_value("4096")
+                .help("Number of inodes, the root directory takes one"),
+        )
+        .arg(
+            Arg::with_name("reserve")
+                .long("reserve")
+                .default_value("0")
+                .help("Space the image can grow by with resize"),
+        )
+        .arg(
+            Arg::with_name("short-names")
+                .long("short-names")
+                .help("Use the fixed directory entries of old images, names take at most 27 bytes"),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Check the bitmaps against the reachable inodes")
+                .arg(image())
+                .arg(
+                    Arg::with_name("repair")
+                        .short("r")
+                        .long("repair")
+                        .help("Fix the problems found"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ls")
+                .about("List a directory")
+                .arg(image())
+                .arg(Arg::with_name("path").default_value("/")),
+        )
+        .subcommand(
+            SubCommand::with_name("cat")
+                .about("Print a file")
+                .arg(image())
+                .arg(path()),
+        )
+        .subcommand(
+            SubCommand::with_name("get")
+                .about("Copy a file out of the image")
+                .arg(image())
+                .arg(path())
+                .arg(host_path()),
+        )
+        .subcommand(
+            SubCommand::with_name("put")
+                .about("Copy a file into the image, replacing an existing one")
+                .arg(image())
+                .arg(host_path())
+                .arg(path()),
+        )
+        .subcommand(
+            SubCommand::with_name("rm")
+                .about("Remove a file or an empty directory")
+                .arg(image())
+                .arg(path()),
+        )
+        .subcommand(
+            SubCommand::with_name("resize")
+                .about("Grow the image into the space reserved when packing")
+                .arg(image())
+                .arg(Arg::with_name("size").required(true).help("New size")),
+        )
+        .subcommand(
+            SubCommand::with_name("stat")
+                .about("Show the attributes of a file")
+                .arg(image())
+                .arg(path()),
+        )
         .get_matches();
+    let result = match matches.subcommand() {
+        ("check", Some(args)) => check_image(args),
+        ("ls", Some(args)) => ls(args),
+        ("cat", Some(args)) => cat(args),
+        ("get", Some(args)) => get(args),
+        ("put", Some(args)) => put(args),
+        ("rm", Some(args)) => rm(args),
+        ("stat", Some(args)) => stat(args),
+        ("resize", Some(args)) => resize(args),
+        _ => easy_fs_pack(&matches),
+    };
+    if let Err(err) = result {
+        eprintln!("easy-fs-fuse: {}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Parse a size in bytes with an optional `K`, `M` or `G` suffix into blocks.
+fn parse_blocks(size: &str) -> std::io::Result<u32> {
+    let (number, unit) = [("K", 1 << 10), ("M", 1 << 20), ("G", 1 << 30)]
+        .iter()
+        .find_map(|(suffix, unit)| size.strip_suffix(suffix).map(|number| (number, *unit)))
+        .unwrap_or((size, 1));
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(unit))
+        .and_then(|bytes| u32::try_from(bytes / BLOCK_SZ as u64).ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("bad size {}", size)))
+}
+
+fn open_block_file(path: &str) -> std::io::Result<Arc<BlockFile>> {
+    let f = OpenOptions::new().read(true).write(true).open(path)?;
+    Ok(Arc::new(BlockFile(Mutex::new(f))))
+}
+
+/// Open the image given by the `image` argument and return its root.
+fn open_image(args: &ArgMatches) -> std::io::Result<Arc<Inode>> {
+    let block_device: Arc<dyn BlockDevice> = open_block_file(args.value_of("image").unwrap())?;
+    if !EasyFileSystem::is_valid(&block_device) {
+        return Err(Error::new(ErrorKind::InvalidData, "not an easy-fs image"));
+    }
+    let efs = EasyFileSystem::open(block_device);
+    Ok(Arc::new(EasyFileSystem::root_inode(&efs)))
+}
+
+fn find(root_inode: &Arc<Inode>, path: &str) -> std::io::Result<Arc<Inode>> {
+    root_inode.find_path(path).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("{}: no such file or directory", path),
+        )
+    })
+}
+
+/// Split a path into its parent directory and its last name.
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(pos) => (&path[..pos + 1], &path[pos + 1..]),
+        None => ("", path),
+    }
+}
+
+fn read_file(inode: &Arc<Inode>, path: &str) -> std::io::Result<Vec<u8>> {
+    if inode.is_dir() {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("{}: is a directory", path),
+        ));
+    }
+    if let Some(target) = inode.readlink() {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("{}: is a symbolic link to {}", path, target),
+        ));
+    }
+    let mut data = vec![0u8; inode.size()];
+    inode.read_at(0, &mut data);
+    Ok(data)
+}
+
+fn check_image(args: &ArgMatches) -> std::io::Result<()> {
+    let block_file = open_block_file(args.value_of("image").unwrap())?;
+    let repair = args.is_present("repair");
+    let problems = check(block_file, repair);
+    for problem in problems.iter() {
+        println!("{}", problem);
+    }
+    if problems.is_empty() {
+        println!("no problem found");
+    } else if repair {
+        println!("{} problems found, repaired if possible", problems.len());
+    } else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{} problems found", problems.len()),
+        ));
+    }
+    Ok(())
+}
+
+fn ls(args: &ArgMatches) -> std::io::Result<()> {
+    let root_inode = open_image(args)?;
+    let path = args.value_of("path").unwrap();
+    let dir = find(&root_inode, path)?;
+    if !dir.is_dir() {
+        println!("{}", path);
+        return Ok(());
+    }
+    for name in dir.ls() {
+        let inode = dir.find(name.as_str()).unwrap();
+        let metadata = inode.metadata();
+        let suffix = match inode.readlink() {
+            Some(target) => format!(" -> {}", target),
+            None if metadata.is_dir => String::from("/"),
+            None => String::new(),
+        };
+        println!("{:>10} {}{}", metadata.size, name, suffix);
+    }
+    Ok(())
+}
+
+fn cat(args: &ArgMatches) -> std::io::Result<()> {
+    let root_inode = open_image(args)?;
+    let path = args.value_of("path").unwrap();
+    let data = read_file(&find(&root_inode, path)?, path)?;
+    std::io::stdout().write_all(&data)
+}
+
+fn get(args: &ArgMatches) -> std::io::Result<()> {
+    let root_inode = open_image(args)?;
+    let path = args.value_of("path").unwrap();
+    let data = read_file(&find(&root_inode, path)?, path)?;
+    std::fs::write(args.value_of("host_path").unwrap(), data)
+}
+
+fn put(args: &ArgMatches) -> std::io::Result<()> {
+    let root_inode = open_image(args)?;
+    let path = args.value_of("path").unwrap();
+    let host_path = args.value_of("host_path").unwrap();
+    let data = std::fs::read(host_path)?;
+    let mode = std::fs::metadata(host_path)?.permissions().mode();
+    let (parent, name) = split_path(path);
+    let dir = find(&root_inode, parent)?;
+    let inode = match dir.find(name) {
+        Some(inode) if !inode.is_dir() && !inode.is_symlink() => {
+            inode.clear();
+            inode
+        }
+        _ => dir
+            .create(name)
+            .ok_or_else(|| Error::new(ErrorKind::Other, format!("{}: can not be created", path)))?,
+    };
+    inode.write_at(0, &data);
+    inode.chmod(mode as u16);
+    root_inode.sync();
+    Ok(())
+}
+
+fn rm(args: &ArgMatches) -> std::io::Result<()> {
+    let root_inode = open_image(args)?;
+    let path = args.value_of("path").unwrap();
+    let (parent, name) = split_path(path);
+    if !find(&root_inode, parent)?.unlink(name) {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("{}: can not be removed", path),
+        ));
+    }
+    root_inode.sync();
+    Ok(())
+}
+
+fn stat(args: &ArgMatches) -> std::io::Result<()> {
+    let root_inode = open_image(args)?;
+    let path = args.value_of("path").unwrap();
+    let metadata = find(&root_inode, path)?.metadata();
+    let type_ = if metadata.is_dir {
+        "directory"
+    } else if metadata.is_symlink {
+        "symbolic link"
+    } else {
+        "regular file"
+    };
+    println!("  File: {}", path);
+    println!("  Type: {}", type_);
+    println!(" Inode: {}", metadata.inode_id);
+    println!("  Size: {}", metadata.size);
+    println!("Blocks: {}", metadata.blocks);
+    println!(" Links: {}", metadata.nlink);
+    println!("  Mode: {:04o}", metadata.mode);
+    println!("   Uid: {}", metadata.uid);
+    println!("   Gid: {}", metadata.gid);
+    // seconds since the Unix epoch
+    println!("Access: {}", metadata.atime);
+    println!("Modify: {}", metadata.mtime);
+    println!("Change: {}", metadata.ctime);
+    Ok(())
+}
+
+fn resize(args: &ArgMatches) -> std::io::Result<()> {
+    let total_blocks = parse_blocks(args.value_of("size").unwrap())?;
+    let block_file = open_block_file(args.value_of("image").unwrap())?;
+    let block_device: Arc<dyn BlockDevice> = block_file.clone();
+    if !EasyFileSystem::is_valid(&block_device) {
+        return Err(Error::new(ErrorKind::InvalidData, "not an easy-fs image"));
+    }
+    let efs = EasyFileSystem::open(block_device);
+    // never truncate the image, the new blocks are cleared on allocation
+    let len = total_blocks as u64 * BLOCK_SZ as u64;
+    {
+        let file = block_file.0.lock().unwrap();
+        if file.metadata()?.len() < len {
+            file.set_len(len)?;
+        }
+    }
+    if !efs.lock().resize(total_blocks) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "the image can only grow into its reserved space",
+        ));
+    }
+    efs.lock().sync();
+    Ok(())
+}
+
+fn easy_fs_pack(matches: &ArgMatches) -> std::io::Result<()> {
     let src_path = matches.value_of("source").unwrap();
     let target_path = matches.value_of("target").unwrap();
+    let total_blocks = parse_blocks(matches.value_of("size").unwrap())?;
+    let inodes = matches
+        .value_of("inodes")
+        .unwrap()
+        .parse::<u32>()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "bad number of inodes"))?;
+    let reserved_blocks = parse_blocks(matches.value_of("reserve").unwrap())?;
     println!("src_path = {}\ntarget_path = {}", src_path, target_path);
     let block_file = Arc::new(BlockFile(Mutex::new({
         let f = OpenOptions::new()
@@ -59,13 +396,22 @@ fn easy_fs_pack() -> std::io::Result<()> {
             .write(true)
             .create(true)
             .open(format!("{}{}", target_path, "fs.img"))?;
-        f.set_len(16 * 2048 * 512).unwrap();
+        f.set_len(total_blocks as u64 * BLOCK_SZ as u64).unwrap();
         f
     })));
-    // 16MiB, at most 4095 files
-    let efs = EasyFileSystem::create(block_file, 16 * 2048, 1);
+    let efs = EasyFileSystem::create(
+        block_file,
+        total_blocks,
+        inodes,
+        reserved_blocks,
+        if matches.is_present("short-names") {
+            FEATURES & !FEATURE_LONG_NAMES
+        } else {
+            FEATURES
+        },
+    );
     let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
-    let apps: Vec<_> = read_dir(src_path)
+    let mut apps: Vec<_> = read_dir(src_path)
         .unwrap()
         .into_iter()
         .map(|dir_entry| {
@@ -74,16 +420,39 @@ fn easy_fs_pack() -> std::io::Result<()> {
             name_with_ext
         })
         .collect();
+    // other names of the apps are symbolic links made in the target dir
+    for dir_entry in read_dir(target_path)? {
+        let dir_entry = dir_entry?;
+        if dir_entry.file_type()?.is_symlink() {
+            let name = dir_entry.file_name().into_string().unwrap();
+            if !apps.contains(&name) {
+                apps.push(name);
+            }
+        }
+    }
     for app in apps {
+        let host_path = format!("{}{}", target_path, app);
+        // keep the target of a link as it is, it should be relative
+        if symlink_metadata(&host_path)?.file_type().is_symlink() {
+            let target = read_link(&host_path)?;
+            root_inode
+                .symlink(app.as_str(), target.to_str().unwrap())
+                .unwrap();
+            continue;
+        }
         // load app data from host file system
-        let mut host_file = File::open(format!("{}{}", target_path, app)).unwrap();
+        let mut host_file = File::open(host_path).unwrap();
         let mut all_data: Vec<u8> = Vec::new();
         host_file.read_to_end(&mut all_data).unwrap();
         // create a file in easy-fs
         let inode = root_inode.create(app.as_str()).unwrap();
         // write data to easy-fs
         inode.write_at(0, all_data.as_slice());
+        // owned by root with the mode on the host, so that apps can be run
+        inode.chmod(host_file.metadata()?.permissions().mode() as u16);
     }
+    // the block cache only writes back on eviction
+    root_inode.sync();
     // list apps
     for app in root_inode.ls() {
         println!("{}", app);
@@ -102,7 +471,9 @@ fn efs_test() -> std::io::Result<()> {
         f.set_len(8192 * 512).unwrap();
         f
     })));
-    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    set_clock(host_time);
+    // room to grow by 1024 blocks
+    EasyFileSystem::create(block_file.clone(), 4096, 4096, 1024, FEATURES);
     let efs = EasyFileSystem::open(block_file.clone());
     let root_inode = EasyFileSystem::root_inode(&efs);
     root_inode.create("filea");
@@ -117,6 +488,137 @@ fn efs_test() -> std::io::Result<()> {
     let mut buffer = [0u8; 233];
     let len = filea.read_at(0, &mut buffer);
     assert_eq!(greet_str, core::str::from_utf8(&buffer[..len]).unwrap(),);
+    let metadata = filea.metadata();
+    assert!(!metadata.is_dir);
+    assert_eq!(metadata.size as usize, greet_str.len());
+    assert_eq!((metadata.nlink, metadata.blocks), (1, 1));
+    assert!(metadata.mtime > 0 && metadata.mtime <= host_time());
+    assert!(metadata.atime >= metadata.mtime);
+    // an empty write past the end leaves the file as it is
+    assert_eq!(filea.write_at(BLOCK_SZ * 4, &[]), 0);
+    let metadata = filea.metadata();
+    assert_eq!(
+        (metadata.size as usize, metadata.blocks),
+        (greet_str.len(), 1)
+    );
+    // the access time is only updated by reads if it is not after the last
+    // change or a day old
+    set_clock(|| host_time() + 60);
+    filea.read_at(0, &mut buffer);
+    let atime = filea.metadata().atime;
+    assert!(atime > metadata.mtime);
+    set_clock(|| host_time() + 120);
+    filea.read_at(0, &mut buffer);
+    assert_eq!(filea.metadata().atime, atime);
+    set_clock(|| host_time() + 24 * 60 * 60 + 60);
+    filea.read_at(0, &mut buffer);
+    assert!(filea.metadata().atime > atime);
+    set_clock(host_time);
+
+    // new inodes are owned by root
+    assert_eq!((metadata.mode, metadata.uid, metadata.gid), (0o644, 0, 0));
+    assert_eq!(root_inode.metadata().mode, 0o755);
+    filea.chmod(0o104755);
+    filea.chown(1000, 100);
+    let metadata = filea.metadata();
+    assert_eq!(
+        (metadata.mode, metadata.uid, metadata.gid),
+        (0o4755, 1000, 100)
+    );
+    assert!(metadata.ctime >= metadata.mtime);
+
+    // directories
+    let root_inode = Arc::new(root_inode);
+    assert!(root_inode.is_dir());
+    assert!(root_inode.create("filea").is_none());
+    let dira = root_inode.mkdir("dira").unwrap();
+    assert!(dira.is_dir());
+    assert_eq!(dira.ls(), vec![".", ".."]);
+    assert!(dira.mkdir("dirb").is_some());
+    assert!(dira.create("filec").is_some());
+    assert!(filea.create("filed").is_none());
+    assert!(filea.find("filea").is_none());
+    let filec = root_inode.find_path("/dira/dirb/../filec").unwrap();
+    assert!(!filec.is_dir());
+    let dirb = root_inode.find_path("dira//dirb/").unwrap();
+    assert_eq!(dirb.find_path("..").unwrap().inode_id(), dira.inode_id());
+    assert_eq!(dirb.find_path("../..").unwrap().inode_id(), 0);
+    assert_eq!(dirb.find_path("/").unwrap().inode_id(), 0);
+    assert_eq!(root_inode.find_path("..").unwrap().inode_id(), 0);
+    assert!(dirb.find_path("./filec").is_none());
+    assert!(root_inode.find_path("filea/x").is_none());
+
+    // hard links, rename and unlink
+    assert!(dira.link("linka", &filea));
+    assert_eq!(filea.metadata().nlink, 2);
+    assert!(!dira.link("linkb", &dirb));
+    assert!(!dira.link("filec", &filea));
+    assert_eq!(dira.find("linka").unwrap().inode_id(), filea.inode_id());
+    assert!(root_inode.unlink("filea"));
+    assert!(root_inode.find("filea").is_none());
+    let linka = dira.find("linka").unwrap();
+    let len = linka.read_at(0, &mut buffer);
+    assert_eq!(greet_str, core::str::from_utf8(&buffer[..len]).unwrap(),);
+    assert!(!root_inode.unlink("dira"));
+    assert!(!dira.unlink(".."));
+    assert!(!dira.rename("dirb", &dirb, "dirb"));
+    assert!(dira.rename("linka", &root_inode, "filea"));
+    assert!(dira.find("linka").is_none());
+    assert!(dira.rename("dirb", &root_inode, "dirb"));
+    assert_eq!(dirb.find_path("..").unwrap().inode_id(), 0);
+    assert!(!root_inode.rename("dirb", &dira, "filec"));
+    assert!(dira.unlink("filec"));
+    assert!(!root_inode.rename("fileb", &root_inode, "dirb"));
+    assert!(!root_inode.rename("dirb", &dirb, "x"));
+    assert!(root_inode.unlink("dirb"));
+    assert!(root_inode.unlink("dira"));
+    assert!(root_inode.find_path("dira/filec").is_none());
+    // removed inodes are kept while they are open, but hold no new entries
+    assert_eq!(dira.ls(), vec![".", ".."]);
+    assert!(dira.create("filec").is_none());
+    let dira_id = dira.inode_id();
+    drop((dira, dirb, filec));
+    // freed slots and inodes are reused
+    assert_eq!(root_inode.mkdir("dirc").unwrap().inode_id(), dira_id);
+    assert_eq!(root_inode.ls(), vec![".", "..", "filea", "fileb", "dirc"]);
+    assert!(root_inode.unlink("dirc"));
+    // names of up to 255 bytes, the space of removed entries is reused
+    assert_eq!(root_inode.name_length_limit(), 255);
+    assert!(root_inode.create(&"x".repeat(256)).is_none());
+    let names: Vec<String> = (0..8)
+        .map(|i| format!("{}{}", i, "x".repeat(254)))
+        .chain(Some("文件".repeat(42)))
+        .collect();
+    for name in names.iter() {
+        assert!(root_inode.create(name).is_some());
+    }
+    let size = root_inode.size();
+    for name in names.iter().step_by(2) {
+        assert!(root_inode.unlink(name));
+    }
+    for name in names.iter().step_by(2) {
+        assert!(root_inode.create(name).is_some());
+    }
+    assert_eq!(root_inode.size(), size);
+    assert_eq!(root_inode.ls().len(), 4 + names.len());
+    for name in names.iter() {
+        assert!(root_inode.find(name).is_some());
+        assert!(root_inode.rename(name, &root_inode, &name[3..]));
+        assert!(root_inode.unlink(&name[3..]));
+    }
+    assert_eq!(root_inode.ls(), vec![".", "..", "filea", "fileb"]);
+    // data blocks are reclaimed, or the image would run out of space
+    for _ in 0..8 {
+        let tmp = root_inode.create("tmp").unwrap();
+        tmp.write_at(0, &vec![1u8; 1000 * BLOCK_SZ]);
+        assert!(root_inode.unlink("tmp"));
+        // until the file is closed
+        assert_eq!(tmp.read_at(999 * BLOCK_SZ, &mut buffer), buffer.len());
+        assert!(buffer.iter().all(|byte| *byte == 1));
+    }
+    let filea = root_inode.find("filea").unwrap();
+    // dirty blocks are written back on eviction and read again from the image
+    easy_fs::set_block_cache_capacity(32);
 
     let mut random_str_test = |len: usize| {
         filea.clear();
@@ -150,6 +652,222 @@ fn efs_test() -> std::io::Result<()> {
     random_str_test(400 * BLOCK_SZ);
     random_str_test(1000 * BLOCK_SZ);
     random_str_test(2000 * BLOCK_SZ);
+    filea.sync();
+
+    // a committed transaction which is not written back is replayed on mount
+    let fileb = root_inode.find("fileb").unwrap();
+    fileb.write_at(0, &[7u8; BLOCK_SZ]);
+    fileb.sync();
+    let mut block = [0u8; BLOCK_SZ];
+    let block_id = (0..4096)
+        .find(|block_id| {
+            block_file.read_block(*block_id, &mut block);
+            block == [7u8; BLOCK_SZ]
+        })
+        .unwrap();
+    // the journal header follows the super block, then the logged blocks
+    let mut header = [0u8; BLOCK_SZ];
+    header[..4].copy_from_slice(&0x6a726e6cu32.to_le_bytes());
+    header[4..8].copy_from_slice(&1u32.to_le_bytes());
+    header[8..12].copy_from_slice(&(block_id as u32).to_le_bytes());
+    block_file.write_block(2, &[9u8; BLOCK_SZ]);
+    block_file.write_block(1, &header);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let fileb = EasyFileSystem::root_inode(&efs).find("fileb").unwrap();
+    assert_eq!(fileb.read_at(0, &mut block), BLOCK_SZ);
+    assert_eq!(block, [9u8; BLOCK_SZ]);
+    block_file.read_block(1, &mut header);
+    assert_eq!(header[4..8], [0u8; 4]);
+
+    // check and repair
+    assert!(check(block_file.clone(), false).is_empty());
+    let leaked_block = efs.lock().data_area().end - 1;
+    assert!(!efs.lock().is_data_allocated(leaked_block));
+    efs.lock().set_data_allocated(leaked_block, true);
+    fileb.set_nlink(3);
+    fileb.sync();
+    assert_eq!(check(block_file.clone(), false).len(), 2);
+    assert_eq!(check(block_file.clone(), true).len(), 2);
+    assert!(check(block_file.clone(), false).is_empty());
+    assert_eq!(fileb.metadata().nlink, 1);
+
+    // grow into the reserved space
+    let data_end = efs.lock().data_area().end;
+    assert!(!efs.lock().resize(4096 + 2048));
+    assert!(!efs.lock().resize(4000));
+    assert!(efs.lock().resize(4096 + 1024));
+    efs.lock().sync();
+    let efs = EasyFileSystem::open(block_file.clone());
+    assert_eq!(efs.lock().data_area().end, data_end + 1024);
+    assert!(check(block_file.clone(), false).is_empty());
+
+    // a full data area cuts writes short and fails creations without leaking
+    // blocks or inodes
+    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    let big = root_inode.create("big").unwrap();
+    let data = vec![1u8; 8192 * BLOCK_SZ];
+    let written = big.write_at(0, &data);
+    assert!(written > 0 && written < data.len());
+    assert_eq!(big.size(), written);
+    // the part cut off needed more blocks than were left
+    let mut size = written;
+    while big.write_at(size, &data[..BLOCK_SZ]) == BLOCK_SZ {
+        size += BLOCK_SZ;
+    }
+    assert_eq!(big.size(), size);
+    assert!(root_inode.mkdir("full").is_none());
+    assert!(root_inode.symlink("full", "big").is_none());
+    assert!(root_inode.find("full").is_none());
+    drop(big);
+    assert!(root_inode.unlink("big"));
+    assert!(root_inode.mkdir("full").is_some());
+    efs.lock().sync();
+    assert!(check(block_file.clone(), false).is_empty());
+
+    // a corrupted entry length ends the walks over a directory and is
+    // reported, the length of `.` follows its inode number
+    let full = root_inode.find("full").unwrap();
+    for rec_len in [0u16, 4, 6, 1024] {
+        full.write_at(4, &rec_len.to_le_bytes());
+        assert!(full.find("..").is_none());
+        assert_eq!(full.ls().len(), 0);
+        assert!(full.create("filea").is_none());
+        assert_eq!(full.bad_dirent(), Some(0));
+        efs.lock().sync();
+        let problems = check(block_file.clone(), false);
+        assert!(problems.contains(&String::from("/full/: bad entry at offset 0")));
+    }
+    full.write_at(4, &8u16.to_le_bytes());
+    assert_eq!(full.bad_dirent(), None);
+    assert_eq!(full.ls(), vec![".", ".."]);
+    efs.lock().sync();
+    assert!(check(block_file.clone(), false).is_empty());
+
+    // images of older versions, with the fixed directory entries and neither
+    // the triple indirect block nor owners
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 4096, 0, 0);
+    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    assert_eq!(root_inode.name_length_limit(), 27);
+    assert_eq!(
+        root_inode.max_file_size(),
+        (24 + 128 + 128 * 128) * BLOCK_SZ
+    );
+    let filea = root_inode.create("filea").unwrap();
+    let metadata = filea.metadata();
+    assert_eq!((metadata.mode, metadata.uid, metadata.gid), (0o755, 0, 0));
+    assert!(!filea.chmod(0o600) && !filea.chown(1000, 1000));
+    assert_eq!(filea.metadata().mode, 0o755);
+    // the last direct blocks are not taken by the owner or an index block
+    let data = [3u8; 24 * BLOCK_SZ];
+    assert_eq!(filea.write_at(0, &data), data.len());
+    assert_eq!(filea.metadata().blocks, 24);
+    assert_eq!(filea.write_at(root_inode.max_file_size(), &[1u8]), 0);
+    drop(filea);
+    assert!(root_inode.unlink("filea"));
+    assert!(root_inode.create(&"y".repeat(28)).is_none());
+    let dira = root_inode.mkdir(&"y".repeat(27)).unwrap();
+    assert!(dira.create("filea").is_some());
+    assert!(root_inode.create("fileb").is_some());
+    assert!(dira.rename("filea", &root_inode, "fileb"));
+    assert!(root_inode.rename(&"y".repeat(27), &root_inode, "dira"));
+    assert_eq!(root_inode.ls(), vec![".", "..", "fileb", "dira"]);
+    assert_eq!(dira.find("..").unwrap().inode_id(), 0);
+    assert!(dira.ls().len() == 2 && root_inode.unlink("dira"));
+    drop(dira);
+    root_inode.sync();
+    assert!(check(block_file.clone(), false).is_empty());
+
+    // files beyond the double indirect block
+    block_file.0.lock().unwrap().set_len(20480 * 512)?;
+    easy_fs::set_block_cache_capacity(256);
+    let efs = EasyFileSystem::create(block_file.clone(), 20480, 64, 0, FEATURES);
+    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    let filea = root_inode.create("filea").unwrap();
+    let data: Vec<u8> = (0..9 << 20).map(|i: usize| (i / BLOCK_SZ) as u8).collect();
+    assert_eq!(filea.write_at(0, &data), data.len());
+    let metadata = filea.metadata();
+    assert_eq!(metadata.size as usize, data.len());
+    // indirect1, indirect2 with 128 indirect1 blocks, then indirect3 with one
+    // indirect2 block and 15 indirect1 blocks
+    assert_eq!(metadata.blocks as usize, 18432 + 1 + 1 + 128 + 1 + 1 + 15);
+    let mut buffer = vec![0u8; 3 * BLOCK_SZ];
+    for offset in [0, 100 * BLOCK_SZ + 1, (9 << 20) - 3 * BLOCK_SZ] {
+        assert_eq!(filea.read_at(offset, &mut buffer), buffer.len());
+        assert_eq!(buffer, data[offset..offset + buffer.len()]);
+    }
+    assert_eq!(
+        filea.write_at(easy_fs::MAX_FILE_SIZE, &[1u8]),
+        0,
+        "writes stop at the largest file size"
+    );
+    // shrink into the middle of a block, which reads as zeros when extended
+    let size = (8 << 20) + 100;
+    assert!(filea.truncate(size));
+    assert!(filea.truncate(9 << 20));
+    assert_eq!(filea.read_at(size - 100, &mut buffer), buffer.len());
+    assert_eq!(buffer[..100], data[size - 100..size]);
+    assert!(buffer[100..].iter().all(|byte| *byte == 0));
+    assert!(!filea.truncate(easy_fs::MAX_FILE_SIZE + 1));
+    assert!(!root_inode.truncate(0));
+    // only the blocks written are allocated
+    let fileb = root_inode.create("fileb").unwrap();
+    assert_eq!(fileb.write_at(9 << 20, &[1u8]), 1);
+    assert_eq!(fileb.metadata().blocks, 4);
+    assert_eq!(fileb.read_at(100 * BLOCK_SZ, &mut buffer), buffer.len());
+    assert!(buffer.iter().all(|byte| *byte == 0));
+    assert_eq!(fileb.write_at(100 * BLOCK_SZ, &[2u8]), 1);
+    assert_eq!(fileb.metadata().blocks, 6);
+    assert!(fileb.truncate(101 * BLOCK_SZ));
+    assert_eq!(fileb.metadata().blocks, 2);
+    // symbolic links hold their targets, which are not resolved
+    let link = root_inode.symlink("linka", "filea").unwrap();
+    assert_eq!(link.readlink().as_deref(), Some("filea"));
+    assert!(link.metadata().is_symlink && !link.truncate(0));
+    assert!(root_inode.find("filea").unwrap().readlink().is_none());
+    assert!(root_inode.symlink("linka", "fileb").is_none());
+    assert!(root_inode.symlink("linkb", "").is_none());
+    let target = "x".repeat(easy_fs::SYMLINK_LENGTH_LIMIT);
+    let linkb = root_inode.symlink("linkb", &target).unwrap();
+    assert_eq!(linkb.readlink(), Some(target));
+    assert!(root_inode
+        .symlink("linkc", &"x".repeat(easy_fs::SYMLINK_LENGTH_LIMIT + 1))
+        .is_none());
+    root_inode.sync();
+    assert!(check(block_file.clone(), false).is_empty());
+    // freed in several operations of the journal
+    drop((filea, fileb, link, linkb));
+    assert!(root_inode.unlink("filea"));
+    assert!(root_inode.unlink("fileb"));
+    assert!(root_inode.unlink("linka") && root_inode.unlink("linkb"));
+    root_inode.sync();
+    assert!(check(block_file.clone(), false).is_empty());
+
+    // a block freed by the running transaction and reused keeps its data on
+    // the disk until the transaction is committed
+    let filea = root_inode.create("filea").unwrap();
+    filea.write_at(0, &[7u8; BLOCK_SZ]);
+    root_inode.sync();
+    let block_id = filea.block_ids()[0];
+    drop(filea);
+    assert!(root_inode.unlink("filea"));
+    let fileb = root_inode.create("fileb").unwrap();
+    fileb.write_at(0, &[8u8; BLOCK_SZ]);
+    assert_eq!(fileb.block_ids(), vec![block_id]);
+    // the block cache grows while all of its blocks are in use or journaled,
+    // the others are written back when evicted
+    easy_fs::set_block_cache_capacity(4);
+    assert_eq!(root_inode.ls(), vec![".", "..", "fileb"]);
+    let mut block = [0u8; BLOCK_SZ];
+    block_file.read_block(block_id as usize, &mut block);
+    assert_eq!(block, [7u8; BLOCK_SZ]);
+    let data = vec![9u8; 64 * BLOCK_SZ];
+    assert_eq!(fileb.write_at(0, &data), data.len());
+    assert!(root_inode.rename("fileb", &root_inode, "filec"));
+    easy_fs::set_block_cache_capacity(256);
+    drop(fileb);
+    assert!(root_inode.unlink("filec"));
+    root_inode.sync();
+    assert!(check(block_file.clone(), false).is_empty());
 
     Ok(())
 }
@@ -7,7 +7,8 @@ const BLOCK_BITS: usize = BLOCK_SZ * 8;
 
 pub struct Bitmap {
     start_block_id: usize,
-    blocks: usize,
+    /// Number of usable bits, the rest of the last block is never allocated.
+    bits: usize,
 }
 
 /// Return (block_pos, bits64_pos, inner_pos)
@@ -18,36 +19,49 @@ fn decomposition(mut bit: usize) -> (usize, usize, usize) {
 }
 
 impl Bitmap {
-    pub fn new(start_block_id: usize, blocks: usize) -> Self {
+    pub fn new(start_block_id: usize, bits: usize) -> Self {
         Self {
             start_block_id,
-            blocks,
+            bits,
         }
     }
 
+    /// Blocks needed by `bits` bits.
+    pub fn blocks_of(bits: usize) -> usize {
+        (bits + BLOCK_BITS - 1) / BLOCK_BITS
+    }
+
+    /// The block holding `bit`, counted from the start of the bitmap.
+    pub fn block_of(bit: usize) -> usize {
+        bit / BLOCK_BITS
+    }
+
+    /// Only the block changed is modified, so that full blocks stay out of
+    /// the journal.
     pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
-        for block_id in 0..self.blocks {
-            let pos = get_block_cache(
+        for block_id in 0..Self::blocks_of(self.bits) {
+            let block_cache = get_block_cache(
                 block_id + self.start_block_id as usize,
                 Arc::clone(block_device),
-            )
-            .lock()
-            .modify(0, |bitmap_block: &mut BitmapBlock| {
-                if let Some((bits64_pos, inner_pos)) = bitmap_block
+            );
+            let free_bit = block_cache.lock().read(0, |bitmap_block: &BitmapBlock| {
+                bitmap_block
                     .iter()
                     .enumerate()
                     .find(|(_, bits64)| **bits64 != u64::MAX)
                     .map(|(bits64_pos, bits64)| (bits64_pos, bits64.trailing_ones() as usize))
-                {
-                    // modify cache
-                    bitmap_block[bits64_pos] |= 1u64 << inner_pos;
-                    Some(block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos as usize)
-                } else {
-                    None
-                }
             });
-            if pos.is_some() {
-                return pos;
+            if let Some((bits64_pos, inner_pos)) = free_bit {
+                let pos = block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos;
+                if pos >= self.bits {
+                    return None;
+                }
+                block_cache
+                    .lock()
+                    .modify(0, |bitmap_block: &mut BitmapBlock| {
+                        bitmap_block[bits64_pos] |= 1u64 << inner_pos;
+                    });
+                return Some(pos);
             }
         }
         None
@@ -63,7 +77,36 @@ impl Bitmap {
             });
     }
 
+    pub fn is_allocated(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) -> bool {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .read(0, |bitmap_block: &BitmapBlock| {
+                bitmap_block[bits64_pos] & (1u64 << inner_pos) > 0
+            })
+    }
+
+    /// Allocate or free `bit` whatever its state is.
+    pub fn set(&self, block_device: &Arc<dyn BlockDevice>, bit: usize, allocated: bool) {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .modify(0, |bitmap_block: &mut BitmapBlock| {
+                if allocated {
+                    bitmap_block[bits64_pos] |= 1u64 << inner_pos;
+                } else {
+                    bitmap_block[bits64_pos] &= !(1u64 << inner_pos);
+                }
+            });
+    }
+
     pub fn maximum(&self) -> usize {
-        self.blocks * BLOCK_BITS
+        self.bits
+    }
+
+    /// Make more bits usable, the bitmap blocks must have been reserved.
+    pub fn grow(&mut self, bits: usize) {
+        assert!(bits >= self.bits);
+        self.bits = bits;
     }
 }
@@ -11,6 +11,9 @@ pub struct BlockCache {
     block_id: usize,
     block_device: Arc<dyn BlockDevice>,
     modified: bool,
+    /// Modified metadata which is not committed to the journal yet, it is
+    /// neither evicted nor written back until then.
+    journaled: bool,
 }
 
 impl BlockCache {
@@ -24,9 +27,14 @@ impl BlockCache {
             block_id,
             block_device,
             modified: false,
+            journaled: false,
         }
     }
 
+    pub fn block_id(&self) -> usize {
+        self.block_id
+    }
+
     fn addr_of_offset(&self, offset: usize) -> usize {
         &self.cache[offset] as *const _ as usize
     }
@@ -48,6 +56,7 @@ impl BlockCache {
         let type_size = core::mem::size_of::<T>();
         assert!(offset + type_size <= BLOCK_SZ);
         self.modified = true;
+        self.journaled = true;
         let addr = self.addr_of_offset(offset);
         unsafe { &mut *(addr as *mut T) }
     }
@@ -56,16 +65,32 @@ impl BlockCache {
         f(self.get_ref(offset))
     }
 
+    /// Modify metadata, which goes through the journal.
     pub fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
         f(self.get_mut(offset))
     }
 
+    /// Modify file data, which is written back without the journal.
+    pub fn modify_data<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
+        let journaled = self.journaled;
+        let ret = self.modify(offset, f);
+        self.journaled = journaled;
+        ret
+    }
+
+    /// Journaled blocks are skipped, see `checkpoint`.
     pub fn sync(&mut self) {
-        if self.modified {
+        if self.modified && !self.journaled {
             self.modified = false;
             self.block_device.write_block(self.block_id, &self.cache);
         }
     }
+
+    /// Write the block back after its transaction is committed to the journal.
+    pub fn checkpoint(&mut self) {
+        self.journaled = false;
+        self.sync();
+    }
 }
 
 impl Drop for BlockCache {
@@ -74,16 +99,21 @@ impl Drop for BlockCache {
     }
 }
 
-const BLOCK_CACHE_SIZE: usize = 16;
+/// Default number of cached blocks, see `set_block_cache_capacity`. Half of
+/// them can be taken by the running transaction of the journal.
+const BLOCK_CACHE_SIZE: usize = 256;
 
 pub struct BlockCacheManager {
+    /// Ordered from the least recently used block to the most recently used.
     queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    capacity: usize,
 }
 
 impl BlockCacheManager {
     pub fn new() -> Self {
         Self {
             queue: VecDeque::new(),
+            capacity: BLOCK_CACHE_SIZE,
         }
     }
 
@@ -92,21 +122,25 @@ impl BlockCacheManager {
         block_id: usize,
         block_device: Arc<dyn BlockDevice>,
     ) -> Arc<Mutex<BlockCache>> {
-        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == block_id) {
-            Arc::clone(&pair.1)
+        if let Some(idx) = self.queue.iter().position(|pair| pair.0 == block_id) {
+            // move to the most recently used end
+            let pair = self.queue.remove(idx).unwrap();
+            let block_cache = Arc::clone(&pair.1);
+            self.queue.push_back(pair);
+            block_cache
         } else {
-            // substitute
-            if self.queue.len() == BLOCK_CACHE_SIZE {
-                // from front to tail
-                if let Some((idx, _)) = self
-                    .queue
-                    .iter()
-                    .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
-                {
-                    self.queue.drain(idx..=idx);
-                } else {
-                    panic!("Run out of BlockCache!");
+            // evict the least recently used blocks which are neither in use
+            // nor journaled, dirty ones are written back when dropped. The
+            // cache grows beyond its capacity until some can be evicted.
+            while self.queue.len() >= self.capacity {
+                match self.queue.iter().position(|pair| {
+                    // nobody else can lock a block cache only held here
+                    Arc::strong_count(&pair.1) == 1 && !pair.1.lock().journaled
+                }) {
+                    Some(idx) => {
+                        self.queue.remove(idx);
+                    }
+                    None => break,
                 }
             }
             // load block into mem and push back
@@ -134,9 +168,38 @@ pub fn get_block_cache(
         .get_block_cache(block_id, block_device)
 }
 
+/// Set how many blocks are cached, extra blocks are evicted on later accesses.
+pub fn set_block_cache_capacity(capacity: usize) {
+    assert!(capacity > 0);
+    BLOCK_CACHE_MANAGER.lock().capacity = capacity;
+}
+
+pub fn block_cache_capacity() -> usize {
+    BLOCK_CACHE_MANAGER.lock().capacity
+}
+
+fn block_caches() -> Vec<Arc<Mutex<BlockCache>>> {
+    // do not hold the manager during the disk accesses
+    BLOCK_CACHE_MANAGER
+        .lock()
+        .queue
+        .iter()
+        .map(|(_, cache)| Arc::clone(cache))
+        .collect()
+}
+
+/// Write back all dirty blocks except the journaled ones, modified blocks are
+/// otherwise only written when they are evicted.
 pub fn block_cache_sync_all() {
-    let manager = BLOCK_CACHE_MANAGER.lock();
-    for (_, cache) in manager.queue.iter() {
+    for cache in block_caches() {
         cache.lock().sync();
     }
 }
+
+/// Blocks of the running transaction, ordered by their last accesses.
+pub fn journaled_block_caches() -> Vec<Arc<Mutex<BlockCache>>> {
+    block_caches()
+        .into_iter()
+        .filter(|cache| cache.lock().journaled)
+        .collect()
+}
@@ -1,52 +1,96 @@
 use super::{
-    block_cache_sync_all, get_block_cache, Bitmap, BlockDevice, DiskInode, DiskInodeType, Inode,
-    SuperBlock,
+    block_cache_sync_all, get_block_cache, max_file_size, Bitmap, BlockDevice, DiskInode,
+    DiskInodeType, Inode, Journal, SuperBlock, FEATURE_LONG_NAMES, JOURNAL_CAPACITY,
+    LONG_NAME_LENGTH_LIMIT, NAME_LENGTH_LIMIT,
 };
 use crate::BLOCK_SZ;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Range;
 use spin::Mutex;
 
 pub struct EasyFileSystem {
     pub block_device: Arc<dyn BlockDevice>,
     pub inode_bitmap: Bitmap,
     pub data_bitmap: Bitmap,
+    journal: Journal,
+    /// The features of the super block, see `FEATURES`.
+    features: u32,
     inode_area_start_block: u32,
     data_area_start_block: u32,
+    /// Number of handles given out for each inode, see `Inode::find`.
+    open_inodes: BTreeMap<u32, usize>,
+    /// Inodes which lost their last link while open, kept until closed.
+    unlinked: BTreeSet<u32>,
+    /// Unlinked inodes closed since the last operation, which frees them.
+    orphans: Vec<u32>,
+    /// Data blocks freed by the running transaction, whose files are back if
+    /// a crash loses it, so they go through the journal when reused.
+    freed_data: BTreeSet<u32>,
 }
 
 type DataBlock = [u8; BLOCK_SZ];
 
+/// The header of the journal followed by the largest transaction.
+const JOURNAL_BLOCKS: u32 = 1 + JOURNAL_CAPACITY as u32;
+const INODES_PER_BLOCK: u32 = (BLOCK_SZ / core::mem::size_of::<DiskInode>()) as u32;
+
 impl EasyFileSystem {
+    /// Create a file system of `total_blocks` blocks with room for `inodes`
+    /// inodes, rounded up to fill the inode area. The data bitmap also covers
+    /// `reserved_blocks` blocks which can be added by `resize` later.
+    ///
+    /// The image has `features`, which are all of `FEATURES` unless it is made
+    /// for older versions. Without `FEATURE_LONG_NAMES` for example the
+    /// directories hold the fixed 32-byte entries, whose names are at most 27
+    /// bytes.
     pub fn create(
         block_device: Arc<dyn BlockDevice>,
         total_blocks: u32,
-        inode_bitmap_blocks: u32,
+        inodes: u32,
+        reserved_blocks: u32,
+        features: u32,
     ) -> Arc<Mutex<Self>> {
         // calculate block size of areas & create bitmaps
-        let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
-        let inode_num = inode_bitmap.maximum();
-        let inode_area_blocks =
-            ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
+        let inode_bitmap_start_block = 1 + JOURNAL_BLOCKS;
+        let inode_area_blocks = (inodes + INODES_PER_BLOCK - 1) / INODES_PER_BLOCK;
+        let inode_num = (inode_area_blocks * INODES_PER_BLOCK) as usize;
+        let inode_bitmap = Bitmap::new(inode_bitmap_start_block as usize, inode_num);
+        let inode_bitmap_blocks = Bitmap::blocks_of(inode_num) as u32;
         let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
-        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
-        let data_bitmap_blocks = (data_total_blocks + 4096) / 4097;
+        assert!(
+            total_blocks > inode_bitmap_start_block + inode_total_blocks,
+            "Too small image!"
+        );
+        let data_total_blocks = total_blocks - inode_bitmap_start_block - inode_total_blocks;
+        let data_bitmap_blocks = (data_total_blocks + reserved_blocks + 4096) / 4097;
+        assert!(data_total_blocks > data_bitmap_blocks, "Too small image!");
         let data_area_blocks = data_total_blocks - data_bitmap_blocks;
         let data_bitmap = Bitmap::new(
-            (1 + inode_bitmap_blocks + inode_area_blocks) as usize,
-            data_bitmap_blocks as usize,
+            (inode_bitmap_start_block + inode_total_blocks) as usize,
+            data_area_blocks as usize,
         );
         let mut efs = Self {
             block_device: Arc::clone(&block_device),
             inode_bitmap,
             data_bitmap,
-            inode_area_start_block: 1 + inode_bitmap_blocks,
-            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            journal: Journal::new(1, JOURNAL_BLOCKS as usize),
+            features,
+            inode_area_start_block: inode_bitmap_start_block + inode_bitmap_blocks,
+            data_area_start_block: inode_bitmap_start_block
+                + inode_total_blocks
+                + data_bitmap_blocks,
+            open_inodes: BTreeMap::new(),
+            unlinked: BTreeSet::new(),
+            orphans: Vec::new(),
+            freed_data: BTreeSet::new(),
         };
         // clear all blocks
         for i in 0..total_blocks {
             get_block_cache(i as usize, Arc::clone(&block_device))
                 .lock()
-                .modify(0, |data_block: &mut DataBlock| {
+                .modify_data(0, |data_block: &mut DataBlock| {
                     for byte in data_block.iter_mut() {
                         *byte = 0;
                     }
@@ -58,63 +102,185 @@ impl EasyFileSystem {
             |super_block: &mut SuperBlock| {
                 super_block.initialize(
                     total_blocks,
+                    JOURNAL_BLOCKS,
                     inode_bitmap_blocks,
                     inode_area_blocks,
                     data_bitmap_blocks,
                     data_area_blocks,
                 );
+                super_block.features = features;
             },
         );
         // write back immediately
         // create a inode for root node "/"
-        assert_eq!(efs.alloc_inode(), 0);
+        assert_eq!(efs.alloc_inode(), Some(0));
         let (root_inode_block_id, root_inode_offset) = efs.get_disk_inode_pos(0);
         get_block_cache(root_inode_block_id as usize, Arc::clone(&block_device))
             .lock()
             .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
-                disk_inode.initialize(DiskInodeType::Directory);
+                disk_inode.initialize(DiskInodeType::Directory, features);
             });
-        block_cache_sync_all();
-        Arc::new(Mutex::new(efs))
+        let efs = Arc::new(Mutex::new(efs));
+        // the parent of root is root itself
+        let root_inode = Self::root_inode(&efs);
+        assert!(root_inode.init_dir(0, &mut efs.lock()));
+        efs.lock().sync();
+        efs
+    }
+
+    /// Whether the device holds an easy-fs image, `open` panics if it does not.
+    pub fn is_valid(block_device: &Arc<dyn BlockDevice>) -> bool {
+        get_block_cache(0, Arc::clone(block_device))
+            .lock()
+            .read(0, |super_block: &SuperBlock| super_block.is_valid())
     }
 
+    /// The committed transaction of the journal is replayed if there is one.
     pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
         // read SuperBlock
-        get_block_cache(0, Arc::clone(&block_device))
-            .lock()
-            .read(0, |super_block: &SuperBlock| {
+        let efs = get_block_cache(0, Arc::clone(&block_device)).lock().read(
+            0,
+            |super_block: &SuperBlock| {
                 assert!(super_block.is_valid(), "Error loading EFS!");
+                let inode_bitmap_start_block = 1 + super_block.journal_blocks;
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
-                let efs = Self {
+                Self {
                     block_device,
-                    inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
+                    inode_bitmap: Bitmap::new(
+                        inode_bitmap_start_block as usize,
+                        (super_block.inode_area_blocks * INODES_PER_BLOCK) as usize,
+                    ),
                     data_bitmap: Bitmap::new(
-                        (1 + inode_total_blocks) as usize,
-                        super_block.data_bitmap_blocks as usize,
+                        (inode_bitmap_start_block + inode_total_blocks) as usize,
+                        super_block.data_area_blocks as usize,
                     ),
-                    inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
-                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
-                };
-                Arc::new(Mutex::new(efs))
-            })
+                    journal: Journal::new(1, super_block.journal_blocks as usize),
+                    features: super_block.features,
+                    inode_area_start_block: inode_bitmap_start_block
+                        + super_block.inode_bitmap_blocks,
+                    data_area_start_block: inode_bitmap_start_block
+                        + inode_total_blocks
+                        + super_block.data_bitmap_blocks,
+                    open_inodes: BTreeMap::new(),
+                    unlinked: BTreeSet::new(),
+                    orphans: Vec::new(),
+                    freed_data: BTreeSet::new(),
+                }
+            },
+        );
+        efs.journal.replay(&efs.block_device);
+        Arc::new(Mutex::new(efs))
+    }
+
+    /// Grow the file system to `total_blocks` blocks, which must be reserved by
+    /// the data bitmap and available on the device. Shrinking is not supported.
+    pub fn resize(&mut self, total_blocks: u32) -> bool {
+        let data_area_blocks = match total_blocks.checked_sub(self.data_area_start_block) {
+            Some(data_area_blocks) => data_area_blocks,
+            None => return false,
+        };
+        let resizable = get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .read(0, |super_block: &SuperBlock| {
+                total_blocks >= super_block.total_blocks
+                    && Bitmap::blocks_of(data_area_blocks as usize)
+                        <= super_block.data_bitmap_blocks as usize
+            });
+        if !resizable {
+            return false;
+        }
+        self.begin_op();
+        get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |super_block: &mut SuperBlock| {
+                super_block.total_blocks = total_blocks;
+                super_block.data_area_blocks = data_area_blocks;
+            });
+        self.data_bitmap.grow(data_area_blocks as usize);
+        true
     }
 
     pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
         let block_device = Arc::clone(&efs.lock().block_device);
         // acquire efs lock temporarily
         let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
+        let long_names = efs.lock().long_names();
+        efs.lock().open_inode(0);
         // release efs lock
-        Inode::new(block_id, block_offset, Arc::clone(efs), block_device)
+        Inode::new(
+            0,
+            block_id,
+            block_offset,
+            long_names,
+            Arc::clone(efs),
+            block_device,
+        )
+        .into_handle()
+    }
+
+    /// Count a handle of `inode_id` given out.
+    pub(crate) fn open_inode(&mut self, inode_id: u32) {
+        *self.open_inodes.entry(inode_id).or_insert(0) += 1;
+    }
+
+    /// Drop a handle of `inode_id`, the inode is freed by the next operation
+    /// if it was the last one of an unlinked inode.
+    pub(crate) fn close_inode(&mut self, inode_id: u32) {
+        let handles = self.open_inodes.get_mut(&inode_id).unwrap();
+        *handles -= 1;
+        if *handles == 0 {
+            self.open_inodes.remove(&inode_id);
+            if self.unlinked.remove(&inode_id) {
+                self.orphans.push(inode_id);
+            }
+        }
+    }
+
+    /// Free an inode which lost its last link by a later operation, after its
+    /// handles are dropped if there are any. Freeing a large file takes
+    /// several operations, which must not be part of another one.
+    pub(crate) fn orphan_inode(&mut self, inode_id: u32) {
+        if self.open_inodes.contains_key(&inode_id) {
+            self.unlinked.insert(inode_id);
+        } else {
+            self.orphans.push(inode_id);
+        }
+    }
+
+    /// An unlinked inode which is neither linked nor open.
+    pub(crate) fn take_orphan(&mut self) -> Option<u32> {
+        self.orphans.pop()
+    }
+
+    pub fn features(&self) -> u32 {
+        self.features
+    }
+
+    pub fn long_names(&self) -> bool {
+        self.features & FEATURE_LONG_NAMES != 0
+    }
+
+    /// Largest size of a regular file.
+    pub fn max_file_size(&self) -> usize {
+        max_file_size(self.features)
+    }
+
+    /// Longest name in bytes a directory entry can hold.
+    pub fn name_length_limit(&self) -> usize {
+        if self.long_names() {
+            LONG_NAME_LENGTH_LIMIT
+        } else {
+            NAME_LENGTH_LIMIT
+        }
     }
 
     pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
         let inode_size = core::mem::size_of::<DiskInode>();
-        let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
-        let block_id = self.inode_area_start_block + inode_id / inodes_per_block;
+        let block_id = self.inode_area_start_block + inode_id / INODES_PER_BLOCK;
         (
             block_id,
-            (inode_id % inodes_per_block) as usize * inode_size,
+            (inode_id % INODES_PER_BLOCK) as usize * inode_size,
         )
     }
 
@@ -122,26 +288,111 @@ impl EasyFileSystem {
         self.data_area_start_block + data_block_id
     }
 
-    pub fn alloc_inode(&mut self) -> u32 {
-        self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
+    /// Called with the efs lock held before each operation which modifies the
+    /// file system.
+    pub fn begin_op(&mut self) {
+        if self.journal.begin_op(&self.block_device) {
+            self.freed_data.clear();
+        }
     }
 
-    /// Return a block ID not ID in the data area.
-    pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
+    /// Commit the journal and write back all dirty blocks.
+    pub fn sync(&mut self) {
+        self.journal.commit(&self.block_device);
+        self.freed_data.clear();
+        block_cache_sync_all();
     }
 
-    pub fn dealloc_data(&mut self, block_id: u32) {
-        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+    pub fn inode_count(&self) -> u32 {
+        self.inode_bitmap.maximum() as u32
+    }
+
+    /// Block IDs of the data area.
+    pub fn data_area(&self) -> Range<u32> {
+        let data_area_blocks = get_block_cache(0, Arc::clone(&self.block_device))
             .lock()
-            .modify(0, |data_block: &mut DataBlock| {
-                data_block.iter_mut().for_each(|p| {
-                    *p = 0;
-                })
-            });
-        self.data_bitmap.dealloc(
+            .read(0, |super_block: &SuperBlock| super_block.data_area_blocks);
+        self.data_area_start_block..self.data_area_start_block + data_area_blocks
+    }
+
+    pub fn is_inode_allocated(&self, inode_id: u32) -> bool {
+        self.inode_bitmap
+            .is_allocated(&self.block_device, inode_id as usize)
+    }
+
+    /// Allocate or free an inode in the bitmap without touching the inode,
+    /// which is only for repairing the file system.
+    pub fn set_inode_allocated(&mut self, inode_id: u32, allocated: bool) {
+        self.begin_op();
+        self.inode_bitmap
+            .set(&self.block_device, inode_id as usize, allocated);
+    }
+
+    /// Blocks of the data bitmap holding the bits of `block_ids`.
+    pub fn data_bitmap_blocks_of(&self, block_ids: &[u32]) -> usize {
+        let mut bitmap_blocks: Vec<usize> = block_ids
+            .iter()
+            .map(|block_id| Bitmap::block_of((block_id - self.data_area_start_block) as usize))
+            .collect();
+        bitmap_blocks.sort_unstable();
+        bitmap_blocks.dedup();
+        bitmap_blocks.len()
+    }
+
+    /// `block_id` is a block ID of the data area, like those of `Inode::block_ids`.
+    pub fn is_data_allocated(&self, block_id: u32) -> bool {
+        self.data_bitmap.is_allocated(
             &self.block_device,
             (block_id - self.data_area_start_block) as usize,
         )
     }
+
+    /// Allocate or free a block of the data area in the bitmap, which is only
+    /// for repairing the file system.
+    pub fn set_data_allocated(&mut self, block_id: u32, allocated: bool) {
+        self.begin_op();
+        self.data_bitmap.set(
+            &self.block_device,
+            (block_id - self.data_area_start_block) as usize,
+            allocated,
+        );
+    }
+
+    /// Return None if all the inodes are used.
+    pub fn alloc_inode(&mut self) -> Option<u32> {
+        self.inode_bitmap
+            .alloc(&self.block_device)
+            .map(|inode_id| inode_id as u32)
+    }
+
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize)
+    }
+
+    /// Return a block ID not ID in the data area, the block is cleared to zero.
+    /// Return None if the data area is full.
+    pub fn alloc_data(&mut self) -> Option<u32> {
+        let bit = self.data_bitmap.alloc(&self.block_device)? as u32;
+        let block_id = bit + self.data_area_start_block;
+        let clear = |data_block: &mut DataBlock| {
+            data_block.iter_mut().for_each(|p| {
+                *p = 0;
+            })
+        };
+        let block_cache = get_block_cache(block_id as usize, Arc::clone(&self.block_device));
+        if self.freed_data.contains(&bit) {
+            // file data written later stays in the journal with the zeros
+            block_cache.lock().modify(0, clear);
+        } else {
+            block_cache.lock().modify_data(0, clear);
+        }
+        Some(block_id)
+    }
+
+    pub fn dealloc_data(&mut self, block_id: u32) {
+        let bit = block_id - self.data_area_start_block;
+        self.data_bitmap.dealloc(&self.block_device, bit as usize);
+        self.freed_data.insert(bit);
+    }
 }
@@ -0,0 +1,95 @@
+use super::{
+    block_cache_capacity, block_cache_sync_all, get_block_cache, journaled_block_caches,
+    BlockDevice, JournalHeader, BLOCK_SZ, JOURNAL_CAPACITY,
+};
+use alloc::sync::Arc;
+
+/// Most blocks journaled by a single operation, like a write of 16 data blocks
+/// freed by the running transaction before, which modifies the inode, at most
+/// 8 index blocks and a bitmap block for each block allocated. Larger writes
+/// and frees are split into several operations.
+pub const MAX_OP_BLOCKS: usize = 16 + 1 + 8 + (16 + 8);
+
+type DataBlock = [u8; BLOCK_SZ];
+
+/// A write-ahead journal of the metadata.
+///
+/// Modified metadata blocks stay in the block cache until the running
+/// transaction is committed, so a crash leaves either all or none of the
+/// operations in it. File data is written back before the commit.
+pub struct Journal {
+    start_block: usize,
+    capacity: usize,
+}
+
+impl Journal {
+    /// The journal takes `blocks` blocks from `start_block`.
+    pub fn new(start_block: usize, blocks: usize) -> Self {
+        assert!(blocks > MAX_OP_BLOCKS, "Journal too small!");
+        Self {
+            start_block,
+            capacity: (blocks - 1).min(JOURNAL_CAPACITY),
+        }
+    }
+
+    /// Called before each operation modifying the file system, commit the
+    /// running transaction if the operation may not fit in it. Return whether
+    /// it was committed.
+    pub fn begin_op(&self, block_device: &Arc<dyn BlockDevice>) -> bool {
+        // the other half of the block cache is left for the blocks in use
+        let limit = self.capacity.min(block_cache_capacity() / 2);
+        if journaled_block_caches().len() + MAX_OP_BLOCKS > limit {
+            self.commit(block_device);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Commit the running transaction and write its blocks back, no operation
+    /// should be in progress.
+    pub fn commit(&self, block_device: &Arc<dyn BlockDevice>) {
+        let caches = journaled_block_caches();
+        if caches.is_empty() {
+            return;
+        }
+        // operations are split to fit in `MAX_OP_BLOCKS`, see `begin_op`
+        assert!(caches.len() <= self.capacity, "Operation too large!");
+        // the metadata must not refer to data which is not on the disk
+        block_cache_sync_all();
+        let mut header = JournalHeader::empty();
+        for (i, cache) in caches.iter().enumerate() {
+            let cache = cache.lock();
+            header.push(cache.block_id());
+            cache.read(0, |data_block: &DataBlock| {
+                block_device.write_block(self.start_block + 1 + i, data_block)
+            });
+        }
+        block_device.write_block(self.start_block, header.as_bytes());
+        for cache in caches.iter() {
+            cache.lock().checkpoint();
+        }
+        block_device.write_block(self.start_block, JournalHeader::empty().as_bytes());
+    }
+
+    /// Redo the committed transaction which was not completely written back.
+    pub fn replay(&self, block_device: &Arc<dyn BlockDevice>) {
+        let mut header = JournalHeader::empty();
+        block_device.read_block(self.start_block, header.as_bytes_mut());
+        if header.block_ids().is_empty() {
+            return;
+        }
+        let mut log_block = [0u8; BLOCK_SZ];
+        for (i, block_id) in header.block_ids().iter().enumerate() {
+            block_device.read_block(self.start_block + 1 + i, &mut log_block);
+            // through the block cache since it may hold the block already
+            get_block_cache(*block_id as usize, Arc::clone(block_device))
+                .lock()
+                .modify_data(0, |data_block: &mut DataBlock| {
+                    data_block.copy_from_slice(&log_block)
+                });
+        }
+        block_cache_sync_all();
+        block_device.write_block(self.start_block, JournalHeader::empty().as_bytes());
+    }
+}
@@ -1,36 +1,89 @@
-use super::{get_block_cache, BlockDevice, BLOCK_SZ};
+use super::{get_block_cache, now, BlockCache, BlockDevice, BLOCK_SZ};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter, Result};
+use core::ops::Range;
+use spin::Mutex;
 
-const EFS_MAGIC: u32 = 0x3b800001;
-const INODE_DIRECT_COUNT: usize = 28;
-const NAME_LENGTH_LIMIT: usize = 27;
+const EFS_MAGIC: u32 = 0x3b800004;
+const JOURNAL_MAGIC: u32 = 0x6a726e6c;
+/// Leave room for the timestamps so that a `DiskInode` still takes 128 bytes,
+/// the last ones hold the triple indirect block and the owner instead if the
+/// inode has their features.
+const INODE_DIRECT_COUNT: usize = 24;
+/// Names of the fixed 32-byte directory entries, see `DirEntry`.
+pub const NAME_LENGTH_LIMIT: usize = 27;
+/// Names of the variable-length directory entries, see `LongDirEntry`.
+pub const LONG_NAME_LENGTH_LIMIT: usize = 255;
+/// Directories hold `LongDirEntry` instead of `DirEntry`.
+pub const FEATURE_LONG_NAMES: u32 = 1;
+/// Inodes hold a triple indirect block in place of a direct block.
+pub const FEATURE_TRIPLE_INDIRECT: u32 = 2;
+/// Inodes hold an owner and a mode in place of a direct block, files of older
+/// images belong to root.
+pub const FEATURE_OWNERS: u32 = 4;
+/// Features of the images made now.
+pub const FEATURES: u32 = FEATURE_LONG_NAMES | FEATURE_TRIPLE_INDIRECT | FEATURE_OWNERS;
+/// Features changing the layout of an inode, which records them.
+const INODE_FEATURES: u32 = FEATURE_TRIPLE_INDIRECT | FEATURE_OWNERS;
+/// Seconds after which a read updates an access time later than the last
+/// change anyway.
+const ATIME_INTERVAL: u32 = 24 * 60 * 60;
+/// Targets of symbolic links, like `PATH_MAX` of Linux without the end `\0`.
+pub const SYMLINK_LENGTH_LIMIT: usize = 4095;
 const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
 const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
-const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
-const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
-#[allow(unused)]
-const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+const INODE_INDIRECT3_COUNT: usize = INODE_INDIRECT2_COUNT * INODE_INDIRECT1_COUNT;
+/// About 1 GiB on the images made now.
+pub const MAX_FILE_SIZE: usize = max_file_size(FEATURES);
+
+/// Data blocks below 0 to 3 levels of index blocks of an inode with `features`.
+const fn level_counts(features: u32) -> [usize; 4] {
+    let triple_indirect = features & FEATURE_TRIPLE_INDIRECT != 0;
+    let owners = features & FEATURE_OWNERS != 0;
+    [
+        INODE_DIRECT_COUNT - triple_indirect as usize - owners as usize,
+        INODE_INDIRECT1_COUNT,
+        INODE_INDIRECT2_COUNT,
+        if triple_indirect {
+            INODE_INDIRECT3_COUNT
+        } else {
+            0
+        },
+    ]
+}
+
+/// Largest file on an image with `features`, about 8 MiB without the triple
+/// indirect block.
+pub const fn max_file_size(features: u32) -> usize {
+    let [direct, indirect1, indirect2, indirect3] = level_counts(features);
+    (direct + indirect1 + indirect2 + indirect3) * BLOCK_SZ
+}
 
 #[repr(C)]
 pub struct SuperBlock {
     magic: u32,
     pub total_blocks: u32,
+    /// The journal follows the super block, see `JournalHeader`.
+    pub journal_blocks: u32,
     pub inode_bitmap_blocks: u32,
     pub inode_area_blocks: u32,
     pub data_bitmap_blocks: u32,
     pub data_area_blocks: u32,
+    /// Images made before the field are cleared there and have no features.
+    pub features: u32,
 }
 
 impl Debug for SuperBlock {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         f.debug_struct("SuperBlock")
             .field("total_blocks", &self.total_blocks)
+            .field("journal_blocks", &self.journal_blocks)
             .field("inode_bitmap_blocks", &self.inode_bitmap_blocks)
             .field("inode_area_blocks", &self.inode_area_blocks)
             .field("data_bitmap_blocks", &self.data_bitmap_blocks)
             .field("data_area_blocks", &self.data_area_blocks)
+            .field("features", &self.features)
             .finish()
     }
 }
@@ -39,6 +92,7 @@ impl SuperBlock {
     pub fn initialize(
         &mut self,
         total_blocks: u32,
+        journal_blocks: u32,
         inode_bitmap_blocks: u32,
         inode_area_blocks: u32,
         data_bitmap_blocks: u32,
@@ -47,10 +101,12 @@ impl SuperBlock {
         *self = Self {
             magic: EFS_MAGIC,
             total_blocks,
+            journal_blocks,
             inode_bitmap_blocks,
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            features: 0,
         }
     }
     pub fn is_valid(&self) -> bool {
@@ -58,237 +114,467 @@ impl SuperBlock {
     }
 }
 
+/// Number of blocks a transaction of the journal can hold at most.
+pub const JOURNAL_CAPACITY: usize = BLOCK_SZ / 4 - 2;
+
+/// The first block of the journal, the copies of the blocks follow it.
+///
+/// A transaction is committed once the header holding its block ids is written,
+/// and it is replayed on mount until the header is cleared.
+#[repr(C)]
+pub struct JournalHeader {
+    magic: u32,
+    count: u32,
+    block_ids: [u32; JOURNAL_CAPACITY],
+}
+
+impl JournalHeader {
+    pub fn empty() -> Self {
+        Self {
+            magic: JOURNAL_MAGIC,
+            count: 0,
+            block_ids: [0; JOURNAL_CAPACITY],
+        }
+    }
+    pub fn push(&mut self, block_id: usize) {
+        self.block_ids[self.count as usize] = block_id as u32;
+        self.count += 1;
+    }
+    /// Home locations of the logged blocks, nothing for a cleared header.
+    pub fn block_ids(&self) -> &[u32] {
+        if self.magic == JOURNAL_MAGIC && self.count as usize <= JOURNAL_CAPACITY {
+            &self.block_ids[..self.count as usize]
+        } else {
+            &[]
+        }
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, BLOCK_SZ) }
+    }
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as *mut u8, BLOCK_SZ) }
+    }
+}
+
 #[derive(PartialEq)]
 pub enum DiskInodeType {
     File,
     Directory,
+    /// The data is the target path.
+    Symlink,
 }
 
 type IndirectBlock = [u32; BLOCK_SZ / 4];
 type DataBlock = [u8; BLOCK_SZ];
 
+/// The index blocks last followed at each level, so that mapping consecutive
+/// data blocks looks each index block up in the block cache only once.
+struct IndexBlocks {
+    levels: [Option<(u32, Arc<Mutex<BlockCache>>)>; 3],
+}
+
+impl IndexBlocks {
+    fn new() -> Self {
+        Self {
+            levels: [None, None, None],
+        }
+    }
+    fn get(
+        &mut self,
+        level: usize,
+        block_id: u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> &Arc<Mutex<BlockCache>> {
+        match &self.levels[level] {
+            Some((cached_id, _)) if *cached_id == block_id => {}
+            _ => {
+                let block_cache = get_block_cache(block_id as usize, Arc::clone(block_device));
+                self.levels[level] = Some((block_id, block_cache));
+            }
+        }
+        &self.levels[level].as_ref().unwrap().1
+    }
+}
+
 #[repr(C)]
 pub struct DiskInode {
     pub size: u32,
-    pub direct: [u32; INODE_DIRECT_COUNT],
+    /// The direct blocks, then the triple indirect block and the owner if the
+    /// inode has their features, see `direct_count`.
+    direct: [u32; INODE_DIRECT_COUNT],
     pub indirect1: u32,
     pub indirect2: u32,
+    /// Number of directory entries naming this inode, `.` and `..` excluded.
+    pub nlink: u32,
+    /// Seconds since the Unix epoch of the last read.
+    pub atime: u32,
+    /// Seconds since the Unix epoch of the last change of the data.
+    pub mtime: u32,
+    /// Seconds since the Unix epoch of the last change of the data or the inode.
+    pub ctime: u32,
     type_: DiskInodeType,
+    /// The layout of the inode from `INODE_FEATURES` of the image it was made
+    /// on, cleared on older images.
+    features: u8,
+    /// Permission bits together with the setuid, setgid and sticky bits, with
+    /// `FEATURE_OWNERS`.
+    mode: u16,
 }
 
 impl DiskInode {
-    /// indirect1 and indirect2 block are allocated only when they are needed.
-    pub fn initialize(&mut self, type_: DiskInodeType) {
+    /// indirect1, indirect2 and indirect3 block are allocated only when they are needed.
+    ///
+    /// The inode is laid out for `features` of its image, owned by root, with
+    /// the usual mode of its type.
+    pub fn initialize(&mut self, type_: DiskInodeType, features: u32) {
         self.size = 0;
+        self.features = (features & INODE_FEATURES) as u8;
+        // the triple indirect block and the owner too
         self.direct.iter_mut().for_each(|v| *v = 0);
         self.indirect1 = 0;
         self.indirect2 = 0;
+        self.nlink = 1;
+        let now = now();
+        self.atime = now;
+        self.mtime = now;
+        self.ctime = now;
+        self.mode = match type_ {
+            DiskInodeType::File => 0o644,
+            DiskInodeType::Directory => 0o755,
+            DiskInodeType::Symlink => 0o777,
+        };
         self.type_ = type_;
     }
+    fn has(&self, feature: u32) -> bool {
+        self.features as u32 & feature != 0
+    }
+    /// Number of the direct blocks, the triple indirect block follows them.
+    fn direct_count(&self) -> usize {
+        level_counts(self.features as u32)[0]
+    }
+    /// The first data block beyond each level of index blocks.
+    fn bounds(&self) -> [usize; 4] {
+        let mut bounds = level_counts(self.features as u32);
+        for depth in 1..bounds.len() {
+            bounds[depth] += bounds[depth - 1];
+        }
+        bounds
+    }
+    /// Permission bits together with the setuid, setgid and sticky bits. Any
+    /// file of older images can be run by anyone.
+    pub fn mode(&self) -> u16 {
+        if self.has(FEATURE_OWNERS) {
+            self.mode
+        } else if self.is_symlink() {
+            0o777
+        } else {
+            0o755
+        }
+    }
+    /// The owner user and group.
+    pub fn owner(&self) -> (u16, u16) {
+        if self.has(FEATURE_OWNERS) {
+            let owner = self.direct[INODE_DIRECT_COUNT - 1];
+            (owner as u16, (owner >> 16) as u16)
+        } else {
+            (0, 0)
+        }
+    }
+    /// Return false without `FEATURE_OWNERS`.
+    pub fn set_mode(&mut self, mode: u16) -> bool {
+        if !self.has(FEATURE_OWNERS) {
+            return false;
+        }
+        self.mode = mode;
+        true
+    }
+    /// Return false without `FEATURE_OWNERS`.
+    pub fn set_owner(&mut self, uid: u16, gid: u16) -> bool {
+        if !self.has(FEATURE_OWNERS) {
+            return false;
+        }
+        self.direct[INODE_DIRECT_COUNT - 1] = uid as u32 | (gid as u32) << 16;
+        true
+    }
+    /// The change time is updated along with the modification time.
+    pub fn update_mtime(&mut self) {
+        self.mtime = now();
+        self.ctime = self.mtime;
+    }
+    pub fn update_ctime(&mut self) {
+        self.ctime = now();
+    }
+    /// Whether a read at `now` should update the access time, which is only
+    /// done when it is not after the last change or a day old, like relatime
+    /// on Linux.
+    pub fn atime_outdated(&self, now: u32) -> bool {
+        self.atime != now
+            && (self.atime <= self.mtime
+                || self.atime <= self.ctime
+                || now.saturating_sub(self.atime) >= ATIME_INTERVAL)
+    }
     pub fn is_dir(&self) -> bool {
         self.type_ == DiskInodeType::Directory
     }
-    #[allow(unused)]
     pub fn is_file(&self) -> bool {
         self.type_ == DiskInodeType::File
     }
+    pub fn is_symlink(&self) -> bool {
+        self.type_ == DiskInodeType::Symlink
+    }
     /// Return block number correspond to size.
     pub fn data_blocks(&self) -> u32 {
         Self::_data_blocks(self.size)
     }
-    fn _data_blocks(size: u32) -> u32 {
+    pub(crate) fn _data_blocks(size: u32) -> u32 {
         (size + BLOCK_SZ as u32 - 1) / BLOCK_SZ as u32
     }
-    /// Return number of blocks needed include indirect1/2.
-    pub fn total_blocks(size: u32) -> u32 {
-        let data_blocks = Self::_data_blocks(size) as usize;
-        let mut total = data_blocks as usize;
-        // indirect1
-        if data_blocks > INODE_DIRECT_COUNT {
-            total += 1;
+    /// The new part of the file is a hole until it is written.
+    pub fn increase_size(&mut self, new_size: u32) {
+        assert!(new_size >= self.size);
+        self.size = new_size;
+    }
+    /// Return the levels of index blocks above the `inner_id`th data block and
+    /// the entry followed at each of them, or its entry in `direct` if there
+    /// are none.
+    fn locate(&self, inner_id: usize) -> (usize, [usize; 3]) {
+        let [direct_bound, indirect1_bound, indirect2_bound, indirect3_bound] = self.bounds();
+        if inner_id < direct_bound {
+            (0, [inner_id, 0, 0])
+        } else if inner_id < indirect1_bound {
+            (1, [inner_id - direct_bound, 0, 0])
+        } else if inner_id < indirect2_bound {
+            let last = inner_id - indirect1_bound;
+            (
+                2,
+                [
+                    last / INODE_INDIRECT1_COUNT,
+                    last % INODE_INDIRECT1_COUNT,
+                    0,
+                ],
+            )
+        } else {
+            assert!(inner_id < indirect3_bound, "File too large!");
+            let last = inner_id - indirect2_bound;
+            (
+                3,
+                [
+                    last / INODE_INDIRECT2_COUNT,
+                    last / INODE_INDIRECT1_COUNT % INODE_INDIRECT1_COUNT,
+                    last % INODE_INDIRECT1_COUNT,
+                ],
+            )
         }
-        // indirect2
-        if data_blocks > INDIRECT1_BOUND {
-            total += 1;
-            // sub indirect1
-            total +=
-                (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT;
+    }
+    /// The top index block of `depth` levels.
+    fn indirect(&self, depth: usize) -> u32 {
+        match depth {
+            1 => self.indirect1,
+            2 => self.indirect2,
+            _ => self.direct[self.direct_count()],
         }
-        total as u32
     }
-    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
-        assert!(new_size >= self.size);
-        Self::total_blocks(new_size) - Self::total_blocks(self.size)
-    }
-    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
-        let inner_id = inner_id as usize;
-        if inner_id < INODE_DIRECT_COUNT {
-            self.direct[inner_id]
-        } else if inner_id < INDIRECT1_BOUND {
-            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect_block: &IndirectBlock| {
-                    indirect_block[inner_id - INODE_DIRECT_COUNT]
-                })
-        } else {
-            let last = inner_id - INDIRECT1_BOUND;
-            let indirect1 = get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect2: &IndirectBlock| {
-                    indirect2[last / INODE_INDIRECT1_COUNT]
-                });
-            get_block_cache(indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect1: &IndirectBlock| {
-                    indirect1[last % INODE_INDIRECT1_COUNT]
-                })
+    fn indirect_mut(&mut self, depth: usize) -> &mut u32 {
+        match depth {
+            1 => &mut self.indirect1,
+            2 => &mut self.indirect2,
+            _ => {
+                let slot = self.direct_count();
+                &mut self.direct[slot]
+            }
         }
     }
-    pub fn increase_size(
+    /// Map the data blocks in `range` to their block ids, 0 for a hole.
+    pub fn get_block_ids(
+        &self,
+        range: Range<usize>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Vec<u32> {
+        let mut index_blocks = IndexBlocks::new();
+        range
+            .map(|inner_id| {
+                let (depth, slots) = self.locate(inner_id);
+                if depth == 0 {
+                    return self.direct[slots[0]];
+                }
+                let mut block_id = self.indirect(depth);
+                for (level, slot) in slots[..depth].iter().enumerate() {
+                    if block_id == 0 {
+                        break;
+                    }
+                    block_id = index_blocks
+                        .get(level, block_id, block_device)
+                        .lock()
+                        .read(0, |index_block: &IndirectBlock| index_block[*slot]);
+                }
+                block_id
+            })
+            .collect()
+    }
+    /// Allocate the holes among the data blocks in `range` with `alloc`,
+    /// together with the missing index blocks above them. Return false once
+    /// `alloc` fails, the blocks allocated before are kept.
+    pub fn alloc_blocks(
         &mut self,
-        new_size: u32,
-        new_blocks: Vec<u32>,
+        range: Range<usize>,
+        mut alloc: impl FnMut() -> Option<u32>,
         block_device: &Arc<dyn BlockDevice>,
-    ) {
-        let mut current_blocks = self.data_blocks();
-        self.size = new_size;
-        let mut total_blocks = self.data_blocks();
-        let mut new_blocks = new_blocks.into_iter();
-        // fill direct
-        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT as u32) {
-            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
-            current_blocks += 1;
-        }
-        // alloc indirect1
-        if total_blocks > INODE_DIRECT_COUNT as u32 {
-            if current_blocks == INODE_DIRECT_COUNT as u32 {
-                self.indirect1 = new_blocks.next().unwrap();
+    ) -> bool {
+        let mut index_blocks = IndexBlocks::new();
+        for inner_id in range {
+            let (depth, slots) = self.locate(inner_id);
+            if depth == 0 {
+                if self.direct[slots[0]] == 0 {
+                    match alloc() {
+                        Some(block_id) => self.direct[slots[0]] = block_id,
+                        None => return false,
+                    }
+                }
+                continue;
             }
-            current_blocks -= INODE_DIRECT_COUNT as u32;
-            total_blocks -= INODE_DIRECT_COUNT as u32;
-        } else {
-            return;
-        }
-        // fill indirect1
-        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect1: &mut IndirectBlock| {
-                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
-                    indirect1[current_blocks as usize] = new_blocks.next().unwrap();
-                    current_blocks += 1;
+            if self.indirect(depth) == 0 {
+                match alloc() {
+                    Some(block_id) => *self.indirect_mut(depth) = block_id,
+                    None => return false,
                 }
-            });
-        // alloc indirect2
-        if total_blocks > INODE_INDIRECT1_COUNT as u32 {
-            if current_blocks == INODE_INDIRECT1_COUNT as u32 {
-                self.indirect2 = new_blocks.next().unwrap();
             }
-            current_blocks -= INODE_INDIRECT1_COUNT as u32;
-            total_blocks -= INODE_INDIRECT1_COUNT as u32;
-        } else {
-            return;
-        }
-        // fill indirect2 from (a0, b0) -> (a1, b1)
-        let mut a0 = current_blocks as usize / INODE_INDIRECT1_COUNT;
-        let mut b0 = current_blocks as usize % INODE_INDIRECT1_COUNT;
-        let a1 = total_blocks as usize / INODE_INDIRECT1_COUNT;
-        let b1 = total_blocks as usize % INODE_INDIRECT1_COUNT;
-        // alloc low-level indirect1
-        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect2: &mut IndirectBlock| {
-                while (a0 < a1) || (a0 == a1 && b0 < b1) {
-                    if b0 == 0 {
-                        indirect2[a0] = new_blocks.next().unwrap();
-                    }
-                    // fill current
-                    get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
+            let mut block_id = self.indirect(depth);
+            for (level, slot) in slots[..depth].iter().enumerate() {
+                let index_block = index_blocks.get(level, block_id, block_device);
+                block_id = index_block
+                    .lock()
+                    .read(0, |index_block: &IndirectBlock| index_block[*slot]);
+                // the data block or an index block below
+                if block_id == 0 {
+                    block_id = match alloc() {
+                        Some(block_id) => block_id,
+                        None => return false,
+                    };
+                    index_block
                         .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            indirect1[b0] = new_blocks.next().unwrap();
+                        .modify(0, |index_block: &mut IndirectBlock| {
+                            index_block[*slot] = block_id;
                         });
-                    // move to next
-                    b0 += 1;
-                    if b0 == INODE_INDIRECT1_COUNT {
-                        b0 = 0;
-                        a0 += 1;
-                    }
                 }
-            });
+            }
+        }
+        true
     }
 
-    /// Clear size to zero and return blocks that should be deallocated.
-    ///
-    /// We will clear the block contents to zero later.
-    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+    /// Return the data blocks in `range` together with the index blocks whose
+    /// first data block is in it, holes are skipped.
+    fn block_ids_in(&self, range: Range<usize>, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
         let mut v: Vec<u32> = Vec::new();
-        let mut data_blocks = self.data_blocks() as usize;
-        self.size = 0;
-        let mut current_blocks = 0usize;
-        // direct
-        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
-            v.push(self.direct[current_blocks]);
-            self.direct[current_blocks] = 0;
-            current_blocks += 1;
+        let mut index_blocks = IndexBlocks::new();
+        let mut inner_id = range.start;
+        'data_blocks: while inner_id < range.end {
+            let (depth, slots) = self.locate(inner_id);
+            let mut block_id = if depth == 0 {
+                self.direct[slots[0]]
+            } else {
+                self.indirect(depth)
+            };
+            // the block below `level` levels of index blocks, the last one is
+            // the data block
+            for level in 0..=depth {
+                let below = &slots[level..depth];
+                if block_id == 0 {
+                    // skip the data blocks below the hole
+                    let offset = below
+                        .iter()
+                        .fold(0, |offset, slot| offset * INODE_INDIRECT1_COUNT + slot);
+                    inner_id += INODE_INDIRECT1_COUNT.pow(below.len() as u32) - offset;
+                    continue 'data_blocks;
+                }
+                if below.iter().all(|slot| *slot == 0) {
+                    v.push(block_id);
+                }
+                if level < depth {
+                    block_id = index_blocks
+                        .get(level, block_id, block_device)
+                        .lock()
+                        .read(0, |index_block: &IndirectBlock| index_block[slots[level]]);
+                }
+            }
+            inner_id += 1;
         }
-        // indirect1 block
-        if data_blocks > INODE_DIRECT_COUNT {
-            v.push(self.indirect1);
-            data_blocks -= INODE_DIRECT_COUNT;
-            current_blocks = 0;
-        } else {
-            return v;
+        v
+    }
+
+    /// Return the data blocks together with the index blocks.
+    pub fn block_ids(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        self.block_ids_in(0..self.data_blocks() as usize, block_device)
+    }
+
+    /// Blocks freed by shrinking the file to `new_size`.
+    pub fn block_ids_after(&self, new_size: u32, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let new_blocks = Self::_data_blocks(new_size) as usize;
+        self.block_ids_in(new_blocks..self.data_blocks() as usize, block_device)
+    }
+
+    /// Shrink the file to `new_size` and return blocks that should be
+    /// deallocated.
+    ///
+    /// The blocks are cleared to zero when they are allocated again, and so is
+    /// the rest of the last block kept.
+    pub fn decrease_size(
+        &mut self,
+        new_size: u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Vec<u32> {
+        assert!(new_size <= self.size);
+        let v = self.block_ids_after(new_size, block_device);
+        let old_blocks = self.data_blocks() as usize;
+        let new_blocks = Self::_data_blocks(new_size) as usize;
+        let tail = new_size as usize % BLOCK_SZ;
+        if tail > 0 {
+            let block_id = self.get_block_ids(new_blocks - 1..new_blocks, block_device)[0];
+            if block_id != 0 {
+                get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .modify_data(0, |data_block: &mut DataBlock| {
+                        data_block[tail..].iter_mut().for_each(|byte| *byte = 0);
+                    });
+            }
         }
-        // indirect1
-        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect1: &mut IndirectBlock| {
-                while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
-                    v.push(indirect1[current_blocks]);
-                    //indirect1[current_blocks] = 0;
-                    current_blocks += 1;
+        // the freed blocks become holes in the index blocks kept, which are
+        // those above the first freed data block with data blocks before it
+        if new_blocks < old_blocks {
+            let (depth, slots) = self.locate(new_blocks);
+            let mut block_id = if depth == 0 { 0 } else { self.indirect(depth) };
+            for level in 0..depth {
+                if block_id == 0 || slots[level..depth].iter().all(|slot| *slot == 0) {
+                    break;
                 }
-            });
-        self.indirect1 = 0;
-        // indirect2 block
-        if data_blocks > INODE_INDIRECT1_COUNT {
-            v.push(self.indirect2);
-            data_blocks -= INODE_INDIRECT1_COUNT;
+                // the entry followed is kept too unless its blocks all go
+                let kept = slots[level + 1..depth].iter().any(|slot| *slot != 0);
+                let first_freed = slots[level] + kept as usize;
+                block_id = get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .modify(0, |index_block: &mut IndirectBlock| {
+                        index_block[first_freed..]
+                            .iter_mut()
+                            .for_each(|block_id| *block_id = 0);
+                        index_block[slots[level]]
+                    });
+            }
+        }
+        self.size = new_size;
+        let direct_count = self.direct_count();
+        self.direct[..direct_count]
+            .iter_mut()
+            .skip(new_blocks)
+            .for_each(|block_id| *block_id = 0);
+        let depths = if self.has(FEATURE_TRIPLE_INDIRECT) {
+            3
         } else {
-            return v;
+            2
+        };
+        for (depth, bound) in self.bounds()[..depths].iter().enumerate() {
+            if new_blocks <= *bound {
+                *self.indirect_mut(depth + 1) = 0;
+            }
         }
-        // indirect2
-        assert!(data_blocks <= INODE_INDIRECT2_COUNT);
-        let a1 = data_blocks / INODE_INDIRECT1_COUNT;
-        let b1 = data_blocks % INODE_INDIRECT1_COUNT;
-        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
-            .lock()
-            .modify(0, |indirect2: &mut IndirectBlock| {
-                // full indirect1 blocks
-                for entry in indirect2.iter_mut().take(a1) {
-                    v.push(*entry);
-                    get_block_cache(*entry as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            for entry in indirect1.iter() {
-                                v.push(*entry);
-                            }
-                        });
-                }
-                // last indirect1 block
-                if b1 > 0 {
-                    v.push(indirect2[a1]);
-                    get_block_cache(indirect2[a1] as usize, Arc::clone(block_device))
-                        .lock()
-                        .modify(0, |indirect1: &mut IndirectBlock| {
-                            for entry in indirect1.iter().take(b1) {
-                                v.push(*entry);
-                            }
-                        });
-                    //indirect2[a1] = 0;
-                }
-            });
-        self.indirect2 = 0;
         v
     }
     pub fn read_at(
@@ -303,6 +589,9 @@ impl DiskInode {
             return 0;
         }
         let mut start_block = start / BLOCK_SZ;
+        let first_block = start_block;
+        let block_ids =
+            self.get_block_ids(first_block..(end + BLOCK_SZ - 1) / BLOCK_SZ, block_device);
         let mut read_size = 0usize;
         loop {
             // calculate end of current block
@@ -311,15 +600,18 @@ impl DiskInode {
             // read and update read size
             let block_read_size = end_current_block - start;
             let dst = &mut buf[read_size..read_size + block_read_size];
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
-                Arc::clone(block_device),
-            )
-            .lock()
-            .read(0, |data_block: &DataBlock| {
-                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
-                dst.copy_from_slice(src);
-            });
+            let block_id = block_ids[start_block - first_block];
+            if block_id == 0 {
+                // a hole reads as zeros
+                dst.iter_mut().for_each(|byte| *byte = 0);
+            } else {
+                get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |data_block: &DataBlock| {
+                        let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                        dst.copy_from_slice(src);
+                    });
+            }
             read_size += block_read_size;
             // move to next block
             if end_current_block == end {
@@ -330,7 +622,7 @@ impl DiskInode {
         }
         read_size
     }
-    /// File size must be adjusted before.
+    /// File size must be adjusted and the blocks allocated before.
     pub fn write_at(
         &mut self,
         offset: usize,
@@ -340,7 +632,13 @@ impl DiskInode {
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
         assert!(start <= end);
+        if start == end {
+            return 0;
+        }
         let mut start_block = start / BLOCK_SZ;
+        let first_block = start_block;
+        let block_ids =
+            self.get_block_ids(first_block..(end + BLOCK_SZ - 1) / BLOCK_SZ, block_device);
         let mut write_size = 0usize;
         loop {
             // calculate end of current block
@@ -348,16 +646,21 @@ impl DiskInode {
             end_current_block = end_current_block.min(end);
             // write and update write size
             let block_write_size = end_current_block - start;
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
+            let block_cache = get_block_cache(
+                block_ids[start_block - first_block] as usize,
                 Arc::clone(block_device),
-            )
-            .lock()
-            .modify(0, |data_block: &mut DataBlock| {
+            );
+            let write = |data_block: &mut DataBlock| {
                 let src = &buf[write_size..write_size + block_write_size];
                 let dst = &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
                 dst.copy_from_slice(src);
-            });
+            };
+            // directory entries and link targets are metadata
+            if !self.is_file() {
+                block_cache.lock().modify(0, write);
+            } else {
+                block_cache.lock().modify_data(0, write);
+            }
             write_size += block_write_size;
             // move to next block
             if end_current_block == end {
@@ -399,6 +702,10 @@ impl DirEntry {
     pub fn as_bytes_mut(&mut self) -> &mut [u8] {
         unsafe { core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, DIRENT_SZ) }
     }
+    /// A free slot left by unlink, which can be reused by a new entry.
+    pub fn is_empty(&self) -> bool {
+        self.name[0] == 0
+    }
     pub fn name(&self) -> &str {
         let len = (0usize..).find(|i| self.name[*i] == 0).unwrap();
         core::str::from_utf8(&self.name[..len]).unwrap()
@@ -407,3 +714,88 @@ impl DirEntry {
         self.inode_number
     }
 }
+
+/// Bytes before the name of a `LongDirEntry`.
+pub const LONG_DIRENT_HEAD_SZ: usize = 7;
+
+/// A directory entry taking `rec_len` bytes, its name is stored in the first
+/// `name_len` bytes of `name`.
+///
+/// Entries are 4-byte aligned and never cross a block, the last entry of a
+/// block takes the rest of it. A removed entry is merged into the one before
+/// it in the block, or left with an empty name at the start of a block.
+#[repr(C)]
+pub struct LongDirEntry {
+    inode_number: u32,
+    rec_len: u16,
+    name_len: u8,
+    name: [u8; LONG_NAME_LENGTH_LIMIT],
+}
+
+impl LongDirEntry {
+    pub fn empty(rec_len: usize) -> Self {
+        Self::new("", 0, rec_len)
+    }
+    pub fn new(name: &str, inode_number: u32, rec_len: usize) -> Self {
+        let mut bytes = [0u8; LONG_NAME_LENGTH_LIMIT];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Self {
+            inode_number,
+            rec_len: rec_len as u16,
+            name_len: name.len() as u8,
+            name: bytes,
+        }
+    }
+    /// Bytes taken by an entry named `name`.
+    pub fn size_of(name: &str) -> usize {
+        (LONG_DIRENT_HEAD_SZ + name.len() + 3) & !3
+    }
+    /// The head and the name, which are all that is stored.
+    pub fn as_bytes(&self) -> &[u8] {
+        let len = LONG_DIRENT_HEAD_SZ + self.name_len as usize;
+        unsafe { core::slice::from_raw_parts(self as *const _ as usize as *const u8, len) }
+    }
+    /// Read the head first, the length then covers the name.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        let len = LONG_DIRENT_HEAD_SZ + self.name_len as usize;
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, len) }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.name_len == 0
+    }
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap()
+    }
+    pub fn inode_number(&self) -> u32 {
+        self.inode_number
+    }
+    pub fn set_inode_number(&mut self, inode_number: u32) {
+        self.inode_number = inode_number;
+    }
+    pub fn rec_len(&self) -> usize {
+        self.rec_len as usize
+    }
+    pub fn set_rec_len(&mut self, rec_len: usize) {
+        self.rec_len = rec_len as u16;
+    }
+    /// Whether the entry at `offset` of a directory of `size` bytes holds its
+    /// name and ends in its block, or a corrupted length would stop the walks
+    /// over the entries or run them past the end.
+    pub fn is_valid(&self, offset: usize, size: usize) -> bool {
+        let name_len = self.name_len as usize;
+        let rec_len = self.rec_len();
+        rec_len & 3 == 0
+            && rec_len >= (LONG_DIRENT_HEAD_SZ + name_len + 3) & !3
+            && offset % BLOCK_SZ + rec_len <= BLOCK_SZ
+            && offset + rec_len <= size
+            && core::str::from_utf8(&self.name[..name_len]).is_ok()
+    }
+    /// Bytes left after the entry, all of them for an empty entry.
+    pub fn free_len(&self) -> usize {
+        if self.is_empty() {
+            self.rec_len()
+        } else {
+            self.rec_len() - Self::size_of(self.name())
+        }
+    }
+}
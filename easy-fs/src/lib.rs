@@ -5,14 +5,26 @@ extern crate alloc;
 mod bitmap;
 mod block_cache;
 mod block_dev;
+mod clock;
 mod efs;
+mod journal;
 mod layout;
 mod vfs;
 
 pub const BLOCK_SZ: usize = 512;
 use bitmap::Bitmap;
-use block_cache::{block_cache_sync_all, get_block_cache};
+pub use block_cache::set_block_cache_capacity;
+use block_cache::{
+    block_cache_capacity, block_cache_sync_all, get_block_cache, journaled_block_caches, BlockCache,
+};
 pub use block_dev::BlockDevice;
+use clock::now;
+pub use clock::set_clock;
 pub use efs::EasyFileSystem;
+use journal::{Journal, MAX_OP_BLOCKS};
 use layout::*;
-pub use vfs::Inode;
+pub use layout::{
+    FEATURES, FEATURE_LONG_NAMES, FEATURE_OWNERS, FEATURE_TRIPLE_INDIRECT, MAX_FILE_SIZE,
+    SYMLINK_LENGTH_LIMIT,
+};
+pub use vfs::{Inode, Metadata};
@@ -1,35 +1,95 @@
 use super::{
-    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
-    EasyFileSystem, DIRENT_SZ,
+    get_block_cache, now, BlockDevice, DirEntry, DiskInode, DiskInodeType, EasyFileSystem,
+    LongDirEntry, BLOCK_SZ, DIRENT_SZ, MAX_OP_BLOCKS, SYMLINK_LENGTH_LIMIT,
 };
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
+use core::ops::Range;
 use spin::{Mutex, MutexGuard};
 
+/// Largest part of a write done in one operation of the journal, see
+/// `MAX_OP_BLOCKS` for its metadata.
+const OP_WRITE_SIZE: usize = 16 * BLOCK_SZ;
+/// Most blocks freed in one operation of the journal, fewer if their bits are
+/// spread over too many blocks of the data bitmap.
+const OP_FREE_BLOCKS: usize = 4096;
+
 pub struct Inode {
+    inode_id: u32,
     block_id: usize,
     block_offset: usize,
+    /// Directories hold `LongDirEntry` instead of `DirEntry`.
+    long_names: bool,
     fs: Arc<Mutex<EasyFileSystem>>,
     block_device: Arc<dyn BlockDevice>,
+    /// Counted among the handles of the inode, which keep it after its last
+    /// link is removed. Inodes built inside operations are not.
+    handle: bool,
+}
+
+/// Attributes of an inode, the times are seconds since the Unix epoch.
+pub struct Metadata {
+    pub inode_id: u32,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    /// Permission bits together with the setuid, setgid and sticky bits.
+    pub mode: u16,
+    pub uid: u16,
+    pub gid: u16,
+    pub size: u32,
+    pub nlink: u32,
+    /// Number of blocks taken by the data together with the index blocks.
+    pub blocks: u32,
+    pub atime: u32,
+    pub mtime: u32,
+    pub ctime: u32,
 }
 
 impl Inode {
     /// We should not acquire efs lock here.
     pub fn new(
+        inode_id: u32,
         block_id: u32,
         block_offset: usize,
+        long_names: bool,
         fs: Arc<Mutex<EasyFileSystem>>,
         block_device: Arc<dyn BlockDevice>,
     ) -> Self {
         Self {
+            inode_id,
             block_id: block_id as usize,
             block_offset,
+            long_names,
             fs,
             block_device,
+            handle: false,
         }
     }
 
+    /// Turn an inode counted by `EasyFileSystem::open_inode` into a handle.
+    pub(crate) fn into_handle(mut self) -> Self {
+        self.handle = true;
+        self
+    }
+
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    /// Acquire the efs lock for an operation which modifies the file system.
+    /// Unlinked inodes closed before are freed first.
+    fn begin_op(&self) -> MutexGuard<'_, EasyFileSystem> {
+        let mut fs = self.fs.lock();
+        fs.begin_op();
+        while let Some(inode_id) = fs.take_orphan() {
+            self.inode_of(inode_id, &fs).free(&mut fs);
+            fs.begin_op();
+        }
+        fs
+    }
+
     fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
         get_block_cache(self.block_id, Arc::clone(&self.block_device))
             .lock()
@@ -42,145 +102,845 @@ impl Inode {
             .modify(self.block_offset, f)
     }
 
-    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
-        // assert it is a directory
-        assert!(disk_inode.is_dir());
-        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+    /// Build the inode with the given id, the efs lock must be held by caller.
+    fn inode_of(&self, inode_id: u32, fs: &MutexGuard<EasyFileSystem>) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Arc::new(Self::new(
+            inode_id,
+            block_id,
+            block_offset,
+            fs.long_names(),
+            self.fs.clone(),
+            self.block_device.clone(),
+        ))
+    }
+
+    /// Build a handle of the inode with the given id to be returned, the efs
+    /// lock must be held by caller.
+    fn handle_of(&self, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        fs.open_inode(inode_id);
+        Arc::new(
+            Self::new(
+                inode_id,
+                block_id,
+                block_offset,
+                fs.long_names(),
+                self.fs.clone(),
+                self.block_device.clone(),
+            )
+            .into_handle(),
+        )
+    }
+
+    fn dirent_at(&self, offset: usize, disk_inode: &DiskInode) -> DirEntry {
         let mut dirent = DirEntry::empty();
-        for i in 0..file_count {
-            assert_eq!(
-                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device,),
-                DIRENT_SZ,
-            );
-            if dirent.name() == name {
-                return Some(dirent.inode_number() as u32);
+        assert_eq!(
+            disk_inode.read_at(offset, dirent.as_bytes_mut(), &self.block_device,),
+            DIRENT_SZ,
+        );
+        dirent
+    }
+
+    fn long_dirent_at(&self, offset: usize, disk_inode: &DiskInode) -> LongDirEntry {
+        let mut dirent = LongDirEntry::empty(0);
+        // the head holds the length of the name read next
+        disk_inode.read_at(offset, dirent.as_bytes_mut(), &self.block_device);
+        disk_inode.read_at(offset, dirent.as_bytes_mut(), &self.block_device);
+        dirent
+    }
+
+    /// Call `f` with the offset, name and inode id of each entry in use until
+    /// it returns a value. The walk stops at a corrupted entry, see
+    /// `bad_dirent`.
+    fn find_map_dirent<V>(
+        &self,
+        disk_inode: &DiskInode,
+        mut f: impl FnMut(usize, &str, u32) -> Option<V>,
+    ) -> Option<V> {
+        let size = disk_inode.size as usize;
+        if !self.long_names {
+            return (0..size / DIRENT_SZ).find_map(|slot| {
+                let dirent = self.dirent_at(slot * DIRENT_SZ, disk_inode);
+                if dirent.is_empty() {
+                    None
+                } else {
+                    f(slot * DIRENT_SZ, dirent.name(), dirent.inode_number())
+                }
+            });
+        }
+        let mut offset = 0;
+        while offset < size {
+            let dirent = self.long_dirent_at(offset, disk_inode);
+            if !dirent.is_valid(offset, size) {
+                return None;
+            }
+            if !dirent.is_empty() {
+                let value = f(offset, dirent.name(), dirent.inode_number());
+                if value.is_some() {
+                    return value;
+                }
             }
+            offset += dirent.rec_len();
         }
         None
     }
 
+    /// Return the offset of the first corrupted entry of this directory, whose
+    /// length or name is out of bounds. The entries from it on are not seen.
+    pub fn bad_dirent(&self) -> Option<usize> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            if !self.long_names || !disk_inode.is_dir() {
+                return None;
+            }
+            let size = disk_inode.size as usize;
+            let mut offset = 0;
+            while offset < size {
+                let dirent = self.long_dirent_at(offset, disk_inode);
+                if !dirent.is_valid(offset, size) {
+                    return Some(offset);
+                }
+                offset += dirent.rec_len();
+            }
+            None
+        })
+    }
+
+    /// Return the offset and inode id of the entry `name`.
+    fn find_dirent(&self, name: &str, disk_inode: &DiskInode) -> Option<(usize, u32)> {
+        // assert it is a directory
+        assert!(disk_inode.is_dir());
+        self.find_map_dirent(disk_inode, |offset, dirent_name, inode_id| {
+            if dirent_name == name {
+                Some((offset, inode_id))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        self.find_dirent(name, disk_inode)
+            .map(|(_, inode_id)| inode_id)
+    }
+
+    /// Look up `name` in this directory, return None if this is not a directory.
+    ///
+    /// The inode returned is kept until it is dropped, even if its last link
+    /// is removed before.
     pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
-        let fs = self.fs.lock();
+        let mut fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode).map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
+            if !disk_inode.is_dir() {
+                return None;
+            }
+            self.find_inode_id(name, disk_inode)
         })
+        .map(|inode_id| self.handle_of(inode_id, &mut fs))
+    }
+
+    /// Walk a `/`-separated path component by component.
+    ///
+    /// Absolute paths start from the root directory and relative paths from
+    /// this inode. `.` and `..` are resolved through the real directory entries,
+    /// and symbolic links are not followed.
+    pub fn find_path(self: &Arc<Self>, path: &str) -> Option<Arc<Inode>> {
+        let mut inode = if path.starts_with('/') {
+            Arc::new(EasyFileSystem::root_inode(&self.fs))
+        } else {
+            Arc::clone(self)
+        };
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            inode = inode.find(name)?;
+        }
+        Some(inode)
+    }
+
+    /// Grow the file to `new_size` if it is smaller and allocate the holes
+    /// among the data blocks in `range`. If the data area runs out, the blocks
+    /// past the old size are freed again and false is returned, those filling
+    /// holes before it stay as blocks of zeros.
+    fn reserve_blocks(
+        &self,
+        new_size: u32,
+        range: Range<usize>,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        let old_size = disk_inode.size;
+        if new_size > old_size {
+            disk_inode.increase_size(new_size);
+        }
+        if disk_inode.alloc_blocks(range, || fs.alloc_data(), &self.block_device) {
+            return true;
+        }
+        for data_block in disk_inode.decrease_size(old_size, &self.block_device) {
+            fs.dealloc_data(data_block);
+        }
+        false
     }
 
+    /// Grow a directory, which has no holes. Return false if the data area
+    /// is full.
     fn increase_size(
         &self,
         new_size: u32,
         disk_inode: &mut DiskInode,
         fs: &mut MutexGuard<EasyFileSystem>,
-    ) {
+    ) -> bool {
         if new_size < disk_inode.size {
-            return;
+            return true;
         }
-        let blocks_needed = disk_inode.blocks_num_needed(new_size);
-        let mut v: Vec<u32> = Vec::new();
-        for _ in 0..blocks_needed {
-            v.push(fs.alloc_data());
+        let old_blocks = disk_inode.data_blocks() as usize;
+        let new_blocks = DiskInode::_data_blocks(new_size) as usize;
+        self.reserve_blocks(new_size, old_blocks..new_blocks, disk_inode, fs)
+    }
+
+    /// Add a dirent to a directory whose disk inode is `dir_inode`,
+    /// a free slot is reused if there is one. Return false if the directory
+    /// can not grow.
+    fn push_dirent(
+        &self,
+        name: &str,
+        inode_id: u32,
+        dir_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        if self.long_names {
+            if !self.push_long_dirent(name, inode_id, dir_inode, fs) {
+                return false;
+            }
+            dir_inode.update_mtime();
+            return true;
+        }
+        let file_count = (dir_inode.size as usize) / DIRENT_SZ;
+        let slot = (0..file_count)
+            .find(|slot| self.dirent_at(*slot * DIRENT_SZ, dir_inode).is_empty())
+            .unwrap_or(file_count);
+        if slot == file_count
+            && !self.increase_size(((file_count + 1) * DIRENT_SZ) as u32, dir_inode, fs)
+        {
+            return false;
         }
-        disk_inode.increase_size(new_size, v, &self.block_device);
+        // write dirent
+        let dirent = DirEntry::new(name, inode_id);
+        dir_inode.write_at(slot * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+        dir_inode.update_mtime();
+        true
     }
 
-    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
-        let mut fs = self.fs.lock();
-        let op = |root_inode: &mut DiskInode| {
-            // assert it is a directory
-            assert!(root_inode.is_dir());
-            // has the file been created?
-            self.find_inode_id(name, root_inode)
+    /// Split the free space after the first entry with enough of it, or add a
+    /// block to the directory.
+    fn push_long_dirent(
+        &self,
+        name: &str,
+        inode_id: u32,
+        dir_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        let size = dir_inode.size as usize;
+        let mut offset = 0;
+        while offset < size {
+            let mut dirent = self.long_dirent_at(offset, dir_inode);
+            // nothing is added to a corrupted directory
+            if !dirent.is_valid(offset, size) {
+                return false;
+            }
+            let rec_len = dirent.rec_len();
+            let free_len = dirent.free_len();
+            if free_len >= LongDirEntry::size_of(name) {
+                // an empty entry is taken as a whole
+                if !dirent.is_empty() {
+                    dirent.set_rec_len(rec_len - free_len);
+                    dir_inode.write_at(offset, dirent.as_bytes(), &self.block_device);
+                }
+                let dirent = LongDirEntry::new(name, inode_id, free_len);
+                let offset = offset + rec_len - free_len;
+                dir_inode.write_at(offset, dirent.as_bytes(), &self.block_device);
+                return true;
+            }
+            offset += rec_len;
+        }
+        if !self.increase_size((size + BLOCK_SZ) as u32, dir_inode, fs) {
+            return false;
+        }
+        let dirent = LongDirEntry::new(name, inode_id, BLOCK_SZ);
+        dir_inode.write_at(size, dirent.as_bytes(), &self.block_device);
+        true
+    }
+
+    /// Free the entry at `offset` of this directory.
+    fn remove_dirent(&self, offset: usize) {
+        self.modify_disk_inode(|dir_inode| {
+            if !self.long_names {
+                let dirent = DirEntry::empty();
+                dir_inode.write_at(offset, dirent.as_bytes(), &self.block_device);
+                dir_inode.update_mtime();
+                return;
+            }
+            let rec_len = self.long_dirent_at(offset, dir_inode).rec_len();
+            let block_start = offset - offset % BLOCK_SZ;
+            if offset == block_start {
+                let dirent = LongDirEntry::empty(rec_len);
+                dir_inode.write_at(offset, dirent.as_bytes(), &self.block_device);
+            } else {
+                // merge into the entry before it
+                let mut prev_offset = block_start;
+                let mut prev = self.long_dirent_at(prev_offset, dir_inode);
+                while prev_offset + prev.rec_len() != offset {
+                    prev_offset += prev.rec_len();
+                    prev = self.long_dirent_at(prev_offset, dir_inode);
+                }
+                prev.set_rec_len(prev.rec_len() + rec_len);
+                dir_inode.write_at(prev_offset, prev.as_bytes(), &self.block_device);
+            }
+            dir_inode.update_mtime();
+        });
+    }
+
+    /// Point the entry at `offset` of this directory to another inode.
+    fn set_dirent_inode(&self, offset: usize, inode_id: u32) {
+        self.modify_disk_inode(|dir_inode| {
+            if self.long_names {
+                let mut dirent = self.long_dirent_at(offset, dir_inode);
+                dirent.set_inode_number(inode_id);
+                dir_inode.write_at(offset, dirent.as_bytes(), &self.block_device);
+            } else {
+                let dirent = self.dirent_at(offset, dir_inode);
+                let dirent = DirEntry::new(dirent.name(), inode_id);
+                dir_inode.write_at(offset, dirent.as_bytes(), &self.block_device);
+            }
+            dir_inode.update_mtime();
+        });
+    }
+
+    /// Fill an empty directory with its `.` and `..` entries, return false if
+    /// the data area is full.
+    pub(crate) fn init_dir(
+        &self,
+        parent_inode_id: u32,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        self.modify_disk_inode(|dir_inode| {
+            self.push_dirent(".", self.inode_id, dir_inode, fs)
+                && self.push_dirent("..", parent_inode_id, dir_inode, fs)
+        })
+    }
+
+    /// Create an inode of `type_` holding `data` in this directory.
+    fn create_inode(&self, name: &str, type_: DiskInodeType, data: &[u8]) -> Option<Arc<Inode>> {
+        let mut fs = self.begin_op();
+        if !is_valid_name(name, &fs) {
+            return None;
+        }
+        let op = |dir_inode: &DiskInode| {
+            // only a directory not removed can hold files
+            // and the name should not have been used
+            !dir_inode.is_dir()
+                || dir_inode.nlink == 0
+                || self.find_inode_id(name, dir_inode).is_some()
         };
-        if self.modify_disk_inode(op).is_some() {
+        if self.read_disk_inode(op) {
             return None;
         }
         // create a new file
         // alloc a inode with an indirect block
-        let new_inode_id = fs.alloc_inode();
+        let new_inode_id = fs.alloc_inode()?;
         // initialize inode
+        let is_dir = type_ == DiskInodeType::Directory;
         let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
         get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+                new_inode.initialize(type_, fs.features());
             });
-        self.modify_disk_inode(|root_inode| {
-            // append file in the dirent
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let new_size = (file_count + 1) * DIRENT_SZ;
-            // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
-            // write dirent
-            let dirent = DirEntry::new(name, new_inode_id);
-            root_inode.write_at(
-                file_count * DIRENT_SZ,
-                dirent.as_bytes(),
-                &self.block_device,
-            );
-        });
+        let new_inode = self.handle_of(new_inode_id, &mut fs);
+        let mut filled = !is_dir || new_inode.init_dir(self.inode_id, &mut fs);
+        if filled && !data.is_empty() {
+            filled = new_inode.modify_disk_inode(|disk_inode| {
+                let size = data.len() as u32;
+                let blocks = DiskInode::_data_blocks(size) as usize;
+                if !new_inode.reserve_blocks(size, 0..blocks, disk_inode, &mut fs) {
+                    return false;
+                }
+                disk_inode.write_at(0, data, &self.block_device);
+                true
+            });
+        }
+        // append file in the dirent
+        if !filled
+            || !self.modify_disk_inode(|dir_inode| {
+                self.push_dirent(name, new_inode_id, dir_inode, &mut fs)
+            })
+        {
+            // out of data blocks, the new inode is freed with the blocks it
+            // got once its handle is dropped
+            new_inode.drop_link(&mut fs);
+            // closing the handle takes the efs lock
+            drop(fs);
+            return None;
+        }
 
-        let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
-        block_cache_sync_all();
         // return inode
-        Some(Arc::new(Self::new(
-            block_id,
-            block_offset,
-            self.fs.clone(),
-            self.block_device.clone(),
-        )))
+        Some(new_inode)
         // release efs lock automatically by compiler
     }
 
+    /// Create a regular file in this directory.
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File, &[])
+    }
+
+    /// Create a sub-directory holding `.` and `..` in this directory.
+    pub fn mkdir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory, &[])
+    }
+
+    /// Create a symbolic link to `target` in this directory, the target is
+    /// neither checked nor resolved.
+    pub fn symlink(&self, name: &str, target: &str) -> Option<Arc<Inode>> {
+        if target.is_empty() || target.len() > SYMLINK_LENGTH_LIMIT {
+            return None;
+        }
+        self.create_inode(name, DiskInodeType::Symlink, target.as_bytes())
+    }
+
+    /// Return the target of a symbolic link, or None for other files.
+    pub fn readlink(&self) -> Option<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_symlink() {
+                return None;
+            }
+            let mut target = vec![0u8; disk_inode.size as usize];
+            disk_inode.read_at(0, &mut target, &self.block_device);
+            String::from_utf8(target).ok()
+        })
+    }
+
+    /// Add the entry `name` in this directory for an existing file.
+    ///
+    /// Directories can not be hard linked.
+    pub fn link(&self, name: &str, inode: &Inode) -> bool {
+        if !Arc::ptr_eq(&self.fs, &inode.fs) {
+            return false;
+        }
+        let mut fs = self.begin_op();
+        if !is_valid_name(name, &fs) {
+            return false;
+        }
+        if inode.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return false;
+        }
+        let op = |dir_inode: &DiskInode| {
+            !dir_inode.is_dir()
+                || dir_inode.nlink == 0
+                || self.find_inode_id(name, dir_inode).is_some()
+        };
+        if self.read_disk_inode(op) {
+            return false;
+        }
+        if !self.modify_disk_inode(|dir_inode| {
+            self.push_dirent(name, inode.inode_id, dir_inode, &mut fs)
+        }) {
+            return false;
+        }
+        inode.modify_disk_inode(|disk_inode| {
+            disk_inode.nlink += 1;
+            disk_inode.update_ctime();
+        });
+        true
+    }
+
+    /// Remove the entry `name` from this directory, the inode and its data
+    /// blocks are freed by the next operation once its last link is gone and
+    /// no handle of it is held.
+    ///
+    /// A directory can only be removed when it is empty.
+    pub fn unlink(&self, name: &str) -> bool {
+        if name == "." || name == ".." {
+            return false;
+        }
+        let mut fs = self.begin_op();
+        let dirent = self.read_disk_inode(|dir_inode| {
+            if dir_inode.is_dir() {
+                self.find_dirent(name, dir_inode)
+            } else {
+                None
+            }
+        });
+        let (offset, inode_id) = match dirent {
+            Some(dirent) => dirent,
+            None => return false,
+        };
+        let inode = self.inode_of(inode_id, &fs);
+        if inode.has_children() {
+            return false;
+        }
+        self.remove_dirent(offset);
+        inode.drop_link(&mut fs);
+        true
+    }
+
+    /// Move the entry `old_name` of this directory to `new_name` in `new_dir`.
+    ///
+    /// An existing `new_name` is replaced if it has the same type as the moved
+    /// inode and is not a non-empty directory.
+    pub fn rename(&self, old_name: &str, new_dir: &Inode, new_name: &str) -> bool {
+        if old_name == "." || old_name == ".." || !Arc::ptr_eq(&self.fs, &new_dir.fs) {
+            return false;
+        }
+        let mut fs = self.begin_op();
+        if !is_valid_name(new_name, &fs) {
+            return false;
+        }
+        let dirent = self.read_disk_inode(|dir_inode| {
+            if dir_inode.is_dir() {
+                self.find_dirent(old_name, dir_inode)
+            } else {
+                None
+            }
+        });
+        let (old_offset, inode_id) = match dirent {
+            Some(dirent) => dirent,
+            None => return false,
+        };
+        if !new_dir.read_disk_inode(|dir_inode| dir_inode.is_dir() && dir_inode.nlink > 0) {
+            return false;
+        }
+        let inode = self.inode_of(inode_id, &fs);
+        let is_dir = inode.read_disk_inode(|disk_inode| disk_inode.is_dir());
+        if is_dir {
+            // a directory can not be moved into itself or its sub-directories
+            let mut ancestor = new_dir.inode_id;
+            loop {
+                if ancestor == inode_id {
+                    return false;
+                }
+                if ancestor == 0 {
+                    break;
+                }
+                ancestor = self.inode_of(ancestor, &fs).parent_id();
+            }
+        }
+        let target = new_dir.read_disk_inode(|dir_inode| new_dir.find_dirent(new_name, dir_inode));
+        if let Some((new_offset, target_id)) = target {
+            if target_id == inode_id {
+                return true;
+            }
+            let target = self.inode_of(target_id, &fs);
+            if target.read_disk_inode(|disk_inode| disk_inode.is_dir()) != is_dir
+                || target.has_children()
+            {
+                return false;
+            }
+            new_dir.set_dirent_inode(new_offset, inode_id);
+            target.drop_link(&mut fs);
+        } else if !new_dir.modify_disk_inode(|dir_inode| {
+            new_dir.push_dirent(new_name, inode_id, dir_inode, &mut fs)
+        }) {
+            return false;
+        }
+        self.remove_dirent(old_offset);
+        inode.modify_disk_inode(|disk_inode| disk_inode.update_ctime());
+        if is_dir && new_dir.inode_id != self.inode_id {
+            let dirent = inode.read_disk_inode(|dir_inode| inode.find_dirent("..", dir_inode));
+            inode.set_dirent_inode(dirent.unwrap().0, new_dir.inode_id);
+        }
+        true
+    }
+
+    /// Return the inode id of `..`, the efs lock must be held by caller.
+    fn parent_id(&self) -> u32 {
+        self.read_disk_inode(|dir_inode| self.find_inode_id("..", dir_inode))
+            .unwrap()
+    }
+
+    /// Whether this is a directory holding entries other than `.` and `..`.
+    fn has_children(&self) -> bool {
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return false;
+            }
+            self.find_map_dirent(disk_inode, |_, name, _| {
+                if name != "." && name != ".." {
+                    Some(())
+                } else {
+                    None
+                }
+            })
+            .is_some()
+        })
+    }
+
+    /// Decrease the link count, the inode is freed with its data blocks by a
+    /// later operation when the count drops to zero and no handle of it is held.
+    fn drop_link(&self, fs: &mut MutexGuard<EasyFileSystem>) {
+        let nlink = self.modify_disk_inode(|disk_inode| {
+            disk_inode.nlink -= 1;
+            disk_inode.update_ctime();
+            disk_inode.nlink
+        });
+        if nlink == 0 {
+            fs.orphan_inode(self.inode_id);
+        }
+    }
+
+    /// Free the inode with its data blocks, which begins new operations of
+    /// the journal.
+    fn free(&self, fs: &mut MutexGuard<EasyFileSystem>) {
+        self.decrease_size(0, fs);
+        fs.dealloc_inode(self.inode_id);
+    }
+
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_symlink())
+    }
+
+    /// Permission bits together with the setuid, setgid and sticky bits.
+    pub fn mode(&self) -> u16 {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.mode())
+    }
+
+    /// The owner user and group.
+    pub fn owner(&self) -> (u16, u16) {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.owner())
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| Metadata {
+            inode_id: self.inode_id,
+            is_dir: disk_inode.is_dir(),
+            is_symlink: disk_inode.is_symlink(),
+            mode: disk_inode.mode(),
+            uid: disk_inode.owner().0,
+            gid: disk_inode.owner().1,
+            size: disk_inode.size,
+            nlink: disk_inode.nlink,
+            blocks: disk_inode.block_ids(&self.block_device).len() as u32,
+            atime: disk_inode.atime,
+            mtime: disk_inode.mtime,
+            ctime: disk_inode.ctime,
+        })
+    }
+
+    /// Blocks taken by the data together with the index blocks.
+    pub fn block_ids(&self) -> Vec<u32> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.block_ids(&self.block_device))
+    }
+
+    /// Longest name in bytes an entry of this file system can hold.
+    pub fn name_length_limit(&self) -> usize {
+        self.fs.lock().name_length_limit()
+    }
+
+    /// Largest size of a regular file of this file system.
+    pub fn max_file_size(&self) -> usize {
+        self.fs.lock().max_file_size()
+    }
+
+    /// Set the permission bits together with the setuid, setgid and sticky
+    /// bits, the others are ignored. Return false on images without
+    /// `FEATURE_OWNERS`, whose files keep their modes.
+    pub fn chmod(&self, mode: u16) -> bool {
+        let _fs = self.begin_op();
+        self.modify_disk_inode(|disk_inode| {
+            let changed = disk_inode.set_mode(mode & 0o7777);
+            if changed {
+                disk_inode.update_ctime();
+            }
+            changed
+        })
+    }
+
+    /// Return false on images without `FEATURE_OWNERS`, whose files belong
+    /// to root.
+    pub fn chown(&self, uid: u16, gid: u16) -> bool {
+        let _fs = self.begin_op();
+        self.modify_disk_inode(|disk_inode| {
+            let changed = disk_inode.set_owner(uid, gid);
+            if changed {
+                disk_inode.update_ctime();
+            }
+            changed
+        })
+    }
+
+    /// Set the link count, which is only for repairing the file system.
+    pub fn set_nlink(&self, nlink: u32) {
+        let _fs = self.begin_op();
+        self.modify_disk_inode(|disk_inode| {
+            disk_inode.nlink = nlink;
+            disk_inode.update_ctime();
+        });
+    }
+
     pub fn ls(&self) -> Vec<String> {
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
-            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
             let mut v: Vec<String> = Vec::new();
-            for i in 0..file_count {
-                let mut dirent = DirEntry::empty();
-                assert_eq!(
-                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
-                    DIRENT_SZ,
-                );
-                v.push(String::from(dirent.name()));
-            }
+            self.find_map_dirent(disk_inode, |_, name, _| -> Option<()> {
+                v.push(String::from(name));
+                None
+            });
             v
         })
     }
 
+    /// Most reads leave the access time as it is and stay out of the
+    /// journal, see `DiskInode::atime_outdated`.
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
-        let _fs = self.fs.lock();
+        let fs = self.fs.lock();
+        let now = now();
+        let _fs = if self.read_disk_inode(|disk_inode| disk_inode.atime_outdated(now)) {
+            drop(fs);
+            let fs = self.begin_op();
+            self.modify_disk_inode(|disk_inode| disk_inode.atime = now);
+            fs
+        } else {
+            fs
+        };
         self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
     }
 
+    /// Nothing is written beyond `max_file_size`, a write beyond the end of
+    /// the file leaves a hole before it.
     pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
-        let mut fs = self.fs.lock();
-        let size = self.modify_disk_inode(|disk_inode| {
-            self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
-            disk_inode.write_at(offset, buf, &self.block_device)
-        });
-        block_cache_sync_all();
-        size
+        let max_file_size = self.max_file_size();
+        // an empty write neither grows the file nor opens an operation
+        if offset >= max_file_size || buf.is_empty() {
+            return 0;
+        }
+        let buf = &buf[..buf.len().min(max_file_size - offset)];
+        let mut size = 0;
+        loop {
+            let part = &buf[size..buf.len().min(size + OP_WRITE_SIZE)];
+            let mut fs = self.begin_op();
+            let written = self.modify_disk_inode(|disk_inode| {
+                let offset = offset + size;
+                let end = offset + part.len();
+                let range = offset / BLOCK_SZ..(end + BLOCK_SZ - 1) / BLOCK_SZ;
+                if !self.reserve_blocks(end as u32, range, disk_inode, &mut fs) {
+                    return None;
+                }
+                disk_inode.update_mtime();
+                Some(disk_inode.write_at(offset, part, &self.block_device))
+            });
+            // the data area is full, the part is not written
+            match written {
+                Some(written) => size += written,
+                None => return size,
+            }
+            if size == buf.len() {
+                return size;
+            }
+        }
     }
 
-    pub fn clear(&self) {
-        let mut fs = self.fs.lock();
+    /// Cut a regular file to zero size, return false for anything else.
+    pub fn clear(&self) -> bool {
+        let mut fs = self.begin_op();
+        if !self.read_disk_inode(|disk_inode| disk_inode.is_file()) {
+            return false;
+        }
+        self.decrease_size(0, &mut fs);
+        self.modify_disk_inode(|disk_inode| disk_inode.update_mtime());
+        true
+    }
+
+    /// Change the size of a file, the part added is a hole reading as zeros.
+    /// Return false for anything but a regular file or a size beyond
+    /// `max_file_size`.
+    pub fn truncate(&self, new_size: usize) -> bool {
+        if new_size > self.max_file_size() {
+            return false;
+        }
+        let mut fs = self.begin_op();
+        let (is_file, size) =
+            self.read_disk_inode(|disk_inode| (disk_inode.is_file(), disk_inode.size as usize));
+        if !is_file {
+            return false;
+        }
+        if new_size < size {
+            self.decrease_size(new_size as u32, &mut fs);
+        }
         self.modify_disk_inode(|disk_inode| {
-            let size = disk_inode.size;
-            let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
-            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
-            for data_block in data_blocks_dealloc.into_iter() {
-                fs.dealloc_data(data_block);
+            if new_size > size {
+                disk_inode.increase_size(new_size as u32);
             }
+            disk_inode.update_mtime();
         });
-        block_cache_sync_all();
+        true
+    }
+
+    /// Write back this file together with all other modified files.
+    pub fn sync(&self) {
+        self.begin_op().sync();
+    }
+
+    /// Shrink the file to `new_size` by freeing the blocks from its end in
+    /// parts, the parts after the first one begin new operations of the journal.
+    fn decrease_size(&self, new_size: u32, fs: &mut MutexGuard<EasyFileSystem>) {
+        let min_blocks = (new_size as usize + BLOCK_SZ - 1) / BLOCK_SZ;
+        loop {
+            let size = self.modify_disk_inode(|disk_inode| {
+                let data_blocks = disk_inode.data_blocks() as usize;
+                let mut new_blocks = data_blocks.saturating_sub(OP_FREE_BLOCKS).max(min_blocks);
+                // the inode block, at most 3 index blocks kept and the inode
+                // bitmap freeing the inode after the last part take 5 more
+                // blocks of the operation
+                while new_blocks + 1 < data_blocks
+                    && fs.data_bitmap_blocks_of(
+                        &disk_inode
+                            .block_ids_after((new_blocks * BLOCK_SZ) as u32, &self.block_device),
+                    ) >= MAX_OP_BLOCKS - 5
+                {
+                    new_blocks = (new_blocks + data_blocks + 1) / 2;
+                }
+                let part_size = if new_blocks == min_blocks {
+                    new_size
+                } else {
+                    (new_blocks * BLOCK_SZ) as u32
+                };
+                for data_block in disk_inode.decrease_size(part_size, &self.block_device) {
+                    fs.dealloc_data(data_block);
+                }
+                disk_inode.size
+            });
+            if size == new_size {
+                return;
+            }
+            fs.begin_op();
+        }
     }
 }
+
+impl Drop for Inode {
+    /// Closing the last handle of an unlinked inode leaves it to be freed by
+    /// the next operation, the disk is not accessed here.
+    fn drop(&mut self) {
+        if self.handle {
+            self.fs.lock().close_inode(self.inode_id);
+        }
+    }
+}
+
+fn is_valid_name(name: &str, fs: &EasyFileSystem) -> bool {
+    !name.is_empty() && name.len() <= fs.name_length_limit() && !name.contains('/')
+}
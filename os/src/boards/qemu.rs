@@ -10,6 +10,7 @@ pub const MMIO: &[(usize, usize)] = &[
 pub type BlockDeviceImpl = crate::drivers::block::VirtIOBlock;
 pub type CharDeviceImpl = crate::drivers::chardev::NS16550a<VIRT_UART>;
 
+pub const VIRT_RTC: usize = 0x10_1000;
 pub const VIRT_PLIC: usize = 0xC00_0000;
 pub const VIRT_UART: usize = 0x1000_0000;
 #[allow(unused)]
@@ -21,19 +22,29 @@ use crate::drivers::block::BLOCK_DEVICE;
 use crate::drivers::chardev::{CharDevice, UART};
 use crate::drivers::plic::{IntrTargetPriority, PLIC};
 use crate::drivers::{KEYBOARD_DEVICE, MOUSE_DEVICE};
+use crate::task::hart_id;
+
+//irq nums: 5 keyboard, 6 mouse, 8 block, 10 uart
+const IRQ_SOURCES: [usize; 4] = [5, 6, 8, 10];
 
 pub fn device_init() {
+    let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
+    for intr_src_id in IRQ_SOURCES {
+        plic.set_priority(intr_src_id, 1);
+    }
+}
+
+/// Route the device interrupts to the supervisor context of `hart_id`, any
+/// hart may claim them.
+pub fn irq_init_hart(hart_id: usize) {
     use riscv::register::sie;
     let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
-    let hart_id: usize = 0;
     let supervisor = IntrTargetPriority::Supervisor;
     let machine = IntrTargetPriority::Machine;
     plic.set_threshold(hart_id, supervisor, 0);
     plic.set_threshold(hart_id, machine, 1);
-    //irq nums: 5 keyboard, 6 mouse, 8 block, 10 uart
-    for intr_src_id in [5usize, 6, 8, 10] {
+    for intr_src_id in IRQ_SOURCES {
         plic.enable(hart_id, supervisor, intr_src_id);
-        plic.set_priority(intr_src_id, 1);
     }
     unsafe {
         sie::set_sext();
@@ -42,15 +53,18 @@ pub fn device_init() {
 
 pub fn irq_handler() {
     let mut plic = unsafe { PLIC::new(VIRT_PLIC) };
-    let intr_src_id = plic.claim(0, IntrTargetPriority::Supervisor);
+    let hart_id = hart_id();
+    let intr_src_id = plic.claim(hart_id, IntrTargetPriority::Supervisor);
     match intr_src_id {
+        // claimed by another hart already
+        0 => return,
         5 => KEYBOARD_DEVICE.handle_irq(),
         6 => MOUSE_DEVICE.handle_irq(),
         8 => BLOCK_DEVICE.handle_irq(),
         10 => UART.handle_irq(),
         _ => panic!("unsupported IRQ {}", intr_src_id),
     }
-    plic.complete(0, IntrTargetPriority::Supervisor, intr_src_id);
+    plic.complete(hart_id, IntrTargetPriority::Supervisor, intr_src_id);
 }
 
 //ref:: https://github.com/andre-richter/qemu-exit
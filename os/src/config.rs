@@ -1,12 +1,34 @@
 #[allow(unused)]
 
-pub const USER_STACK_SIZE: usize = 4096 * 2;
+pub const USER_STACK_SIZE: usize = 4096 * 16;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_HEAP_SIZE: usize = 0x100_0000;
 pub const MEMORY_END: usize = 0x88000000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
 
+/// the heap grows from the end of the elf image up to this size,
+/// followed by a guard page and the user stacks
+pub const USER_HEAP_LIMIT: usize = 0x400_0000;
+
+/// mmap areas are placed in [MMAP_BASE, MMAP_TOP) unless MAP_FIXED is given,
+/// MMAP_TOP is the end of the lower half of sv39
+pub const MMAP_BASE: usize = 0x2000_0000;
+pub const MMAP_TOP: usize = 0x40_0000_0000;
+
+/// number of disk blocks cached in memory, dirty ones are written back on
+/// eviction, fsync, sync or every SYNC_INTERVAL_MS
+pub const BLOCK_CACHE_SIZE: usize = 256;
+pub const SYNC_INTERVAL_MS: usize = 5000;
+
+/// pages of file data each tmpfs may take from the frame allocator, writes
+/// beyond are cut short
+pub const TMPFS_MAX_PAGES: usize = 0x1000;
+
+/// harts beyond this are parked, entry.asm reserves a boot stack for each of
+/// the others
+pub const MAX_HARTS: usize = 8;
+
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
 
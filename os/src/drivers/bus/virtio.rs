@@ -2,14 +2,14 @@ use crate::mm::{
     frame_alloc, frame_dealloc, kernel_token, FrameTracker, PageTable, PhysAddr, PhysPageNum,
     StepByOne, VirtAddr,
 };
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinIntrFreeCell;
 use alloc::vec::Vec;
 use lazy_static::*;
 use virtio_drivers::Hal;
 
 lazy_static! {
-    static ref QUEUE_FRAMES: UPIntrFreeCell<Vec<FrameTracker>> =
-        unsafe { UPIntrFreeCell::new(Vec::new()) };
+    static ref QUEUE_FRAMES: SpinIntrFreeCell<Vec<FrameTracker>> =
+        SpinIntrFreeCell::new(Vec::new());
 }
 
 pub struct VirtioHal;
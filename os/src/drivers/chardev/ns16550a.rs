@@ -2,7 +2,7 @@
 ///! Ref: ns16550a datasheet: https://datasheetspdf.com/pdf-file/605590/NationalSemiconductor/NS16550A/1
 ///! Ref: ns16450 datasheet: https://datasheetspdf.com/pdf-file/1311818/NationalSemiconductor/NS16450/1
 use super::CharDevice;
-use crate::sync::{Condvar, UPIntrFreeCell};
+use crate::sync::{Condvar, SpinIntrFreeCell};
 use crate::task::schedule;
 use alloc::collections::VecDeque;
 use bitflags::*;
@@ -125,7 +125,7 @@ struct NS16550aInner {
 }
 
 pub struct NS16550a<const BASE_ADDR: usize> {
-    inner: UPIntrFreeCell<NS16550aInner>,
+    inner: SpinIntrFreeCell<NS16550aInner>,
     condvar: Condvar,
 }
 
@@ -137,7 +137,7 @@ impl<const BASE_ADDR: usize> NS16550a<BASE_ADDR> {
         };
         //inner.ns16550a.init();
         Self {
-            inner: unsafe { UPIntrFreeCell::new(inner) },
+            inner: SpinIntrFreeCell::new(inner),
             condvar: Condvar::new(),
         }
     }
@@ -1,5 +1,5 @@
 use crate::drivers::bus::virtio::VirtioHal;
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinIntrFreeCell;
 use alloc::{sync::Arc, vec::Vec};
 use core::any::Any;
 use embedded_graphics::pixelcolor::Rgb888;
@@ -17,7 +17,7 @@ lazy_static::lazy_static!(
 );
 
 pub struct VirtIOGpuWrapper {
-    gpu: UPIntrFreeCell<VirtIOGpu<'static, VirtioHal>>,
+    gpu: SpinIntrFreeCell<VirtIOGpu<'static, VirtioHal>>,
     fb: &'static [u8],
 }
 static BMP_DATA: &[u8] = include_bytes!("../../assert/mouse.bmp");
@@ -47,7 +47,7 @@ impl VirtIOGpuWrapper {
             virtio.setup_cursor(b.as_slice(), 50, 50, 50, 50).unwrap();
 
             Self {
-                gpu: UPIntrFreeCell::new(virtio),
+                gpu: SpinIntrFreeCell::new(virtio),
                 fb,
             }
         }
@@ -1,12 +1,42 @@
     .section .text.entry
     .globl _start
 _start:
+    la t1, rust_main
+    j boot_hart
+    # other harts are started here by the boot hart
+    .globl _start_secondary
+_start_secondary:
+    la t1, rust_main_secondary
+boot_hart:
+    # a0 = hart id, kept in tp by the kernel
+    # harts from MAX_HARTS on have no boot stack and are parked, a boot hart
+    # among them first starts hart 0 to boot in its place
+    li t0, {MAX_HARTS}
+    bltu a0, t0, 1f
+    la t0, rust_main
+    bne t1, t0, park
+    # sbi_hart_start(0, _start, 0) of the HSM extension
+    li a7, 0x48534D
+    li a6, 0
+    li a0, 0
+    la a1, _start
+    li a2, 0
+    ecall
+park:
+    wfi
+    j park
+1:
+    mv tp, a0
+    # each hart has its own boot stack below boot_stack_top
     la sp, boot_stack_top
-    call rust_main
+    slli t0, a0, 16
+    sub sp, sp, t0
+    jr t1
 
     .section .bss.stack
     .globl boot_stack_lower_bound
 boot_stack_lower_bound:
-    .space 4096 * 16
+    # 64 KiB for each of MAX_HARTS harts
+    .space 4096 * 16 * {MAX_HARTS}
     .globl boot_stack_top
 boot_stack_top:
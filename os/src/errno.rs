@@ -0,0 +1,63 @@
+//! Error numbers of syscalls, which take the values of Linux.
+
+/// A failed syscall returns the negated number to the user.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(isize)]
+pub enum Errno {
+    /// Operation not permitted
+    EPERM = 1,
+    /// No such file or directory
+    ENOENT = 2,
+    /// No such process or thread
+    ESRCH = 3,
+    /// Interrupted by a signal
+    EINTR = 4,
+    /// Bad file descriptor
+    EBADF = 9,
+    /// No such child process
+    ECHILD = 10,
+    /// Try again, nothing is ready yet
+    EAGAIN = 11,
+    /// Out of memory
+    ENOMEM = 12,
+    /// Permission denied
+    EACCES = 13,
+    /// Bad address
+    EFAULT = 14,
+    /// Device or resource busy
+    EBUSY = 16,
+    /// File exists
+    EEXIST = 17,
+    /// Cross-device link
+    EXDEV = 18,
+    /// The file does not support the operation
+    ENODEV = 19,
+    /// Not a directory
+    ENOTDIR = 20,
+    /// Is a directory
+    EISDIR = 21,
+    /// Invalid argument
+    EINVAL = 22,
+    /// File too large
+    EFBIG = 27,
+    /// No space left on device
+    ENOSPC = 28,
+    /// The file is a pipe or a device which can not seek
+    ESPIPE = 29,
+    /// Result out of range
+    ERANGE = 34,
+    /// Resource deadlock would occur
+    EDEADLK = 35,
+    /// File name too long
+    ENAMETOOLONG = 36,
+    /// Function not implemented
+    ENOSYS = 38,
+    /// Directory not empty
+    ENOTEMPTY = 39,
+    /// Too many levels of symbolic links
+    ELOOP = 40,
+}
+
+/// The result of a syscall, `Ok` values are returned to the user as they are.
+pub type SysResult<T = usize> = Result<T, Errno>;
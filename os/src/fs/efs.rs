@@ -0,0 +1,166 @@
+//! `easy_fs` on the block device, seen through the VFS interfaces.
+
+use super::{FileSystem, Stat, StatMode, VfsInode};
+use crate::config::BLOCK_CACHE_SIZE;
+use crate::drivers::BLOCK_DEVICE;
+use crate::timer::get_real_time_sec;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use easy_fs::{set_block_cache_capacity, set_clock, EasyFileSystem, Inode, SYMLINK_LENGTH_LIMIT};
+use lazy_static::*;
+
+pub struct EfsFileSystem {
+    root: Arc<Inode>,
+}
+
+lazy_static! {
+    /// The file system on the block device, which is mounted at `/`.
+    pub static ref EFS: Arc<EfsFileSystem> = {
+        set_clock(|| get_real_time_sec() as u32);
+        set_block_cache_capacity(BLOCK_CACHE_SIZE);
+        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
+        Arc::new(EfsFileSystem {
+            root: Arc::new(EasyFileSystem::root_inode(&efs)),
+        })
+    };
+}
+
+impl FileSystem for EfsFileSystem {
+    fn root_inode(&self) -> Arc<dyn VfsInode> {
+        Arc::new(EfsInode(self.root.clone()))
+    }
+    /// Commit the journal and write all dirty blocks back to the disk.
+    fn sync(&self) {
+        self.root.sync();
+    }
+    /// The root file system is never unmounted.
+    fn busy(&self) -> bool {
+        true
+    }
+}
+
+pub struct EfsInode(Arc<Inode>);
+
+impl EfsInode {
+    fn wrap(inode: Option<Arc<Inode>>) -> Option<Arc<dyn VfsInode>> {
+        inode.map(|inode| Arc::new(EfsInode(inode)) as Arc<dyn VfsInode>)
+    }
+    /// The inode behind `inode` if it is also on `easy_fs`.
+    fn of(inode: &dyn VfsInode) -> Option<&Inode> {
+        inode
+            .as_any()
+            .downcast_ref::<EfsInode>()
+            .map(|inode| inode.0.as_ref())
+    }
+}
+
+impl VfsInode for EfsInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn ino(&self) -> u64 {
+        self.0.inode_id() as u64
+    }
+    fn is_dir(&self) -> bool {
+        self.0.is_dir()
+    }
+    fn is_symlink(&self) -> bool {
+        self.0.is_symlink()
+    }
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+    fn stat(&self) -> Stat {
+        let metadata = self.0.metadata();
+        Stat {
+            ino: metadata.inode_id as u64,
+            mode: if metadata.is_dir {
+                StatMode::DIR
+            } else if metadata.is_symlink {
+                StatMode::LNK
+            } else {
+                StatMode::FILE
+            },
+            perm: metadata.mode as u32,
+            nlink: metadata.nlink,
+            uid: metadata.uid as u32,
+            gid: metadata.gid as u32,
+            size: metadata.size as u64,
+            blocks: metadata.blocks as u64,
+            atime: metadata.atime as u64,
+            mtime: metadata.mtime as u64,
+            ctime: metadata.ctime as u64,
+        }
+    }
+    fn mode(&self) -> u16 {
+        self.0.mode()
+    }
+    fn owner(&self) -> (u16, u16) {
+        self.0.owner()
+    }
+    /// Files of older images keep their modes and belong to root.
+    fn chmod(&self, mode: u16) -> bool {
+        self.0.chmod(mode)
+    }
+    fn chown(&self, uid: u16, gid: u16) -> bool {
+        self.0.chown(uid, gid)
+    }
+    /// Older images only hold names of up to 27 bytes.
+    fn name_length_limit(&self) -> usize {
+        self.0.name_length_limit()
+    }
+    fn symlink_length_limit(&self) -> usize {
+        SYMLINK_LENGTH_LIMIT
+    }
+    /// Smaller on older images without the triple indirect block.
+    fn max_file_size(&self) -> usize {
+        self.0.max_file_size()
+    }
+
+    fn find(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        Self::wrap(self.0.find(name))
+    }
+    fn ls(&self) -> Vec<String> {
+        self.0.ls()
+    }
+    fn create(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        Self::wrap(self.0.create(name))
+    }
+    fn mkdir(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        Self::wrap(self.0.mkdir(name))
+    }
+    fn symlink(&self, name: &str, target: &str) -> Option<Arc<dyn VfsInode>> {
+        Self::wrap(self.0.symlink(name, target))
+    }
+    fn readlink(&self) -> Option<String> {
+        self.0.readlink()
+    }
+    fn link(&self, name: &str, inode: &dyn VfsInode) -> bool {
+        Self::of(inode).map_or(false, |inode| self.0.link(name, inode))
+    }
+    fn unlink(&self, name: &str) -> bool {
+        self.0.unlink(name)
+    }
+    fn rename(&self, old_name: &str, new_dir: &dyn VfsInode, new_name: &str) -> bool {
+        Self::of(new_dir).map_or(false, |new_dir| self.0.rename(old_name, new_dir, new_name))
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.0.read_at(offset, buf)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.0.write_at(offset, buf)
+    }
+    fn clear(&self) {
+        self.0.clear();
+    }
+    fn truncate(&self, new_size: usize) -> bool {
+        self.0.truncate(new_size)
+    }
+    /// Write back this file together with all other modified files.
+    fn sync(&self) {
+        self.0.sync();
+    }
+}
@@ -1,58 +1,104 @@
-use super::File;
-use crate::drivers::BLOCK_DEVICE;
+use super::mount::{
+    cross_mount, is_mount_point, is_mount_root, leave_mount, mount, root_inode, sync_mounts,
+    umount, ROOT_MOUNT,
+};
+use super::{Credentials, File, FileSystem, Stat, TmpFileSystem, VfsInode, R_OK, W_OK, X_OK};
+use crate::config::SYNC_INTERVAL_MS;
+use crate::errno::{Errno, SysResult};
 use crate::mm::UserBuffer;
-use crate::sync::UPIntrFreeCell;
+use crate::sync::{Mutex, MutexBlocking, SpinIntrFreeCell};
+use crate::timer::get_time_ms;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::*;
-use easy_fs::{EasyFileSystem, Inode};
-use lazy_static::*;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPIntrFreeCell<OSInodeInner>,
+    /// Serializes the users of the offset, which may sleep on the disk.
+    offset_lock: MutexBlocking,
+    inner: SpinIntrFreeCell<OSInodeInner>,
 }
 
 pub struct OSInodeInner {
     offset: usize,
-    inode: Arc<Inode>,
+    inode: Arc<dyn VfsInode>,
 }
 
 impl OSInode {
-    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
+    pub fn new(readable: bool, writable: bool, inode: Arc<dyn VfsInode>) -> Self {
         Self {
             readable,
             writable,
-            inner: unsafe { UPIntrFreeCell::new(OSInodeInner { offset: 0, inode }) },
+            offset_lock: MutexBlocking::new(),
+            inner: SpinIntrFreeCell::new(OSInodeInner { offset: 0, inode }),
         }
     }
     pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
-        let mut buffer = [0u8; 512];
-        let mut v: Vec<u8> = Vec::new();
-        loop {
-            let len = inner.inode.read_at(inner.offset, &mut buffer);
-            if len == 0 {
-                break;
+        self.with_offset(|inode, offset| {
+            let mut buffer = [0u8; 512];
+            let mut v: Vec<u8> = Vec::new();
+            loop {
+                let len = inode.read_at(*offset, &mut buffer);
+                if len == 0 {
+                    break;
+                }
+                *offset += len;
+                v.extend_from_slice(&buffer[..len]);
             }
-            inner.offset += len;
-            v.extend_from_slice(&buffer[..len]);
-        }
-        v
+            v
+        })
+    }
+    /// Run `f` on the inode and the offset, which no other reader or writer
+    /// of this file moves meanwhile.
+    fn with_offset<T>(&self, f: impl FnOnce(&dyn VfsInode, &mut usize) -> T) -> T {
+        self.offset_lock.lock();
+        let (inode, mut offset) = self.position();
+        let ret = f(inode.as_ref(), &mut offset);
+        self.inner.exclusive_access().offset = offset;
+        self.offset_lock.unlock();
+        ret
+    }
+    /// The inode and the offset, copied out so that the cell is not held
+    /// during disk accesses which may block.
+    fn position(&self) -> (Arc<dyn VfsInode>, usize) {
+        let inner = self.inner.exclusive_access();
+        (inner.inode.clone(), inner.offset)
     }
 }
 
-lazy_static! {
-    pub static ref ROOT_INODE: Arc<Inode> = {
-        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
-        Arc::new(EasyFileSystem::root_inode(&efs))
-    };
+/// Write all cached data of the mounted file systems back.
+pub fn sync_all() {
+    sync_mounts();
+}
+
+/// Time in ms after which the dirty blocks are written back again.
+static NEXT_SYNC_MS: AtomicUsize = AtomicUsize::new(SYNC_INTERVAL_MS);
+
+/// Called on timer interrupts of user mode, the disk may block so it must be
+/// in a task context. Only one hart writes back once the interval expires.
+pub fn sync_periodically() {
+    let current_ms = get_time_ms();
+    let next_ms = NEXT_SYNC_MS.load(Ordering::Relaxed);
+    if current_ms >= next_ms
+        && NEXT_SYNC_MS
+            .compare_exchange(
+                next_ms,
+                current_ms + SYNC_INTERVAL_MS,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    {
+        sync_all();
+    }
 }
 
 pub fn list_apps() {
     println!("/**** APPS ****");
-    for app in ROOT_INODE.ls() {
+    for app in root_inode().ls() {
         println!("{}", app);
     }
     println!("**************/")
@@ -82,29 +128,474 @@ impl OpenFlags {
     }
 }
 
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
-    let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            // clear size
-            inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            // create file
-            ROOT_INODE
-                .create(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
+/// Split `path` into its parent directory and final component.
+/// The parent is `""` when `path` has only one component.
+pub fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(0) => ("/", &path[1..]),
+        Some(idx) => (&path[..idx], &path[idx + 1..]),
+        None => ("", path),
+    }
+}
+
+/// Most symbolic links followed in resolving one path, like Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Push the names of `path` to be walked before those already in `names`,
+/// which are kept in reverse order.
+fn push_names(names: &mut Vec<String>, path: &str) {
+    names.extend(
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .rev()
+            .map(String::from),
+    );
+}
+
+/// The working directory of a process. It is held by its inode, so it stays
+/// the same directory when it or a directory above it is renamed.
+#[derive(Clone)]
+pub struct Cwd {
+    inode: Arc<dyn VfsInode>,
+    /// The mount holding `inode`.
+    mount: usize,
+}
+
+impl Cwd {
+    pub fn root() -> Self {
+        Self {
+            inode: root_inode(),
+            mount: ROOT_MOUNT,
         }
+    }
+    /// The directory at `path`, which must be searchable.
+    pub fn change(&self, path: &str, cred: &Credentials) -> SysResult<Self> {
+        let (inode, mount) = walk_path(self, path, true, cred)?;
+        if !inode.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        cred.check(inode.as_ref(), X_OK)?;
+        Ok(Self { inode, mount })
+    }
+    /// Build the absolute path by looking each directory up in its parent,
+    /// `ENOENT` once the directory is removed.
+    pub fn path(&self) -> SysResult<String> {
+        let mut names = Vec::new();
+        let mut inode = self.inode.clone();
+        let mut mount = self.mount;
+        loop {
+            // the root of a mount is named in the directory it covers
+            if let Some((point, parent)) = leave_mount(mount, inode.as_ref()) {
+                inode = point;
+                mount = parent;
+                continue;
+            }
+            if mount == ROOT_MOUNT && is_mount_root(mount, inode.as_ref()) {
+                break;
+            }
+            let parent = inode.find("..").ok_or(Errno::ENOENT)?;
+            let name = parent
+                .ls()
+                .into_iter()
+                .filter(|name| name != "." && name != "..")
+                .find(|name| {
+                    parent
+                        .find(name.as_str())
+                        .map_or(false, |child| child.ino() == inode.ino())
+                })
+                .ok_or(Errno::ENOENT)?;
+            names.push(name);
+            inode = parent;
+        }
+        let mut path = String::new();
+        for name in names.iter().rev() {
+            path.push('/');
+            path.push_str(name);
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        Ok(path)
+    }
+}
+
+/// Resolve `path` relative to the working directory `cwd`, and return the
+/// inode with the id of its mount. Symbolic links are followed on the way,
+/// and at the end only if `follow` is set.
+fn walk_path(
+    cwd: &Cwd,
+    path: &str,
+    follow: bool,
+    cred: &Credentials,
+) -> SysResult<(Arc<dyn VfsInode>, usize)> {
+    let mut names = Vec::new();
+    push_names(&mut names, path);
+    let (mut inode, mut mount) = if path.starts_with('/') {
+        (root_inode(), ROOT_MOUNT)
     } else {
-        ROOT_INODE.find(name).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
+        (cwd.inode.clone(), cwd.mount)
+    };
+    let mut links = 0;
+    while let Some(name) = names.pop() {
+        // names are only looked up in directories, with the search permission
+        if !inode.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        cred.check(inode.as_ref(), X_OK)?;
+        // `..` of the root of a mount is that of the directory it covers
+        if name == ".." {
+            if let Some((point, parent)) = leave_mount(mount, inode.as_ref()) {
+                inode = point;
+                mount = parent;
+            }
+        }
+        let next = inode.find(name.as_str()).ok_or(Errno::ENOENT)?;
+        if next.is_symlink() && (follow || !names.is_empty()) {
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return Err(Errno::ELOOP);
+            }
+            // the target is relative to the directory holding the link
+            let target = next.readlink().ok_or(Errno::EINVAL)?;
+            if target.starts_with('/') {
+                inode = root_inode();
+                mount = ROOT_MOUNT;
+            }
+            push_names(&mut names, target.as_str());
+            continue;
+        }
+        (inode, mount) = cross_mount(mount, next);
+    }
+    Ok((inode, mount))
+}
+
+/// Resolve `path` relative to the working directory `cwd`, following all
+/// symbolic links.
+pub fn find_inode(cwd: &Cwd, path: &str, cred: &Credentials) -> SysResult<Arc<dyn VfsInode>> {
+    walk_path(cwd, path, true, cred).map(|(inode, _)| inode)
+}
+
+/// Resolve `path` like `find_inode`, but a symbolic link at its end is
+/// returned itself.
+pub fn find_link(cwd: &Cwd, path: &str, cred: &Credentials) -> SysResult<Arc<dyn VfsInode>> {
+    walk_path(cwd, path, false, cred).map(|(inode, _)| inode)
+}
+
+/// Resolve the parent directory of `path`, and return it with the id of its
+/// mount and the final component.
+fn find_parent<'a>(
+    cwd: &Cwd,
+    path: &'a str,
+    cred: &Credentials,
+) -> SysResult<(Arc<dyn VfsInode>, usize, &'a str)> {
+    let (parent, name) = split_path(path);
+    let (parent, mount) = walk_path(cwd, parent, true, cred)?;
+    if !parent.is_dir() {
+        return Err(Errno::ENOTDIR);
+    }
+    // a removed directory stays empty, even as a working directory
+    if parent.stat().nlink == 0 {
+        return Err(Errno::ENOENT);
+    }
+    if name.len() > parent.name_length_limit() {
+        return Err(Errno::ENAMETOOLONG);
+    }
+    Ok((parent, mount, name))
+}
+
+/// Why creating `name` in `parent` failed after the checks passed: the name
+/// was taken meanwhile, or the file system is full.
+fn create_error(parent: &dyn VfsInode, name: &str) -> Errno {
+    if parent.find(name).is_some() {
+        Errno::EEXIST
+    } else {
+        Errno::ENOSPC
+    }
+}
+
+/// Give a new inode to the process creating it, if its file system keeps
+/// owners.
+fn set_owner(inode: &dyn VfsInode, mode: u16, cred: &Credentials) {
+    inode.chown(cred.euid as u16, cred.egid as u16);
+    inode.chmod(mode);
+}
+
+/// Create a directory at `path` with the permission bits of `mode`.
+pub fn create_dir(cwd: &Cwd, path: &str, mode: u16, cred: &Credentials) -> SysResult<()> {
+    let (parent, _, name) = find_parent(cwd, path, cred)?;
+    // an empty name stands for the parent itself, as in `/`
+    if name.is_empty() || parent.find(name).is_some() {
+        return Err(Errno::EEXIST);
+    }
+    cred.check(parent.as_ref(), W_OK | X_OK)?;
+    let dir = parent
+        .mkdir(name)
+        .ok_or_else(|| create_error(parent.as_ref(), name))?;
+    set_owner(dir.as_ref(), mode, cred);
+    Ok(())
+}
+
+/// Remove the file at `path`, or the empty directory if `is_dir` is set.
+pub fn unlink_file(cwd: &Cwd, path: &str, is_dir: bool, cred: &Credentials) -> SysResult<()> {
+    let (parent, mount, name) = find_parent(cwd, path, cred)?;
+    let inode = parent.find(name).ok_or(Errno::ENOENT)?;
+    match (inode.is_dir(), is_dir) {
+        (true, false) => return Err(Errno::EISDIR),
+        (false, true) => return Err(Errno::ENOTDIR),
+        _ => {}
+    }
+    if name == "." || name == ".." {
+        return Err(Errno::EINVAL);
+    }
+    if is_mount_point(mount, inode.as_ref()) {
+        return Err(Errno::EBUSY);
+    }
+    cred.check_remove(parent.as_ref(), inode.as_ref())?;
+    if parent.unlink(name) {
+        Ok(())
+    } else {
+        Err(Errno::ENOTEMPTY)
+    }
+}
+
+/// Create a hard link `new_path` to the file at `old_path`, or to the target
+/// of a symbolic link there if `follow` is set.
+pub fn link_file(
+    cwd: &Cwd,
+    old_path: &str,
+    new_path: &str,
+    follow: bool,
+    cred: &Credentials,
+) -> SysResult<()> {
+    let (inode, old_mount) = walk_path(cwd, old_path, follow, cred)?;
+    if inode.is_dir() {
+        return Err(Errno::EPERM);
+    }
+    let (parent, new_mount, name) = find_parent(cwd, new_path, cred)?;
+    if name.is_empty() || parent.find(name).is_some() {
+        return Err(Errno::EEXIST);
+    }
+    if old_mount != new_mount {
+        return Err(Errno::EXDEV);
+    }
+    cred.check(parent.as_ref(), W_OK | X_OK)?;
+    if parent.link(name, inode.as_ref()) {
+        Ok(())
+    } else {
+        Err(create_error(parent.as_ref(), name))
+    }
+}
+
+/// Create a symbolic link at `path` to `target`, which need not exist.
+pub fn symlink_file(cwd: &Cwd, target: &str, path: &str, cred: &Credentials) -> SysResult<()> {
+    if target.is_empty() {
+        return Err(Errno::ENOENT);
+    }
+    let (parent, _, name) = find_parent(cwd, path, cred)?;
+    if target.len() > parent.symlink_length_limit() {
+        return Err(Errno::ENAMETOOLONG);
+    }
+    if name.is_empty() || parent.find(name).is_some() {
+        return Err(Errno::EEXIST);
+    }
+    cred.check(parent.as_ref(), W_OK | X_OK)?;
+    let link = parent
+        .symlink(name, target)
+        .ok_or_else(|| create_error(parent.as_ref(), name))?;
+    // the mode of a link is never checked
+    link.chown(cred.euid as u16, cred.egid as u16);
+    Ok(())
+}
+
+/// Return the target of the symbolic link at `path`.
+pub fn read_link(cwd: &Cwd, path: &str, cred: &Credentials) -> SysResult<String> {
+    find_link(cwd, path, cred)?.readlink().ok_or(Errno::EINVAL)
+}
+
+pub fn rename_file(cwd: &Cwd, old_path: &str, new_path: &str, cred: &Credentials) -> SysResult<()> {
+    let (old_parent, old_mount, old_name) = find_parent(cwd, old_path, cred)?;
+    let inode = old_parent.find(old_name).ok_or(Errno::ENOENT)?;
+    let (new_parent, new_mount, new_name) = find_parent(cwd, new_path, cred)?;
+    let target = new_parent.find(new_name);
+    match target.as_ref().map(|target| target.is_dir()) {
+        Some(true) if !inode.is_dir() => return Err(Errno::EISDIR),
+        Some(false) if inode.is_dir() => return Err(Errno::ENOTDIR),
+        _ => {}
+    }
+    if old_mount != new_mount {
+        return Err(Errno::EXDEV);
+    }
+    if is_mount_point(old_mount, inode.as_ref())
+        || target
+            .as_ref()
+            .map_or(false, |target| is_mount_point(new_mount, target.as_ref()))
+    {
+        return Err(Errno::EBUSY);
+    }
+    cred.check_remove(old_parent.as_ref(), inode.as_ref())?;
+    match target.as_ref() {
+        Some(target) => cred.check_remove(new_parent.as_ref(), target.as_ref())?,
+        None => cred.check(new_parent.as_ref(), W_OK | X_OK)?,
+    }
+    if old_parent.rename(old_name, new_parent.as_ref(), new_name) {
+        Ok(())
+    } else if target.map_or(false, |target| target.is_dir()) {
+        Err(Errno::ENOTEMPTY)
+    } else {
+        // `.` or `..`, or a directory moved into itself
+        Err(Errno::EINVAL)
+    }
+}
+
+/// Create a file system of the type `fstype` to be mounted.
+fn new_fs(fstype: &str) -> SysResult<Arc<dyn FileSystem>> {
+    match fstype {
+        // the block device only holds the root file system, which is mounted
+        // on `/` already
+        "easyfs" => Err(Errno::EBUSY),
+        "tmpfs" => Ok(TmpFileSystem::new()),
+        _ => Err(Errno::ENODEV),
+    }
+}
+
+/// Mount a new file system of the type `fstype` on the directory at `target`,
+/// which only root may do.
+pub fn mount_fs(cwd: &Cwd, target: &str, fstype: &str, cred: &Credentials) -> SysResult<()> {
+    if !cred.is_root() {
+        return Err(Errno::EPERM);
+    }
+    let (point, parent) = walk_path(cwd, target, true, cred)?;
+    mount(parent, point, new_fs(fstype)?)
+}
+
+/// Unmount the file system whose root directory is at `target`.
+pub fn umount_fs(cwd: &Cwd, target: &str, cred: &Credentials) -> SysResult<()> {
+    if !cred.is_root() {
+        return Err(Errno::EPERM);
+    }
+    let (root, id) = walk_path(cwd, target, true, cred)?;
+    if !is_mount_root(id, root.as_ref()) {
+        return Err(Errno::EINVAL);
+    }
+    // the root directory would be busy with this reference
+    drop(root);
+    umount(id)
+}
+
+/// Create a regular file at `path`, or at the target of a dangling symbolic
+/// link there, whose directory must exist.
+fn create_file(
+    cwd: &Cwd,
+    path: &str,
+    mode: u16,
+    cred: &Credentials,
+) -> SysResult<Arc<dyn VfsInode>> {
+    let mut dir = cwd.clone();
+    let mut path = String::from(path);
+    for _ in 0..=MAX_SYMLINKS {
+        let (parent, mount, name) = find_parent(&dir, path.as_str(), cred)?;
+        let link = match parent.find(name) {
+            Some(link) if link.is_symlink() => link,
+            // created meanwhile
+            Some(_) => return Err(Errno::EEXIST),
+            None => {
+                cred.check(parent.as_ref(), W_OK | X_OK)?;
+                let inode = parent
+                    .create(name)
+                    .ok_or_else(|| create_error(parent.as_ref(), name))?;
+                set_owner(inode.as_ref(), mode, cred);
+                return Ok(inode);
+            }
+        };
+        // the target is relative to the directory holding the link
+        path = link.readlink().ok_or(Errno::EINVAL)?;
+        dir = Cwd {
+            inode: parent,
+            mount,
+        };
+    }
+    Err(Errno::ELOOP)
+}
+
+/// Open the file at `path`, a file created takes the permission bits of `mode`.
+pub fn open_file(
+    cwd: &Cwd,
+    path: &str,
+    flags: OpenFlags,
+    mode: u16,
+    cred: &Credentials,
+) -> SysResult<Arc<OSInode>> {
+    let (readable, writable) = flags.read_write();
+    match find_inode(cwd, path, cred) {
+        Ok(inode) => {
+            let clear = flags.contains(OpenFlags::CREATE) || flags.contains(OpenFlags::TRUNC);
+            // directories can only be opened read-only, and are never cut
+            if inode.is_dir() && (writable || clear) {
+                return Err(Errno::EISDIR);
+            }
+            let mut access = 0;
+            if readable {
+                access |= R_OK;
+            }
+            if writable || clear {
+                access |= W_OK;
+            }
+            cred.check(inode.as_ref(), access)?;
+            if clear {
+                // clear size
                 inode.clear();
             }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
+            Ok(Arc::new(OSInode::new(readable, writable, inode)))
+        }
+        Err(Errno::ENOENT) if flags.contains(OpenFlags::CREATE) => {
+            let inode = create_file(cwd, path, mode, cred)?;
+            Ok(Arc::new(OSInode::new(readable, writable, inode)))
+        }
+        Err(errno) => Err(errno),
+    }
+}
+
+/// Read from `offset` of `inode` into `buf`, return the read length.
+fn read_buffer(inode: &dyn VfsInode, offset: usize, mut buf: UserBuffer) -> usize {
+    let mut total_read_size = 0usize;
+    for slice in buf.buffers.iter_mut() {
+        let read_size = inode.read_at(offset + total_read_size, *slice);
+        if read_size == 0 {
+            break;
+        }
+        total_read_size += read_size;
+    }
+    total_read_size
+}
+
+/// Write `buf` to `inode` from `offset`, return the written length. Nothing
+/// written is an error unless `buf` is empty, `EFBIG` beyond the largest file
+/// and `ENOSPC` if the file system is full.
+fn write_buffer(inode: &dyn VfsInode, offset: usize, buf: UserBuffer) -> SysResult {
+    let mut total_write_size = 0usize;
+    for slice in buf.buffers.iter() {
+        let write_size = inode.write_at(offset + total_write_size, *slice);
+        total_write_size += write_size;
+        // the file is full, or tmpfs runs out of pages
+        if write_size < slice.len() {
+            break;
+        }
+    }
+    if total_write_size == 0 && buf.len() > 0 {
+        return Err(if offset >= inode.max_file_size() {
+            Errno::EFBIG
+        } else {
+            Errno::ENOSPC
+        });
     }
+    Ok(total_write_size)
 }
 
+const SEEK_SET: usize = 0;
+const SEEK_CUR: usize = 1;
+const SEEK_END: usize = 2;
+
 impl File for OSInode {
     fn readable(&self) -> bool {
         self.readable
@@ -112,28 +603,48 @@ impl File for OSInode {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, mut buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let mut total_read_size = 0usize;
-        for slice in buf.buffers.iter_mut() {
-            let read_size = inner.inode.read_at(inner.offset, *slice);
-            if read_size == 0 {
-                break;
-            }
-            inner.offset += read_size;
-            total_read_size += read_size;
-        }
-        total_read_size
-    }
-    fn write(&self, buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let mut total_write_size = 0usize;
-        for slice in buf.buffers.iter() {
-            let write_size = inner.inode.write_at(inner.offset, *slice);
-            assert_eq!(write_size, slice.len());
-            inner.offset += write_size;
-            total_write_size += write_size;
-        }
-        total_write_size
+    fn read(&self, buf: UserBuffer) -> usize {
+        self.with_offset(|inode, offset| {
+            let read_size = read_buffer(inode, *offset, buf);
+            *offset += read_size;
+            read_size
+        })
+    }
+    fn write(&self, buf: UserBuffer) -> SysResult {
+        self.with_offset(|inode, offset| {
+            let write_size = write_buffer(inode, *offset, buf)?;
+            *offset += write_size;
+            Ok(write_size)
+        })
+    }
+    fn stat(&self) -> Stat {
+        self.position().0.stat()
+    }
+    /// Seeking past the end is allowed, a later write leaves a hole of zeros.
+    fn seek(&self, offset: isize, whence: usize) -> SysResult {
+        self.with_offset(|inode, current| {
+            let base = match whence {
+                SEEK_SET => 0,
+                SEEK_CUR => *current,
+                SEEK_END => inode.size(),
+                _ => return Err(Errno::EINVAL),
+            };
+            *current = (base as isize)
+                .checked_add(offset)
+                .filter(|offset| *offset >= 0)
+                .ok_or(Errno::EINVAL)? as usize;
+            Ok(*current)
+        })
+    }
+    fn read_at(&self, offset: usize, buf: UserBuffer) -> SysResult {
+        let (inode, _) = self.position();
+        Ok(read_buffer(inode.as_ref(), offset, buf))
+    }
+    fn write_at(&self, offset: usize, buf: UserBuffer) -> SysResult {
+        let (inode, _) = self.position();
+        write_buffer(inode.as_ref(), offset, buf)
+    }
+    fn inode(&self) -> Option<Arc<dyn VfsInode>> {
+        Some(self.inner.exclusive_access().inode.clone())
     }
 }
@@ -1,16 +1,102 @@
+mod efs;
 mod inode;
+mod mount;
+mod perm;
 mod pipe;
 mod stdio;
+mod tmpfs;
+mod vfs;
 
+use crate::errno::{Errno, SysResult};
 use crate::mm::UserBuffer;
+use alloc::sync::Arc;
+use bitflags::*;
 
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
     fn read(&self, buf: UserBuffer) -> usize;
-    fn write(&self, buf: UserBuffer) -> usize;
+    /// Return the written length, an error if nothing could be written.
+    fn write(&self, buf: UserBuffer) -> SysResult;
+    fn stat(&self) -> Stat;
+    /// Move the offset of the next read or write, return the new offset.
+    fn seek(&self, _offset: isize, _whence: usize) -> SysResult {
+        Err(Errno::ESPIPE)
+    }
+    /// Read from `offset` without moving the offset of the file.
+    fn read_at(&self, _offset: usize, _buf: UserBuffer) -> SysResult {
+        Err(Errno::ESPIPE)
+    }
+    /// Write at `offset` without moving the offset of the file.
+    fn write_at(&self, _offset: usize, _buf: UserBuffer) -> SysResult {
+        Err(Errno::ESPIPE)
+    }
+    /// The inode behind a regular file, `None` for pipes and stdio.
+    fn inode(&self) -> Option<Arc<dyn VfsInode>> {
+        None
+    }
 }
 
-pub use inode::{list_apps, open_file, OSInode, OpenFlags, ROOT_INODE};
+bitflags! {
+    /// The type of a file.
+    pub struct StatMode: u32 {
+        const FIFO = 0o010000;
+        const CHR = 0o020000;
+        const DIR = 0o040000;
+        const FILE = 0o100000;
+        const LNK = 0o120000;
+    }
+}
+
+/// Status of a file filled by `sys_fstat`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Stat {
+    /// Inode number, 0 if the file is not on the disk.
+    pub ino: u64,
+    pub mode: StatMode,
+    /// Permission bits together with the setuid, setgid and sticky bits.
+    pub perm: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    /// Number of 512-byte blocks taken on the disk.
+    pub blocks: u64,
+    /// Seconds since the Unix epoch of the last access, the last change of
+    /// the data and the last change of the data or the inode.
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+}
+
+impl Stat {
+    /// The status of a file which only lives in memory.
+    pub fn new(mode: StatMode) -> Self {
+        Self {
+            ino: 0,
+            mode,
+            perm: 0o600,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            blocks: 0,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+        }
+    }
+}
+
+pub use efs::EFS;
+pub use inode::{
+    create_dir, find_inode, find_link, link_file, list_apps, mount_fs, open_file, read_link,
+    rename_file, symlink_file, sync_all, sync_periodically, umount_fs, unlink_file, Cwd, OSInode,
+    OpenFlags,
+};
+pub use perm::{Credentials, R_OK, S_ISGID, S_ISUID, S_ISVTX, W_OK, X_OK};
 pub use pipe::{make_pipe, Pipe};
 pub use stdio::{Stdin, Stdout};
+pub use tmpfs::TmpFileSystem;
+pub use vfs::{FileSystem, VfsInode};
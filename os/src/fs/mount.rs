@@ -0,0 +1,182 @@
+//! The mount table, which covers directories with the root directories of
+//! other file systems.
+
+use super::{FileSystem, VfsInode, EFS};
+use crate::errno::{Errno, SysResult};
+use crate::sync::SpinIntrFreeCell;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Id of the mount of the root file system.
+pub const ROOT_MOUNT: usize = 0;
+
+struct Mount {
+    id: usize,
+    /// The mount holding `point`, itself for the root file system.
+    parent: usize,
+    /// The directory covered.
+    point: Arc<dyn VfsInode>,
+    root: Arc<dyn VfsInode>,
+    fs: Arc<dyn FileSystem>,
+}
+
+struct MountTable {
+    mounts: Vec<Mount>,
+    next_id: usize,
+}
+
+impl MountTable {
+    /// The mount `id`, `None` once it is unmounted.
+    fn get(&self, id: usize) -> Option<&Mount> {
+        self.mounts.iter().find(|mount| mount.id == id)
+    }
+    /// The mount on `inode` of the mount `parent`.
+    fn on(&self, parent: usize, inode: &dyn VfsInode) -> Option<&Mount> {
+        self.mounts.iter().find(|mount| {
+            mount.id != ROOT_MOUNT && mount.parent == parent && mount.point.ino() == inode.ino()
+        })
+    }
+}
+
+lazy_static! {
+    static ref MOUNTS: SpinIntrFreeCell<MountTable> = {
+        let fs: Arc<dyn FileSystem> = EFS.clone();
+        let root = fs.root_inode();
+        SpinIntrFreeCell::new(MountTable {
+            mounts: vec![Mount {
+                id: ROOT_MOUNT,
+                parent: ROOT_MOUNT,
+                point: root.clone(),
+                root,
+                fs,
+            }],
+            next_id: ROOT_MOUNT + 1,
+        })
+    };
+}
+
+/// The root directory of the whole tree.
+pub fn root_inode() -> Arc<dyn VfsInode> {
+    MOUNTS
+        .exclusive_access()
+        .get(ROOT_MOUNT)
+        .unwrap()
+        .root
+        .clone()
+}
+
+/// Enter the file system mounted on `inode` of the mount `id`, if there is
+/// one. Return the inode to go on with and its mount.
+pub fn cross_mount(id: usize, inode: Arc<dyn VfsInode>) -> (Arc<dyn VfsInode>, usize) {
+    match MOUNTS.exclusive_access().on(id, inode.as_ref()) {
+        Some(mount) => (mount.root.clone(), mount.id),
+        None => (inode, id),
+    }
+}
+
+/// Leave the root directory of the mount `id` for the directory it covers,
+/// where `..` is looked up instead. `None` for any other directory.
+pub fn leave_mount(id: usize, inode: &dyn VfsInode) -> Option<(Arc<dyn VfsInode>, usize)> {
+    let table = MOUNTS.exclusive_access();
+    let mount = table.get(id)?;
+    if id == ROOT_MOUNT || mount.root.ino() != inode.ino() {
+        return None;
+    }
+    Some((mount.point.clone(), mount.parent))
+}
+
+/// Whether a file system is mounted on `inode` of the mount `id`.
+pub fn is_mount_point(id: usize, inode: &dyn VfsInode) -> bool {
+    MOUNTS.exclusive_access().on(id, inode).is_some()
+}
+
+/// Whether `inode` is the root directory of the mount `id`, which is still
+/// mounted.
+pub fn is_mount_root(id: usize, inode: &dyn VfsInode) -> bool {
+    MOUNTS
+        .exclusive_access()
+        .get(id)
+        .map_or(false, |mount| mount.root.ino() == inode.ino())
+}
+
+/// Mount `fs` on the directory `point` of the mount `parent`, which must
+/// neither be the root of a mount nor covered by one already.
+pub fn mount(parent: usize, point: Arc<dyn VfsInode>, fs: Arc<dyn FileSystem>) -> SysResult<()> {
+    if !point.is_dir() {
+        return Err(Errno::ENOTDIR);
+    }
+    let root = fs.root_inode();
+    let mut table = MOUNTS.exclusive_access();
+    let busy = match table.get(parent) {
+        Some(mount) => {
+            mount.root.ino() == point.ino() || table.on(parent, point.as_ref()).is_some()
+        }
+        // the parent was unmounted meanwhile
+        None => return Err(Errno::ENOENT),
+    };
+    if busy {
+        return Err(Errno::EBUSY);
+    }
+    let id = table.next_id;
+    table.next_id += 1;
+    table.mounts.push(Mount {
+        id,
+        parent,
+        point,
+        root,
+        fs,
+    });
+    Ok(())
+}
+
+/// Unmount the file system of the mount `id` after writing it back. It is
+/// busy while other file systems are mounted on it, or any of its files is
+/// open or the working directory of a process.
+pub fn umount(id: usize) -> SysResult<()> {
+    if id == ROOT_MOUNT {
+        return Err(Errno::EBUSY);
+    }
+    let fs = match MOUNTS.exclusive_access().get(id) {
+        Some(mount) => mount.fs.clone(),
+        None => return Err(Errno::EINVAL),
+    };
+    // do not hold the table during disk accesses which may block
+    fs.sync();
+    let mount = {
+        let mut table = MOUNTS.exclusive_access();
+        // another hart may have unmounted it while the table was released
+        let index = table
+            .mounts
+            .iter()
+            .position(|mount| mount.id == id)
+            .ok_or(Errno::EINVAL)?;
+        let mount = &table.mounts[index];
+        // only the table holds the root directory, and the file system counts
+        // the handles of the other inodes
+        if table.mounts.iter().any(|mount| mount.parent == id)
+            || Arc::strong_count(&mount.root) > 1
+            || mount.fs.busy()
+        {
+            return Err(Errno::EBUSY);
+        }
+        table.mounts.remove(index)
+    };
+    // a file system is freed with its last inode, out of the table
+    drop(mount);
+    Ok(())
+}
+
+/// Write back all mounted file systems.
+pub fn sync_mounts() {
+    let file_systems: Vec<Arc<dyn FileSystem>> = MOUNTS
+        .exclusive_access()
+        .mounts
+        .iter()
+        .map(|mount| mount.fs.clone())
+        .collect();
+    for fs in file_systems {
+        fs.sync();
+    }
+}
@@ -0,0 +1,83 @@
+//! Owners and permission bits of inodes, checked against the credentials of
+//! a process.
+
+use super::VfsInode;
+use crate::errno::{Errno, SysResult};
+
+/// Bits of the access checked by `Credentials::check`.
+pub const R_OK: u16 = 4;
+pub const W_OK: u16 = 2;
+pub const X_OK: u16 = 1;
+
+/// Run an executable with the user of its owner.
+pub const S_ISUID: u16 = 0o4000;
+/// Run an executable with the group of its owner.
+pub const S_ISGID: u16 = 0o2000;
+/// Only the owners of the entries or of the directory may remove them.
+pub const S_ISVTX: u16 = 0o1000;
+
+/// Identity of a process, files are accessed with the effective ids.
+#[derive(Clone, Copy)]
+pub struct Credentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub euid: u32,
+    pub egid: u32,
+}
+
+impl Credentials {
+    /// The superuser, who starts the first process.
+    pub fn root() -> Self {
+        Self {
+            uid: 0,
+            gid: 0,
+            euid: 0,
+            egid: 0,
+        }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.euid == 0
+    }
+
+    /// Whether this is the owner of `inode` or root, who can change its mode.
+    pub fn owns(&self, inode: &dyn VfsInode) -> bool {
+        self.is_root() || self.euid == inode.owner().0 as u32
+    }
+
+    /// Check `access` of `R_OK`, `W_OK` and `X_OK` against the bits of the
+    /// owner, the group or the others of `inode`. Root passes except for
+    /// executing a file without any execute bit.
+    pub fn check(&self, inode: &dyn VfsInode, access: u16) -> SysResult<()> {
+        let mode = inode.mode();
+        if self.is_root() {
+            if access & X_OK == 0 || mode & 0o111 != 0 || inode.is_dir() {
+                return Ok(());
+            }
+            return Err(Errno::EACCES);
+        }
+        let (uid, gid) = inode.owner();
+        let bits = if self.euid == uid as u32 {
+            mode >> 6
+        } else if self.egid == gid as u32 {
+            mode >> 3
+        } else {
+            mode
+        };
+        if bits & access == access {
+            Ok(())
+        } else {
+            Err(Errno::EACCES)
+        }
+    }
+
+    /// Check that the entry for `inode` in `dir` may be removed or replaced,
+    /// which the sticky bit of `dir` restricts to the owners.
+    pub fn check_remove(&self, dir: &dyn VfsInode, inode: &dyn VfsInode) -> SysResult<()> {
+        self.check(dir, W_OK | X_OK)?;
+        if dir.mode() & S_ISVTX != 0 && !self.owns(dir) && !self.owns(inode) {
+            return Err(Errno::EPERM);
+        }
+        Ok(())
+    }
+}
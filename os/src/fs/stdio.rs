@@ -1,6 +1,7 @@
-use super::File;
+use super::{File, Stat, StatMode};
 use crate::drivers::chardev::CharDevice;
 use crate::drivers::chardev::UART;
+use crate::errno::SysResult;
 use crate::mm::UserBuffer;
 
 pub struct Stdin;
@@ -22,9 +23,12 @@ impl File for Stdin {
         }
         1
     }
-    fn write(&self, _user_buf: UserBuffer) -> usize {
+    fn write(&self, _user_buf: UserBuffer) -> SysResult {
         panic!("Cannot write to stdin!");
     }
+    fn stat(&self) -> Stat {
+        Stat::new(StatMode::CHR)
+    }
 }
 
 impl File for Stdout {
@@ -37,10 +41,13 @@ impl File for Stdout {
     fn read(&self, _user_buf: UserBuffer) -> usize {
         panic!("Cannot read from stdout!");
     }
-    fn write(&self, user_buf: UserBuffer) -> usize {
+    fn write(&self, user_buf: UserBuffer) -> SysResult {
         for buffer in user_buf.buffers.iter() {
             print!("{}", core::str::from_utf8(*buffer).unwrap());
         }
-        user_buf.len()
+        Ok(user_buf.len())
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(StatMode::CHR)
     }
 }
@@ -0,0 +1,552 @@
+//! A file system in memory, whose data pages come from the frame allocator,
+//! at most `TMPFS_MAX_PAGES` for each.
+//!
+//! All nodes of a file system are kept in one table, so an operation takes a
+//! single lock. A node is freed once it has neither links nor handles, and
+//! the whole table is freed with the file system when the last handle goes.
+
+use super::{FileSystem, Stat, StatMode, VfsInode};
+use crate::config::{PAGE_SIZE, TMPFS_MAX_PAGES};
+use crate::mm::{frame_alloc, FrameTracker};
+use crate::sync::SpinIntrFreeCell;
+use crate::timer::get_real_time_sec;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+
+const NAME_LENGTH_LIMIT: usize = 255;
+const SYMLINK_LENGTH_LIMIT: usize = PAGE_SIZE - 1;
+/// Files are sparse, so this only bounds `ftruncate` and writes.
+const MAX_FILE_SIZE: usize = 1 << 32;
+const ROOT_INO: u64 = 1;
+
+enum Content {
+    /// Pages of a regular file by their index, holes have none.
+    File(BTreeMap<usize, FrameTracker>),
+    /// Entries of a directory besides `.` and `..`, which is `parent`.
+    Dir {
+        parent: u64,
+        entries: BTreeMap<String, u64>,
+    },
+    Symlink(String),
+}
+
+struct Node {
+    content: Content,
+    size: usize,
+    mode: u16,
+    uid: u16,
+    gid: u16,
+    nlink: u32,
+    /// Number of `TmpInode`s of this node.
+    handles: usize,
+    atime: u32,
+    mtime: u32,
+    ctime: u32,
+}
+
+impl Node {
+    fn new(content: Content, mode: u16, nlink: u32) -> Self {
+        let now = get_real_time_sec() as u32;
+        let size = match &content {
+            Content::Symlink(target) => target.len(),
+            _ => 0,
+        };
+        Self {
+            content,
+            size,
+            mode,
+            uid: 0,
+            gid: 0,
+            nlink,
+            handles: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+        }
+    }
+    fn is_dir(&self) -> bool {
+        matches!(self.content, Content::Dir { .. })
+    }
+    /// Number of pages of a regular file.
+    fn pages(&self) -> usize {
+        match &self.content {
+            Content::File(pages) => pages.len(),
+            _ => 0,
+        }
+    }
+    fn touch(&mut self) {
+        self.mtime = get_real_time_sec() as u32;
+        self.ctime = self.mtime;
+    }
+    /// Cut or extend a regular file to `new_size`, the part added reads as
+    /// zeros. Return the number of pages freed.
+    fn resize(&mut self, new_size: usize) -> usize {
+        let old_pages = self.pages();
+        if let Content::File(pages) = &mut self.content {
+            if new_size < self.size {
+                pages.split_off(&((new_size + PAGE_SIZE - 1) / PAGE_SIZE));
+                // the rest of the last page may be exposed by extending again
+                if let Some(frame) = pages.get(&(new_size / PAGE_SIZE)) {
+                    frame.ppn.get_bytes_array()[new_size % PAGE_SIZE..].fill(0);
+                }
+            }
+            self.size = new_size;
+        }
+        old_pages - self.pages()
+    }
+}
+
+struct Tree {
+    nodes: BTreeMap<u64, Node>,
+    next_ino: u64,
+    /// Pages of all regular files, at most `TMPFS_MAX_PAGES`.
+    pages: usize,
+}
+
+impl Tree {
+    fn node(&mut self, ino: u64) -> &mut Node {
+        self.nodes.get_mut(&ino).unwrap()
+    }
+    /// Free the node `ino` together with its pages.
+    fn remove(&mut self, ino: u64) {
+        let node = self.nodes.remove(&ino).unwrap();
+        self.pages -= node.pages();
+    }
+    /// Entries of the directory `ino`, None for other nodes.
+    fn entries(&mut self, ino: u64) -> Option<&mut BTreeMap<String, u64>> {
+        match &mut self.node(ino).content {
+            Content::Dir { entries, .. } => Some(entries),
+            _ => None,
+        }
+    }
+    /// Find the inode number of `name` in the directory `dir`.
+    fn lookup(&mut self, dir: u64, name: &str) -> Option<u64> {
+        match &self.node(dir).content {
+            Content::Dir { parent, entries } => match name {
+                "." => Some(dir),
+                ".." => Some(*parent),
+                _ => entries.get(name).copied(),
+            },
+            _ => None,
+        }
+    }
+    /// Drop a link to `ino`, a directory loses both its links and the one
+    /// from its `..` to the parent.
+    fn unlink_node(&mut self, ino: u64) {
+        let node = self.node(ino);
+        if let Content::Dir { parent, .. } = node.content {
+            node.nlink = 0;
+            self.node(parent).nlink -= 1;
+        } else {
+            node.nlink -= 1;
+        }
+        let node = self.node(ino);
+        node.ctime = get_real_time_sec() as u32;
+        if node.nlink == 0 && node.handles == 0 {
+            self.remove(ino);
+        }
+    }
+    /// Whether the directory `dir` is `ino` or inside it.
+    fn is_within(&mut self, mut dir: u64, ino: u64) -> bool {
+        loop {
+            if dir == ino {
+                return true;
+            }
+            match self.node(dir).content {
+                Content::Dir { parent, .. } if dir != ROOT_INO => dir = parent,
+                _ => return false,
+            }
+        }
+    }
+}
+
+pub struct TmpFileSystem {
+    /// The `Arc` of this file system, held by its inodes.
+    me: Weak<TmpFileSystem>,
+    tree: SpinIntrFreeCell<Tree>,
+}
+
+impl TmpFileSystem {
+    /// An empty file system, whose root directory anyone may write.
+    pub fn new() -> Arc<Self> {
+        let mut nodes = BTreeMap::new();
+        let root = Content::Dir {
+            parent: ROOT_INO,
+            entries: BTreeMap::new(),
+        };
+        nodes.insert(ROOT_INO, Node::new(root, 0o1777, 2));
+        Arc::new_cyclic(|me| Self {
+            me: me.clone(),
+            tree: SpinIntrFreeCell::new(Tree {
+                nodes,
+                next_ino: ROOT_INO + 1,
+                pages: 0,
+            }),
+        })
+    }
+    /// A new handle of the node `ino`.
+    fn handle(&self, tree: &mut Tree, ino: u64) -> Arc<dyn VfsInode> {
+        tree.node(ino).handles += 1;
+        Arc::new(TmpInode {
+            fs: self.me.upgrade().unwrap(),
+            ino,
+        })
+    }
+}
+
+impl FileSystem for TmpFileSystem {
+    fn root_inode(&self) -> Arc<dyn VfsInode> {
+        self.handle(&mut self.tree.exclusive_access(), ROOT_INO)
+    }
+    fn busy(&self) -> bool {
+        self.tree
+            .exclusive_access()
+            .nodes
+            .iter()
+            .any(|(ino, node)| node.handles > (*ino == ROOT_INO) as usize)
+    }
+}
+
+pub struct TmpInode {
+    fs: Arc<TmpFileSystem>,
+    ino: u64,
+}
+
+impl TmpInode {
+    /// Access the node of this inode.
+    fn with_node<V>(&self, f: impl FnOnce(&mut Node) -> V) -> V {
+        f(self.fs.tree.exclusive_access().node(self.ino))
+    }
+    /// The inode behind `inode` if it is on the same file system.
+    fn of<'a>(&self, inode: &'a dyn VfsInode) -> Option<&'a TmpInode> {
+        inode
+            .as_any()
+            .downcast_ref::<TmpInode>()
+            .filter(|inode| Arc::ptr_eq(&inode.fs, &self.fs))
+    }
+    /// Add the entry `name` for a new node in this directory, which must not
+    /// have been removed.
+    fn add(&self, name: &str, content: Content, mode: u16) -> Option<Arc<dyn VfsInode>> {
+        let mut tree = self.fs.tree.exclusive_access();
+        if tree.entries(self.ino).is_none()
+            || tree.node(self.ino).nlink == 0
+            || name == "."
+            || name == ".."
+            || tree.lookup(self.ino, name).is_some()
+        {
+            return None;
+        }
+        let is_dir = matches!(content, Content::Dir { .. });
+        let ino = tree.next_ino;
+        tree.next_ino += 1;
+        tree.nodes
+            .insert(ino, Node::new(content, mode, if is_dir { 2 } else { 1 }));
+        tree.entries(self.ino)
+            .unwrap()
+            .insert(String::from(name), ino);
+        let dir = tree.node(self.ino);
+        if is_dir {
+            dir.nlink += 1;
+        }
+        dir.touch();
+        Some(self.fs.handle(&mut tree, ino))
+    }
+}
+
+impl Drop for TmpInode {
+    fn drop(&mut self) {
+        let mut tree = self.fs.tree.exclusive_access();
+        let node = tree.node(self.ino);
+        node.handles -= 1;
+        if node.handles == 0 && node.nlink == 0 {
+            tree.remove(self.ino);
+        }
+    }
+}
+
+impl VfsInode for TmpInode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+    fn is_dir(&self) -> bool {
+        self.with_node(|node| node.is_dir())
+    }
+    fn is_symlink(&self) -> bool {
+        self.with_node(|node| matches!(node.content, Content::Symlink(_)))
+    }
+    fn size(&self) -> usize {
+        self.with_node(|node| node.size)
+    }
+    fn stat(&self) -> Stat {
+        self.with_node(|node| Stat {
+            ino: self.ino,
+            mode: match node.content {
+                Content::Dir { .. } => StatMode::DIR,
+                Content::Symlink(_) => StatMode::LNK,
+                Content::File(_) => StatMode::FILE,
+            },
+            perm: node.mode as u32,
+            nlink: node.nlink,
+            uid: node.uid as u32,
+            gid: node.gid as u32,
+            size: node.size as u64,
+            blocks: match &node.content {
+                Content::File(pages) => (pages.len() * PAGE_SIZE / 512) as u64,
+                _ => 0,
+            },
+            atime: node.atime as u64,
+            mtime: node.mtime as u64,
+            ctime: node.ctime as u64,
+        })
+    }
+    fn mode(&self) -> u16 {
+        self.with_node(|node| node.mode)
+    }
+    fn owner(&self) -> (u16, u16) {
+        self.with_node(|node| (node.uid, node.gid))
+    }
+    fn chmod(&self, mode: u16) -> bool {
+        self.with_node(|node| {
+            node.mode = mode & 0o7777;
+            node.ctime = get_real_time_sec() as u32;
+        });
+        true
+    }
+    fn chown(&self, uid: u16, gid: u16) -> bool {
+        self.with_node(|node| {
+            node.uid = uid;
+            node.gid = gid;
+            node.ctime = get_real_time_sec() as u32;
+        });
+        true
+    }
+    fn name_length_limit(&self) -> usize {
+        NAME_LENGTH_LIMIT
+    }
+    fn symlink_length_limit(&self) -> usize {
+        SYMLINK_LENGTH_LIMIT
+    }
+    fn max_file_size(&self) -> usize {
+        MAX_FILE_SIZE
+    }
+
+    fn find(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        let mut tree = self.fs.tree.exclusive_access();
+        let ino = tree.lookup(self.ino, name)?;
+        Some(self.fs.handle(&mut tree, ino))
+    }
+    fn ls(&self) -> Vec<String> {
+        let mut tree = self.fs.tree.exclusive_access();
+        let mut names = Vec::new();
+        if let Some(entries) = tree.entries(self.ino) {
+            names.push(String::from("."));
+            names.push(String::from(".."));
+            names.extend(entries.keys().cloned());
+        }
+        names
+    }
+    fn create(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        self.add(name, Content::File(BTreeMap::new()), 0o644)
+    }
+    fn mkdir(&self, name: &str) -> Option<Arc<dyn VfsInode>> {
+        let dir = Content::Dir {
+            parent: self.ino,
+            entries: BTreeMap::new(),
+        };
+        self.add(name, dir, 0o755)
+    }
+    fn symlink(&self, name: &str, target: &str) -> Option<Arc<dyn VfsInode>> {
+        if target.is_empty() || target.len() > SYMLINK_LENGTH_LIMIT {
+            return None;
+        }
+        self.add(name, Content::Symlink(String::from(target)), 0o777)
+    }
+    fn readlink(&self) -> Option<String> {
+        self.with_node(|node| match &node.content {
+            Content::Symlink(target) => Some(target.clone()),
+            _ => None,
+        })
+    }
+    /// Directories can not be hard linked.
+    fn link(&self, name: &str, inode: &dyn VfsInode) -> bool {
+        let inode = match self.of(inode) {
+            Some(inode) => inode,
+            None => return false,
+        };
+        let mut tree = self.fs.tree.exclusive_access();
+        if tree.entries(self.ino).is_none()
+            || name == "."
+            || name == ".."
+            || tree.lookup(self.ino, name).is_some()
+        {
+            return false;
+        }
+        let node = tree.node(inode.ino);
+        if node.is_dir() {
+            return false;
+        }
+        node.nlink += 1;
+        node.ctime = get_real_time_sec() as u32;
+        tree.entries(self.ino)
+            .unwrap()
+            .insert(String::from(name), inode.ino);
+        tree.node(self.ino).touch();
+        true
+    }
+    fn unlink(&self, name: &str) -> bool {
+        let mut tree = self.fs.tree.exclusive_access();
+        if name == "." || name == ".." {
+            return false;
+        }
+        let ino = match tree.lookup(self.ino, name) {
+            Some(ino) => ino,
+            None => return false,
+        };
+        if tree
+            .entries(ino)
+            .map_or(false, |entries| !entries.is_empty())
+        {
+            return false;
+        }
+        tree.entries(self.ino).unwrap().remove(name);
+        tree.node(self.ino).touch();
+        tree.unlink_node(ino);
+        true
+    }
+    /// An existing `new_name` is replaced if it has the same type as the
+    /// moved node and is not a non-empty directory.
+    fn rename(&self, old_name: &str, new_dir: &dyn VfsInode, new_name: &str) -> bool {
+        let new_dir = match self.of(new_dir) {
+            Some(new_dir) => new_dir.ino,
+            None => return false,
+        };
+        let mut tree = self.fs.tree.exclusive_access();
+        if old_name == "." || old_name == ".." || new_name == "." || new_name == ".." {
+            return false;
+        }
+        let ino = match tree.lookup(self.ino, old_name) {
+            Some(ino) => ino,
+            None => return false,
+        };
+        let is_dir = tree.node(ino).is_dir();
+        if tree.entries(new_dir).is_none() || (is_dir && tree.is_within(new_dir, ino)) {
+            return false;
+        }
+        if let Some(target) = tree.lookup(new_dir, new_name) {
+            if target == ino {
+                return true;
+            }
+            let target_is_dir = tree.node(target).is_dir();
+            if target_is_dir != is_dir
+                || tree
+                    .entries(target)
+                    .map_or(false, |entries| !entries.is_empty())
+            {
+                return false;
+            }
+            tree.unlink_node(target);
+        }
+        tree.entries(self.ino).unwrap().remove(old_name);
+        tree.entries(new_dir)
+            .unwrap()
+            .insert(String::from(new_name), ino);
+        if is_dir && new_dir != self.ino {
+            if let Content::Dir { parent, .. } = &mut tree.node(ino).content {
+                *parent = new_dir;
+            }
+            tree.node(self.ino).nlink -= 1;
+            tree.node(new_dir).nlink += 1;
+        }
+        tree.node(ino).ctime = get_real_time_sec() as u32;
+        tree.node(self.ino).touch();
+        tree.node(new_dir).touch();
+        true
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.with_node(|node| {
+            let pages = match &node.content {
+                Content::File(pages) => pages,
+                _ => return 0,
+            };
+            let end = node.size.min(offset.saturating_add(buf.len()));
+            let mut pos = offset;
+            while pos < end {
+                let len = (PAGE_SIZE - pos % PAGE_SIZE).min(end - pos);
+                let dst = &mut buf[pos - offset..pos - offset + len];
+                match pages.get(&(pos / PAGE_SIZE)) {
+                    Some(frame) => dst.copy_from_slice(
+                        &frame.ppn.get_bytes_array()[pos % PAGE_SIZE..pos % PAGE_SIZE + len],
+                    ),
+                    None => dst.fill(0),
+                }
+                pos += len;
+            }
+            node.atime = get_real_time_sec() as u32;
+            end.saturating_sub(offset)
+        })
+    }
+    /// Nothing is written beyond `MAX_FILE_SIZE`, and the write stops early
+    /// when the file system holds `TMPFS_MAX_PAGES` or frames run out.
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut tree = self.fs.tree.exclusive_access();
+        let mut free_pages = TMPFS_MAX_PAGES - tree.pages;
+        let node = tree.node(self.ino);
+        let pages = match &mut node.content {
+            Content::File(pages) => pages,
+            _ => return 0,
+        };
+        let end = MAX_FILE_SIZE.min(offset.saturating_add(buf.len()));
+        if offset >= end {
+            return 0;
+        }
+        let mut pos = offset;
+        while pos < end {
+            let len = (PAGE_SIZE - pos % PAGE_SIZE).min(end - pos);
+            let frame = match pages.get(&(pos / PAGE_SIZE)) {
+                Some(frame) => frame,
+                None if free_pages == 0 => break,
+                None => match frame_alloc() {
+                    Some(frame) => {
+                        free_pages -= 1;
+                        pages.entry(pos / PAGE_SIZE).or_insert(frame)
+                    }
+                    None => break,
+                },
+            };
+            frame.ppn.get_bytes_array()[pos % PAGE_SIZE..pos % PAGE_SIZE + len]
+                .copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        if pos > offset {
+            node.size = node.size.max(pos);
+            node.touch();
+        }
+        tree.pages = TMPFS_MAX_PAGES - free_pages;
+        pos - offset
+    }
+    fn clear(&self) {
+        let mut tree = self.fs.tree.exclusive_access();
+        let node = tree.node(self.ino);
+        let freed = node.resize(0);
+        node.touch();
+        tree.pages -= freed;
+    }
+    fn truncate(&self, new_size: usize) -> bool {
+        let mut tree = self.fs.tree.exclusive_access();
+        let node = tree.node(self.ino);
+        if new_size > MAX_FILE_SIZE || !matches!(node.content, Content::File(_)) {
+            return false;
+        }
+        let freed = node.resize(new_size);
+        node.touch();
+        tree.pages -= freed;
+        true
+    }
+}
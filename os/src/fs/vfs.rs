@@ -0,0 +1,76 @@
+//! Interfaces between the kernel and the file systems it mounts.
+
+use super::Stat;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+
+/// A file system mounted on a directory, or the root one.
+pub trait FileSystem: Send + Sync {
+    fn root_inode(&self) -> Arc<dyn VfsInode>;
+    /// Write all cached data back, called by `sync` and before unmounting.
+    fn sync(&self) {}
+    /// Whether any inode but the root directory given to the mount table has
+    /// handles, which keeps the file system from being unmounted.
+    fn busy(&self) -> bool;
+}
+
+/// A file, directory or symbolic link of a file system. Failures are reported
+/// as by `easy_fs::Inode` and turned into errors by the kernel, which checks
+/// the permissions and limits before.
+pub trait VfsInode: Send + Sync {
+    /// For operations on two inodes of the same file system.
+    fn as_any(&self) -> &dyn Any;
+    /// Number of the inode, unique within its file system. It is asked with
+    /// the mount table held, so it must not block.
+    fn ino(&self) -> u64;
+    fn is_dir(&self) -> bool;
+    fn is_symlink(&self) -> bool;
+    fn size(&self) -> usize;
+    fn stat(&self) -> Stat;
+    /// The permission bits together with the setuid, setgid and sticky bits.
+    fn mode(&self) -> u16;
+    /// The user and group owning the inode.
+    fn owner(&self) -> (u16, u16);
+    /// False if the file system keeps no modes.
+    fn chmod(&self, mode: u16) -> bool;
+    /// False if the file system keeps no owners.
+    fn chown(&self, uid: u16, gid: u16) -> bool;
+    /// Longest name in bytes a directory entry can hold.
+    fn name_length_limit(&self) -> usize;
+    /// Longest target in bytes a symbolic link can hold.
+    fn symlink_length_limit(&self) -> usize;
+    /// Largest size of a regular file.
+    fn max_file_size(&self) -> usize;
+
+    /// Find the entry `name` of this directory.
+    fn find(&self, name: &str) -> Option<Arc<dyn VfsInode>>;
+    /// Names of the entries of this directory.
+    fn ls(&self) -> Vec<String>;
+    /// Create a regular file in this directory.
+    fn create(&self, name: &str) -> Option<Arc<dyn VfsInode>>;
+    /// Create a sub-directory in this directory.
+    fn mkdir(&self, name: &str) -> Option<Arc<dyn VfsInode>>;
+    /// Create a symbolic link to `target` in this directory.
+    fn symlink(&self, name: &str, target: &str) -> Option<Arc<dyn VfsInode>>;
+    /// Return the target of a symbolic link, or None for other files.
+    fn readlink(&self) -> Option<String>;
+    /// Add the entry `name` in this directory for a file of the same file
+    /// system.
+    fn link(&self, name: &str, inode: &dyn VfsInode) -> bool;
+    /// Remove the entry `name`, false if it is a directory not empty.
+    fn unlink(&self, name: &str) -> bool;
+    /// Move the entry `old_name` to `new_name` in `new_dir` of the same file
+    /// system, replacing a file or an empty directory there.
+    fn rename(&self, old_name: &str, new_dir: &dyn VfsInode, new_name: &str) -> bool;
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize;
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize;
+    /// Cut a regular file to zero size.
+    fn clear(&self);
+    /// Change the size of a regular file, the part added reads as zeros.
+    fn truncate(&self, new_size: usize) -> bool;
+    /// Write back the data of this file.
+    fn sync(&self) {}
+}
@@ -2,6 +2,7 @@
 #![no_main]
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
+#![feature(asm_const)]
 
 //use crate::drivers::{GPU_DEVICE, KEYBOARD_DEVICE, MOUSE_DEVICE, INPUT_CONDVAR};
 use crate::drivers::{GPU_DEVICE, KEYBOARD_DEVICE, MOUSE_DEVICE};
@@ -17,6 +18,7 @@ mod board;
 mod console;
 mod config;
 mod drivers;
+mod errno;
 mod fs;
 mod lang_items;
 mod mm;
@@ -30,7 +32,7 @@ mod trap;
 use crate::drivers::chardev::CharDevice;
 use crate::drivers::chardev::UART;
 
-core::arch::global_asm!(include_str!("entry.asm"));
+core::arch::global_asm!(include_str!("entry.asm"), MAX_HARTS = const config::MAX_HARTS);
 
 fn clear_bss() {
     extern "C" {
@@ -44,15 +46,14 @@ fn clear_bss() {
 }
 
 use lazy_static::*;
-use sync::UPIntrFreeCell;
+use sync::SpinIntrFreeCell;
 
 lazy_static! {
-    pub static ref DEV_NON_BLOCKING_ACCESS: UPIntrFreeCell<bool> =
-        unsafe { UPIntrFreeCell::new(false) };
+    pub static ref DEV_NON_BLOCKING_ACCESS: SpinIntrFreeCell<bool> = SpinIntrFreeCell::new(false);
 }
 
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize) -> ! {
     clear_bss();
     mm::init();
     UART.init();
@@ -65,11 +66,39 @@ pub fn rust_main() -> ! {
     println!("KERN: init trap");
     trap::init();
     trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
     timer::set_next_trigger();
     board::device_init();
+    board::irq_init_hart(hart_id);
     fs::list_apps();
     task::add_initproc();
     *DEV_NON_BLOCKING_ACCESS.exclusive_access() = true;
+    start_other_harts(hart_id);
     task::run_tasks();
     panic!("Unreachable in rust_main!");
 }
+
+/// Start the other harts at `_start_secondary`, the ones QEMU does not have
+/// simply fail to start.
+fn start_other_harts(boot_hart_id: usize) {
+    extern "C" {
+        fn _start_secondary();
+    }
+    for hart_id in (0..config::MAX_HARTS).filter(|hart_id| *hart_id != boot_hart_id) {
+        if sbi::hart_start(hart_id, _start_secondary as usize, 0) {
+            println!("KERN: start hart {}", hart_id);
+        }
+    }
+}
+
+#[no_mangle]
+pub fn rust_main_secondary(hart_id: usize) -> ! {
+    mm::init_secondary();
+    trap::init();
+    trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
+    timer::set_next_trigger();
+    board::irq_init_hart(hart_id);
+    task::run_tasks();
+    panic!("Unreachable in rust_main_secondary!");
+}
@@ -1,6 +1,7 @@
 use super::{PhysAddr, PhysPageNum};
 use crate::config::MEMORY_END;
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinIntrFreeCell;
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::*;
@@ -18,6 +19,19 @@ impl FrameTracker {
         }
         Self { ppn }
     }
+    /// Number of `FrameTracker`s of this frame, more than one while it is
+    /// shared copy-on-write by processes.
+    pub fn ref_count(&self) -> usize {
+        FRAME_ALLOCATOR.exclusive_access().ref_count(self.ppn)
+    }
+}
+
+impl Clone for FrameTracker {
+    /// Another reference to the same frame, which is freed with the last one.
+    fn clone(&self) -> Self {
+        FRAME_ALLOCATOR.exclusive_access().share(self.ppn);
+        Self { ppn: self.ppn }
+    }
 }
 
 impl Debug for FrameTracker {
@@ -35,13 +49,20 @@ impl Drop for FrameTracker {
 trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
+    /// Drop a reference to the frame, which is freed with the last one.
     fn dealloc(&mut self, ppn: PhysPageNum);
+    /// Add a reference to an allocated frame.
+    fn share(&mut self, ppn: PhysPageNum);
+    fn ref_count(&self, ppn: PhysPageNum) -> usize;
 }
 
 pub struct StackFrameAllocator {
     current: usize,
     end: usize,
     recycled: Vec<usize>,
+    /// Reference counts of the frames with more than one reference, the
+    /// others allocated have exactly one.
+    shared: BTreeMap<usize, usize>,
 }
 
 impl StackFrameAllocator {
@@ -57,6 +78,7 @@ impl FrameAllocator for StackFrameAllocator {
             current: 0,
             end: 0,
             recycled: Vec::new(),
+            shared: BTreeMap::new(),
         }
     }
     fn alloc(&mut self) -> Option<PhysPageNum> {
@@ -71,6 +93,13 @@ impl FrameAllocator for StackFrameAllocator {
     }
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn = ppn.0;
+        if let Some(count) = self.shared.get_mut(&ppn) {
+            *count -= 1;
+            if *count == 1 {
+                self.shared.remove(&ppn);
+            }
+            return;
+        }
         // validity check
         if ppn >= self.current || self.recycled.iter().any(|&v| v == ppn) {
             panic!("Frame ppn={:#x} has not been allocated!", ppn);
@@ -78,13 +107,19 @@ impl FrameAllocator for StackFrameAllocator {
         // recycle
         self.recycled.push(ppn);
     }
+    fn share(&mut self, ppn: PhysPageNum) {
+        *self.shared.entry(ppn.0).or_insert(1) += 1;
+    }
+    fn ref_count(&self, ppn: PhysPageNum) -> usize {
+        self.shared.get(&ppn.0).copied().unwrap_or(1)
+    }
 }
 
 type FrameAllocatorImpl = StackFrameAllocator;
 
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPIntrFreeCell<FrameAllocatorImpl> =
-        unsafe { UPIntrFreeCell::new(FrameAllocatorImpl::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinIntrFreeCell<FrameAllocatorImpl> =
+        SpinIntrFreeCell::new(FrameAllocatorImpl::new());
 }
 
 pub fn init_frame_allocator() {
@@ -2,12 +2,18 @@ use super::{frame_alloc, FrameTracker};
 use super::{PTEFlags, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE};
-use crate::sync::UPIntrFreeCell;
+use crate::config::{
+    MEMORY_END, MMAP_BASE, MMAP_TOP, MMIO, PAGE_SIZE, TRAMPOLINE, USER_HEAP_LIMIT,
+};
+use crate::fs::VfsInode;
+use crate::sbi::remote_sfence_vma;
+use crate::sync::SpinIntrFreeCell;
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::arch::asm;
+use core::mem::{size_of, MaybeUninit};
 use lazy_static::*;
 use riscv::register::satp;
 
@@ -25,8 +31,8 @@ extern "C" {
 }
 
 lazy_static! {
-    pub static ref KERNEL_SPACE: Arc<UPIntrFreeCell<MemorySet>> =
-        Arc::new(unsafe { UPIntrFreeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinIntrFreeCell<MemorySet>> =
+        Arc::new(SpinIntrFreeCell::new(MemorySet::new_kernel()));
 }
 
 pub fn kernel_token() -> usize {
@@ -60,6 +66,15 @@ impl MemorySet {
             None,
         );
     }
+    /// Frames of the area are allocated on the first access.
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) {
+        self.push(MapArea::new_lazy(start_va, end_va, permission), None);
+    }
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
         if let Some((idx, area)) = self
             .areas
@@ -71,6 +86,171 @@ impl MemorySet {
             self.areas.remove(idx);
         }
     }
+    /// Shrink the area starting at `start_va` to end at `new_end`.
+    pub fn shrink_to(&mut self, start_va: VirtAddr, new_end: VirtAddr) -> bool {
+        let start_vpn = start_va.floor();
+        let new_end_vpn = new_end.ceil();
+        match self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() == start_vpn)
+        {
+            Some(area) if new_end_vpn <= area.vpn_range.get_end() => {
+                if new_end_vpn < area.vpn_range.get_end() {
+                    area.split_off(new_end_vpn)
+                        .unmap_pages(&mut self.page_table);
+                    flush_remote_tlb();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Extend the lazy area starting at `start_va` to end at `new_end`.
+    pub fn append_to(&mut self, start_va: VirtAddr, new_end: VirtAddr) -> bool {
+        let start_vpn = start_va.floor();
+        let new_end_vpn = new_end.ceil();
+        let idx = match self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == start_vpn && area.lazy)
+        {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let end_vpn = self.areas[idx].vpn_range.get_end();
+        if new_end_vpn < end_vpn || !self.is_free(end_vpn.into(), new_end) {
+            return false;
+        }
+        // pages of the lazy area are mapped by page faults
+        self.areas[idx].vpn_range = VPNRange::new(start_vpn, new_end_vpn);
+        true
+    }
+    /// Whether `[start_va, end_va)` does not overlap any area.
+    pub fn is_free(&self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        let (start_vpn, end_vpn) = (start_va.floor(), end_va.ceil());
+        !self
+            .areas
+            .iter()
+            .any(|area| area.overlaps(start_vpn, end_vpn))
+    }
+    /// Find the lowest `len` bytes of free space from `MMAP_BASE`.
+    pub fn find_free_area(&self, len: usize) -> Option<VirtAddr> {
+        let pages = VirtAddr::from(len).ceil().0;
+        let mut start_vpn = VirtAddr::from(MMAP_BASE).floor();
+        loop {
+            let end_vpn = VirtPageNum(start_vpn.0 + pages);
+            if end_vpn > VirtAddr::from(MMAP_TOP).floor() {
+                return None;
+            }
+            // skip over the areas in the way
+            match self
+                .areas
+                .iter()
+                .filter(|area| area.overlaps(start_vpn, end_vpn))
+                .map(|area| area.vpn_range.get_end())
+                .max()
+            {
+                Some(next_vpn) => start_vpn = next_vpn,
+                None => return Some(start_vpn.into()),
+            }
+        }
+    }
+    /// Remove the user pages in `[start_va, end_va)`, return the removed areas
+    /// whose frames are not freed yet, so that the caller can write them back,
+    /// or `None` if the range overlaps any kernel-only or device area.
+    pub fn munmap(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> Option<Vec<MapArea>> {
+        let (start_vpn, end_vpn) = (start_va.floor(), end_va.ceil());
+        if self
+            .areas
+            .iter()
+            .any(|area| area.overlaps(start_vpn, end_vpn) && !area.is_user_framed())
+        {
+            return None;
+        }
+        self.split_areas_at(start_vpn, end_vpn);
+        let mut removed = Vec::new();
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            if self.areas[idx].overlaps(start_vpn, end_vpn) {
+                let area = self.areas.remove(idx);
+                area.unmap_pages(&mut self.page_table);
+                removed.push(area);
+            } else {
+                idx += 1;
+            }
+        }
+        flush_remote_tlb();
+        Some(removed)
+    }
+    /// Change the permission of user pages in `[start_va, end_va)`, return false
+    /// if any page is not mapped or a read-only shared file would become writable.
+    pub fn mprotect(&mut self, start_va: VirtAddr, end_va: VirtAddr, perm: MapPermission) -> bool {
+        let (start_vpn, end_vpn) = (start_va.floor(), end_va.ceil());
+        let mut mapped_pages = 0;
+        for area in self
+            .areas
+            .iter()
+            .filter(|area| area.overlaps(start_vpn, end_vpn))
+        {
+            if !area.is_user_framed() {
+                return false;
+            }
+            if area.shared.is_some()
+                && perm.contains(MapPermission::W)
+                && area.file.as_ref().map_or(false, |file| !file.writable)
+            {
+                return false;
+            }
+            mapped_pages += area.vpn_range.get_end().0.min(end_vpn.0)
+                - area.vpn_range.get_start().0.max(start_vpn.0);
+        }
+        if mapped_pages != end_vpn.0 - start_vpn.0 {
+            return false;
+        }
+        self.split_areas_at(start_vpn, end_vpn);
+        for area in self
+            .areas
+            .iter_mut()
+            .filter(|area| area.overlaps(start_vpn, end_vpn))
+        {
+            area.map_perm = perm | MapPermission::U;
+            area.remap_pages(&mut self.page_table);
+        }
+        flush_remote_tlb();
+        true
+    }
+    /// Split the areas crossing `start_vpn` or `end_vpn`, so that every area
+    /// is either inside or outside of the range.
+    fn split_areas_at(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        for vpn in [start_vpn, end_vpn] {
+            // areas never overlap, so at most one of them crosses `vpn`
+            if let Some(area) = self
+                .areas
+                .iter_mut()
+                .find(|area| area.vpn_range.get_start() < vpn && vpn < area.vpn_range.get_end())
+            {
+                let tail = area.split_off(vpn);
+                self.areas.push(tail);
+            }
+        }
+    }
+    /// Remove the `MAP_SHARED` file mappings before the address space goes
+    /// away, the caller writes them back after releasing the process.
+    pub fn remove_shared_file_areas(&mut self) -> Vec<MapArea> {
+        let mut removed = Vec::new();
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            if self.areas[idx].shared.is_some() && self.areas[idx].file.is_some() {
+                let area = self.areas.remove(idx);
+                area.unmap_pages(&mut self.page_table);
+                removed.push(area);
+            } else {
+                idx += 1;
+            }
+        }
+        removed
+    }
     /// Add a new MapArea into this MemorySet.
     /// Assuming that there are no conflicts in the virtual address
     /// space.
@@ -166,9 +346,9 @@ impl MemorySet {
         }
         memory_set
     }
-    /// Include sections in elf and trampoline,
-    /// also returns user_sp_base and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// Include sections in elf, an empty heap and trampoline,
+    /// also returns user_sp_base, heap bottom and entry point.
+    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize, usize) {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
@@ -204,33 +384,197 @@ impl MemorySet {
             }
         }
         let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_base: usize = max_end_va.into();
-        user_stack_base += PAGE_SIZE;
+        let heap_bottom: usize = max_end_va.into();
+        memory_set.insert_lazy_area(
+            heap_bottom.into(),
+            heap_bottom.into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        // leave a guard page above the largest heap
+        let user_stack_base = heap_bottom + USER_HEAP_LIMIT + PAGE_SIZE;
         (
             memory_set,
             user_stack_base,
+            heap_bottom,
             elf.header.pt2.entry_point() as usize,
         )
     }
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    /// Frames of user areas are shared read-only for copy-on-write and get
+    /// duplicated by the first store, while the trap contexts are copied at once
+    /// since the kernel writes them through their physical pages.
+    /// `MAP_SHARED` areas keep sharing their frames with full permission.
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
-        // copy data sections/trap_context/user_stack
         for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
-            // copy data from another space
-            for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
-                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
-                dst_ppn
-                    .get_bytes_array()
-                    .copy_from_slice(src_ppn.get_bytes_array());
+            let mut new_area = MapArea::from_another(area);
+            if area.is_cow() {
+                // share data sections/user_stack
+                let pte_flags = area.pte_flags() - PTEFlags::W;
+                for (vpn, frame) in area.data_frames.iter() {
+                    user_space.page_table.remap(*vpn, frame.ppn, pte_flags);
+                    memory_set.page_table.map(*vpn, frame.ppn, pte_flags);
+                    new_area.data_frames.insert(*vpn, frame.clone());
+                }
+                memory_set.areas.push(new_area);
+            } else if area.shared.is_some() {
+                for (vpn, frame) in area.data_frames.iter() {
+                    memory_set.page_table.map(*vpn, frame.ppn, area.pte_flags());
+                    new_area.data_frames.insert(*vpn, frame.clone());
+                }
+                memory_set.areas.push(new_area);
+            } else {
+                // copy trap_context
+                memory_set.push(new_area, None);
+                for vpn in area.vpn_range {
+                    let src_ppn = user_space.translate(vpn).unwrap().ppn();
+                    let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
+                    dst_ppn
+                        .get_bytes_array()
+                        .copy_from_slice(src_ppn.get_bytes_array());
+                }
             }
         }
+        // other threads of the parent must not write the shared frames
+        flush_remote_tlb();
         memory_set
     }
+    /// Resolve a page fault at `vpn` by allocating the frame of a lazy area or
+    /// duplicating a copy-on-write page, return false if it is an access
+    /// violation, out of memory or a page of a file which is not read yet.
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNum, write: bool) -> bool {
+        match self.areas.iter_mut().find(|area| area.contains(vpn)) {
+            Some(area) => area.handle_page_fault(&mut self.page_table, vpn, write),
+            None => false,
+        }
+    }
+    /// The kernel accesses user memory through physical pages without faulting,
+    /// so lazy pages in `[start, start + len)` are allocated and copy-on-write
+    /// pages are duplicated for a write here first. Pages of file mappings have
+    /// to be read by the caller before, see `first_unread_file_page`.
+    /// Return false at the first page which can not be resolved.
+    fn prepare_user_buffer(&mut self, start: usize, len: usize, write: bool) -> bool {
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            let resolved = match self.translate(vpn) {
+                Some(pte) if pte.is_valid() => !write || pte.writable(),
+                _ => false,
+            };
+            if !resolved && !self.handle_page_fault(vpn, write) {
+                return false;
+            }
+        }
+        true
+    }
+    /// The first page of a file mapping in `[start_va, end_va)` which is neither
+    /// mapped nor read by another sharer yet.
+    pub fn first_unread_file_page(&self, start_va: VirtAddr, end_va: VirtAddr) -> Option<FilePage> {
+        let (start_vpn, end_vpn) = (start_va.floor(), end_va.ceil());
+        self.areas
+            .iter()
+            .filter(|area| area.file.is_some() && area.overlaps(start_vpn, end_vpn))
+            .filter_map(|area| {
+                let start = area.vpn_range.get_start().max(start_vpn);
+                let end = area.vpn_range.get_end().min(end_vpn);
+                VPNRange::new(start, end)
+                    .into_iter()
+                    .find(|vpn| !area.has_page(*vpn))
+                    .map(|vpn| area.file_page(vpn))
+            })
+            .min_by_key(|page| page.vpn)
+    }
+    /// Map `frame` read for `page`, unless the page has been mapped or
+    /// unmapped while it was read.
+    pub fn fill_file_page(&mut self, page: &FilePage, frame: FrameTracker) {
+        if let Some(area) = self.areas.iter_mut().find(|area| area.contains(page.vpn)) {
+            if !area.data_frames.contains_key(&page.vpn) && area.maps(page) {
+                area.map_frame(&mut self.page_table, page.vpn, frame);
+            }
+        }
+    }
+    /// Translate the user buffer `[start, start + len)` into slices of its
+    /// frames, or `None` if any page of it is not a user page which is
+    /// readable, and writable for a `write`.
+    pub fn translated_user_buffer(
+        &mut self,
+        start: usize,
+        len: usize,
+        write: bool,
+    ) -> Option<Vec<&'static mut [u8]>> {
+        if len == 0 {
+            return Some(Vec::new());
+        }
+        // higher addresses alias the lower half after the truncation to sv39
+        let end = start.checked_add(len).filter(|end| *end <= MMAP_TOP)?;
+        if !self.prepare_user_buffer(start, len, write) {
+            return None;
+        }
+        let mut buffers = Vec::new();
+        let mut va = start;
+        while va < end {
+            let pte = self.translate(VirtAddr::from(va).floor()).filter(|pte| {
+                pte.is_valid() && pte.is_user() && pte.readable() && (!write || pte.writable())
+            })?;
+            let offset = VirtAddr::from(va).page_offset();
+            let page_len = (PAGE_SIZE - offset).min(end - va);
+            buffers.push(&mut pte.ppn().get_bytes_array()[offset..offset + page_len]);
+            va += page_len;
+        }
+        Some(buffers)
+    }
+    /// Fill `dst` with the user memory at `src`.
+    pub fn copy_from_user(&mut self, src: usize, dst: &mut [u8]) -> Option<()> {
+        let mut copied = 0;
+        for buffer in self.translated_user_buffer(src, dst.len(), false)? {
+            dst[copied..copied + buffer.len()].copy_from_slice(buffer);
+            copied += buffer.len();
+        }
+        Some(())
+    }
+    /// Copy `src` into the user memory at `dst`.
+    pub fn copy_to_user(&mut self, dst: usize, src: &[u8]) -> Option<()> {
+        let mut copied = 0;
+        for buffer in self.translated_user_buffer(dst, src.len(), true)? {
+            let len = buffer.len();
+            buffer.copy_from_slice(&src[copied..copied + len]);
+            copied += len;
+        }
+        Some(())
+    }
+    /// Read a `T` from user space, which needs not be aligned.
+    pub fn read_user<T: Copy>(&mut self, ptr: *const T) -> Option<T> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>())
+        };
+        self.copy_from_user(ptr as usize, bytes)?;
+        Some(unsafe { value.assume_init() })
+    }
+    /// Write a `T` to user space, which needs not be aligned.
+    pub fn write_user<T: Copy>(&mut self, ptr: *mut T, value: T) -> Option<()> {
+        let bytes =
+            unsafe { core::slice::from_raw_parts(&value as *const T as *const u8, size_of::<T>()) };
+        self.copy_to_user(ptr as usize, bytes)
+    }
+    /// Read a string ending with `\0` from user space without the `\0`.
+    pub fn read_user_str(&mut self, ptr: *const u8) -> Option<String> {
+        let mut bytes = Vec::new();
+        let mut va = ptr as usize;
+        loop {
+            // a page at a time, since the string may end before the next one
+            let page_len = PAGE_SIZE - VirtAddr::from(va).page_offset();
+            let page = self.translated_user_buffer(va, page_len, false)?;
+            let page = &page[0];
+            if let Some(len) = page.iter().position(|byte| *byte == 0) {
+                bytes.extend_from_slice(&page[..len]);
+                return Some(String::from_utf8_lossy(&bytes).into_owned());
+            }
+            bytes.extend_from_slice(page);
+            va += page_len;
+        }
+    }
     pub fn activate(&self) {
         let satp = self.page_table.token();
         unsafe {
@@ -247,11 +591,101 @@ impl MemorySet {
     }
 }
 
+/// Threads of a process may run on other harts with the old entries of a
+/// page table in their TLBs, flush them after entries are removed or changed.
+fn flush_remote_tlb() {
+    remote_sfence_vma(0, usize::MAX);
+}
+
+/// The file backing an mmap area.
+#[derive(Clone)]
+pub struct MapFile {
+    inode: Arc<dyn VfsInode>,
+    /// file offset of the first page
+    offset: usize,
+    /// whether the file was opened writable
+    writable: bool,
+}
+
+impl MapFile {
+    pub fn new(inode: Arc<dyn VfsInode>, offset: usize, writable: bool) -> Self {
+        Self {
+            inode,
+            offset,
+            writable,
+        }
+    }
+}
+
+/// A page of a file mapping to be read without the process borrowed, since
+/// the disk may block.
+pub struct FilePage {
+    vpn: VirtPageNum,
+    inode: Arc<dyn VfsInode>,
+    offset: usize,
+}
+
+impl FilePage {
+    /// Read the page into a new frame, bytes past the end of file are zero.
+    /// `None` if out of memory.
+    pub fn read(&self) -> Option<FrameTracker> {
+        let frame = frame_alloc()?;
+        self.inode.read_at(self.offset, frame.ppn.get_bytes_array());
+        Some(frame)
+    }
+    pub fn vpn(&self) -> VirtPageNum {
+        self.vpn
+    }
+}
+
+/// Frames of a `MAP_SHARED` area by page index, shared with the areas forked
+/// or split from it, so that a page faulted in by one of them is seen by all.
+#[derive(Clone)]
+struct SharedPages {
+    frames: Arc<SpinIntrFreeCell<BTreeMap<usize, FrameTracker>>>,
+    /// index of the first page of the area
+    first: usize,
+}
+
+impl SharedPages {
+    fn new() -> Self {
+        Self {
+            frames: Arc::new(SpinIntrFreeCell::new(BTreeMap::new())),
+            first: 0,
+        }
+    }
+    fn get(&self, index: usize) -> Option<FrameTracker> {
+        self.frames
+            .exclusive_access()
+            .get(&(self.first + index))
+            .cloned()
+    }
+    fn contains(&self, index: usize) -> bool {
+        self.frames
+            .exclusive_access()
+            .contains_key(&(self.first + index))
+    }
+    /// Share `frame` as the page `index` unless another sharer has put one
+    /// there before, return the frame of the page.
+    fn insert(&self, index: usize, frame: FrameTracker) -> FrameTracker {
+        self.frames
+            .exclusive_access()
+            .entry(self.first + index)
+            .or_insert(frame)
+            .clone()
+    }
+}
+
 pub struct MapArea {
     vpn_range: VPNRange,
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// Frames are allocated by page faults instead of `map`.
+    lazy: bool,
+    /// Frames stay shared after fork instead of copy-on-write.
+    shared: Option<SharedPages>,
+    file: Option<MapFile>,
 }
 
 impl MapArea {
@@ -268,14 +702,41 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            lazy: false,
+            shared: None,
+            file: None,
         }
     }
+    pub fn new_lazy(start_va: VirtAddr, end_va: VirtAddr, map_perm: MapPermission) -> Self {
+        let mut map_area = Self::new(start_va, end_va, MapType::Framed, map_perm);
+        map_area.lazy = true;
+        map_area
+    }
+    /// An mmap area, whose pages are allocated or read from the file by page
+    /// faults.
+    pub fn new_mmap(
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        map_perm: MapPermission,
+        shared: bool,
+        file: Option<MapFile>,
+    ) -> Self {
+        let mut map_area = Self::new_lazy(start_va, end_va, map_perm);
+        if shared {
+            map_area.shared = Some(SharedPages::new());
+        }
+        map_area.file = file;
+        map_area
+    }
     pub fn from_another(another: &MapArea) -> Self {
         Self {
             vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            lazy: another.lazy,
+            shared: another.shared.clone(),
+            file: another.file.clone(),
         }
     }
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
@@ -295,8 +756,7 @@ impl MapArea {
                 ppn = PhysPageNum((vpn.0 as isize + pn_offset) as usize);
             }
         }
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        page_table.map(vpn, ppn, self.pte_flags());
     }
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         if self.map_type == MapType::Framed {
@@ -304,13 +764,197 @@ impl MapArea {
         }
         page_table.unmap(vpn);
     }
+    /// Map a lazy page which is not mapped yet, a page of a file has to be
+    /// read by `fill_file_page` unless another sharer has it already.
+    fn map_lazy_page(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let index = self.page_index(vpn);
+        let frame = match self.shared.as_ref().and_then(|shared| shared.get(index)) {
+            Some(frame) => frame,
+            None if self.file.is_some() => return false,
+            None => match frame_alloc() {
+                Some(frame) => frame,
+                None => return false,
+            },
+        };
+        self.map_frame(page_table, vpn, frame);
+        true
+    }
+    /// Map `frame` at `vpn` which is not mapped yet, or the frame another
+    /// sharer has put there before.
+    fn map_frame(&mut self, page_table: &mut PageTable, vpn: VirtPageNum, frame: FrameTracker) {
+        let frame = match &self.shared {
+            Some(shared) => shared.insert(self.page_index(vpn), frame),
+            None => frame,
+        };
+        page_table.map(vpn, frame.ppn, self.pte_flags());
+        self.data_frames.insert(vpn, frame);
+    }
+    /// Whether `vpn` is mapped or, for a shared area, held by another sharer.
+    fn has_page(&self, vpn: VirtPageNum) -> bool {
+        self.data_frames.contains_key(&vpn)
+            || self
+                .shared
+                .as_ref()
+                .map_or(false, |shared| shared.contains(self.page_index(vpn)))
+    }
+    fn file_page(&self, vpn: VirtPageNum) -> FilePage {
+        let file = self.file.as_ref().unwrap();
+        FilePage {
+            vpn,
+            inode: file.inode.clone(),
+            offset: file.offset + self.page_index(vpn) * PAGE_SIZE,
+        }
+    }
+    /// Whether `page` is still the page of the file at its address.
+    fn maps(&self, page: &FilePage) -> bool {
+        self.file.as_ref().map_or(false, |file| {
+            core::ptr::eq(
+                Arc::as_ptr(&file.inode) as *const u8,
+                Arc::as_ptr(&page.inode) as *const u8,
+            ) && file.offset + self.page_index(page.vpn) * PAGE_SIZE == page.offset
+        })
+    }
+    fn page_index(&self, vpn: VirtPageNum) -> usize {
+        vpn.0 - self.vpn_range.get_start().0
+    }
+    fn pte_flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.map_perm.bits).unwrap()
+    }
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+    fn overlaps(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        // an empty heap overlaps nothing
+        let (start, end) = (self.vpn_range.get_start(), self.vpn_range.get_end());
+        start < end && start < end_vpn && start_vpn < end
+    }
+    fn is_user_framed(&self) -> bool {
+        self.map_type == MapType::Framed && self.map_perm.contains(MapPermission::U)
+    }
+    /// Whether frames of this area are shared copy-on-write by fork.
+    fn is_cow(&self) -> bool {
+        self.is_user_framed() && self.shared.is_none()
+    }
+    /// Split the area at `vpn`, `self` keeps the pages below it.
+    fn split_off(&mut self, vpn: VirtPageNum) -> MapArea {
+        let start_vpn = self.vpn_range.get_start();
+        let tail = MapArea {
+            vpn_range: VPNRange::new(vpn, self.vpn_range.get_end()),
+            data_frames: self.data_frames.split_off(&vpn),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            lazy: self.lazy,
+            shared: self.shared.as_ref().map(|shared| SharedPages {
+                frames: shared.frames.clone(),
+                first: shared.first + (vpn.0 - start_vpn.0),
+            }),
+            file: self.file.as_ref().map(|file| MapFile {
+                offset: file.offset + (vpn.0 - start_vpn.0) * PAGE_SIZE,
+                ..file.clone()
+            }),
+        };
+        self.vpn_range = VPNRange::new(start_vpn, vpn);
+        tail
+    }
+    /// Unmap the pages of a framed area but keep its frames alive.
+    fn unmap_pages(&self, page_table: &mut PageTable) {
+        for vpn in self.data_frames.keys() {
+            page_table.unmap(*vpn);
+        }
+    }
+    /// Apply a new `map_perm` to the mapped pages of a framed area,
+    /// copy-on-write pages stay read-only.
+    fn remap_pages(&self, page_table: &mut PageTable) {
+        for (vpn, frame) in self.data_frames.iter() {
+            let mut pte_flags = self.pte_flags();
+            if self.is_cow() && frame.ref_count() > 1 {
+                pte_flags -= PTEFlags::W;
+            }
+            page_table.remap(*vpn, frame.ppn, pte_flags);
+        }
+    }
+    /// Write the pages of a `MAP_SHARED` file mapping back without extending
+    /// the file, which may block on the disk.
+    pub fn write_back(&self) {
+        let file = match &self.file {
+            Some(file) if self.shared.is_some() && file.writable => file,
+            _ => return,
+        };
+        let size = file.inode.size();
+        let start_vpn = self.vpn_range.get_start();
+        for (vpn, frame) in self.data_frames.iter() {
+            let offset = file.offset + (vpn.0 - start_vpn.0) * PAGE_SIZE;
+            if offset >= size {
+                break;
+            }
+            let len = (size - offset).min(PAGE_SIZE);
+            file.inode
+                .write_at(offset, &frame.ppn.get_bytes_array()[..len]);
+        }
+    }
+    fn handle_page_fault(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        write: bool,
+    ) -> bool {
+        if self.lazy && !self.data_frames.contains_key(&vpn) {
+            if write && !self.map_perm.contains(MapPermission::W) {
+                return false;
+            }
+            self.map_lazy_page(page_table, vpn)
+        } else if write {
+            self.copy_on_write(page_table, vpn)
+        } else {
+            false
+        }
+    }
+    /// Give `vpn` a private frame and restore the W flag.
+    fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if !self.is_cow() || !self.map_perm.contains(MapPermission::W) {
+            return false;
+        }
+        if page_table.translate(vpn).map_or(true, |pte| pte.writable()) {
+            return false;
+        }
+        let frame = match self.data_frames.get(&vpn) {
+            Some(frame) => frame,
+            None => return false,
+        };
+        // only a fork of a process holding the frame adds references, so a
+        // frame held by this process alone stays so while its PCB is locked
+        if frame.ref_count() == 1 {
+            // the other sharers have copied it already
+            page_table.remap(vpn, frame.ppn, self.pte_flags());
+        } else {
+            let new_frame = match frame_alloc() {
+                Some(frame) => frame,
+                None => return false,
+            };
+            new_frame
+                .ppn
+                .get_bytes_array()
+                .copy_from_slice(frame.ppn.get_bytes_array());
+            page_table.remap(vpn, new_frame.ppn, self.pte_flags());
+            self.data_frames.insert(vpn, new_frame);
+            flush_remote_tlb();
+        }
+        true
+    }
     pub fn map(&mut self, page_table: &mut PageTable) {
+        if self.lazy {
+            return;
+        }
         for vpn in self.vpn_range {
             self.map_one(page_table, vpn);
         }
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
+            // pages of a lazy area that are never touched are not mapped
+            if self.lazy && !self.data_frames.contains_key(&vpn) {
+                continue;
+            }
             self.unmap_one(page_table, vpn);
         }
     }
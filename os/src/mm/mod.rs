@@ -8,15 +8,19 @@ pub use address::VPNRange;
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
 pub use frame_allocator::{frame_alloc, frame_dealloc, FrameTracker};
 pub use memory_set::remap_test;
-pub use memory_set::{kernel_token, MapArea, MapPermission, MapType, MemorySet, KERNEL_SPACE};
-use page_table::PTEFlags;
-pub use page_table::{
-    translated_byte_buffer, translated_ref, translated_refmut, translated_str, PageTable,
-    PageTableEntry, UserBuffer, UserBufferIterator,
+pub use memory_set::{
+    kernel_token, FilePage, MapArea, MapFile, MapPermission, MapType, MemorySet, KERNEL_SPACE,
 };
+use page_table::PTEFlags;
+pub use page_table::{PageTable, PageTableEntry, UserBuffer, UserBufferIterator};
 
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
     KERNEL_SPACE.exclusive_access().activate();
 }
+
+/// Other harts only switch to the kernel space built by the boot hart.
+pub fn init_secondary() {
+    KERNEL_SPACE.exclusive_access().activate();
+}
@@ -12,6 +12,13 @@ const SBI_REMOTE_SFENCE_VMA: usize = 6;
 const SBI_REMOTE_SFENCE_VMA_ASID: usize = 7;
 const SBI_SHUTDOWN: usize = 8;
 
+const SBI_EXT_IPI: usize = 0x735049;
+const SBI_EXT_RFENCE: usize = 0x52464E43;
+const SBI_EXT_HSM: usize = 0x48534D;
+
+/// `hart_mask_base` selecting all harts
+const ALL_HARTS: usize = usize::MAX;
+
 #[inline(always)]
 fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     let mut ret;
@@ -28,6 +35,24 @@ fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
+/// Call function `fid` of extension `eid` of SBI v0.2, return the error code.
+#[inline(always)]
+fn sbi_call_ext(eid: usize, fid: usize, args: [usize; 4]) -> isize {
+    let mut error;
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("x10") args[0] => error,
+            inlateout("x11") args[1] => _,
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    error
+}
+
 pub fn set_timer(timer: usize) {
     sbi_call(SBI_SET_TIMER, timer, 0, 0);
 }
@@ -40,6 +65,23 @@ pub fn console_getchar() -> usize {
     sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
 }
 
+/// Start `hart_id` at the physical address `start_addr` with `a0 = hart_id`
+/// and `a1 = opaque`, return false if it does not exist or is running.
+pub fn hart_start(hart_id: usize, start_addr: usize, opaque: usize) -> bool {
+    sbi_call_ext(SBI_EXT_HSM, 0, [hart_id, start_addr, opaque, 0]) == 0
+}
+
+/// Raise a supervisor software interrupt on the harts in `hart_mask`.
+pub fn send_ipi(hart_mask: usize) {
+    sbi_call_ext(SBI_EXT_IPI, 0, [hart_mask, 0, 0, 0]);
+}
+
+/// Flush the TLB entries of `[start_addr, start_addr + size)` on all harts,
+/// a `size` of `usize::MAX` flushes everything.
+pub fn remote_sfence_vma(start_addr: usize, size: usize) {
+    sbi_call_ext(SBI_EXT_RFENCE, 1, [0, ALL_HARTS, start_addr, size]);
+}
+
 use crate::board::QEMUExit;
 pub fn shutdown(exit_code: usize) -> ! {
     crate::board::QEMU_EXIT_HANDLE.exit_failure()
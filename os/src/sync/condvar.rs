@@ -1,4 +1,4 @@
-use crate::sync::{Mutex, UPIntrFreeCell};
+use crate::sync::{Mutex, SpinIntrFreeCell};
 use crate::task::{
     add_task, block_current_and_run_next, block_current_task, current_task, TaskContext,
     TaskControlBlock,
@@ -6,7 +6,7 @@ use crate::task::{
 use alloc::{collections::VecDeque, sync::Arc};
 
 pub struct Condvar {
-    pub inner: UPIntrFreeCell<CondvarInner>,
+    pub inner: SpinIntrFreeCell<CondvarInner>,
 }
 
 pub struct CondvarInner {
@@ -16,11 +16,9 @@ pub struct CondvarInner {
 impl Condvar {
     pub fn new() -> Self {
         Self {
-            inner: unsafe {
-                UPIntrFreeCell::new(CondvarInner {
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinIntrFreeCell::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
 
@@ -31,6 +29,13 @@ impl Condvar {
         }
     }
 
+    pub fn signal_all(&self) {
+        let mut inner = self.inner.exclusive_access();
+        while let Some(task) = inner.wait_queue.pop_front() {
+            add_task(task);
+        }
+    }
+
     /*
     pub fn wait(&self) {
         let mut inner = self.inner.exclusive_access();
@@ -48,10 +53,11 @@ impl Condvar {
     }
 
     pub fn wait_with_mutex(&self, mutex: Arc<dyn Mutex>) {
-        mutex.unlock();
+        // queue up first, a signal from another hart may follow the unlock at once
         self.inner.exclusive_session(|inner| {
             inner.wait_queue.push_back(current_task().unwrap());
         });
+        mutex.unlock();
         block_current_and_run_next();
         mutex.lock();
     }
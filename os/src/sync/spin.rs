@@ -0,0 +1,131 @@
+use crate::config::MAX_HARTS;
+use crate::task::hart_id;
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+use riscv::register::sstatus;
+
+pub struct IntrMaskingInfo {
+    nested_level: usize,
+    sie_before_masking: bool,
+}
+
+/// The masking state of every hart, each hart only touches its own entry.
+struct IntrMaskingInfos([UnsafeCell<IntrMaskingInfo>; MAX_HARTS]);
+
+unsafe impl Sync for IntrMaskingInfos {}
+
+lazy_static! {
+    static ref INTR_MASKING_INFO: IntrMaskingInfos = {
+        let infos = core::array::from_fn(|_| UnsafeCell::new(IntrMaskingInfo::new()));
+        IntrMaskingInfos(infos)
+    };
+}
+
+fn intr_masking_info() -> &'static mut IntrMaskingInfo {
+    unsafe { &mut *INTR_MASKING_INFO.0[hart_id()].get() }
+}
+
+impl IntrMaskingInfo {
+    pub fn new() -> Self {
+        Self {
+            nested_level: 0,
+            sie_before_masking: false,
+        }
+    }
+
+    pub fn enter(&mut self) {
+        let sie = sstatus::read().sie();
+        unsafe {
+            sstatus::clear_sie();
+        }
+        if self.nested_level == 0 {
+            self.sie_before_masking = sie;
+        }
+        self.nested_level += 1;
+    }
+
+    pub fn exit(&mut self) {
+        self.nested_level -= 1;
+        if self.nested_level == 0 && self.sie_before_masking {
+            unsafe {
+                sstatus::set_sie();
+            }
+        }
+    }
+}
+
+const NO_HART: usize = usize::MAX;
+
+/// A spinlock which also masks the interrupts of the holding hart, so that an
+/// interrupt handler never spins on a lock held by the code it interrupted.
+pub struct SpinIntrFreeCell<T> {
+    /// the hart holding the lock, or `NO_HART`
+    owner: AtomicUsize,
+    /// inner data
+    inner: UnsafeCell<T>,
+}
+
+unsafe impl<T> Sync for SpinIntrFreeCell<T> {}
+
+pub struct SpinIntrRefMut<'a, T> {
+    cell: &'a SpinIntrFreeCell<T>,
+}
+
+impl<T> SpinIntrFreeCell<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            owner: AtomicUsize::new(NO_HART),
+            inner: UnsafeCell::new(value),
+        }
+    }
+
+    /// Panic if the data has been borrowed by the same hart, which would
+    /// never be released.
+    pub fn exclusive_access(&self) -> SpinIntrRefMut<'_, T> {
+        intr_masking_info().enter();
+        let hart_id = hart_id();
+        while self
+            .owner
+            .compare_exchange_weak(NO_HART, hart_id, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            assert_ne!(
+                self.owner.load(Ordering::Relaxed),
+                hart_id,
+                "already borrowed by this hart"
+            );
+            spin_loop();
+        }
+        SpinIntrRefMut { cell: self }
+    }
+
+    pub fn exclusive_session<F, V>(&self, f: F) -> V
+    where
+        F: FnOnce(&mut T) -> V,
+    {
+        let mut inner = self.exclusive_access();
+        f(inner.deref_mut())
+    }
+}
+
+impl<'a, T> Drop for SpinIntrRefMut<'a, T> {
+    fn drop(&mut self) {
+        self.cell.owner.store(NO_HART, Ordering::Release);
+        intr_masking_info().exit();
+    }
+}
+
+impl<'a, T> Deref for SpinIntrRefMut<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.cell.inner.get() }
+    }
+}
+impl<'a, T> DerefMut for SpinIntrRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.cell.inner.get() }
+    }
+}
@@ -1,99 +1,399 @@
-use crate::fs::{make_pipe, open_file, OpenFlags};
-use crate::mm::{translated_byte_buffer, translated_refmut, translated_str, UserBuffer};
-use crate::task::{current_process, current_user_token};
+use super::{cwd_and_cred, read_user_str};
+use crate::errno::{Errno, SysResult};
+use crate::fs::{
+    create_dir, find_inode, find_link, link_file, make_pipe, mount_fs, open_file, read_link,
+    rename_file, symlink_file, sync_all, umount_fs, unlink_file, File, OpenFlags, Stat, S_ISGID,
+    S_ISUID,
+};
+use crate::mm::UserBuffer;
+use crate::task::current_process;
 use alloc::sync::Arc;
 
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
+/// Take the file `fd` to read into `buf` if `read` is set, or to write `buf`.
+fn file_with_buffer(
+    fd: usize,
+    buf: *const u8,
+    len: usize,
+    read: bool,
+) -> SysResult<(Arc<dyn File + Send + Sync>, UserBuffer)> {
     let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table[fd] {
-        if !file.writable() {
-            return -1;
-        }
-        let file = file.clone();
-        // release current task TCB manually to avoid multi-borrow
-        drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
-    } else {
-        -1
+    let file = process.inner_exclusive_access().get_file(fd)?;
+    if (read && !file.readable()) || (!read && !file.writable()) {
+        return Err(Errno::EBADF);
+    }
+    // the buffer may be in a file mapping, whose pages are read first
+    if !process.load_file_pages(buf as usize, len) {
+        return Err(Errno::ENOMEM);
     }
+    let mut inner = process.inner_exclusive_access();
+    let buffers = inner
+        .memory_set
+        .translated_user_buffer(buf as usize, len, read)
+        .ok_or(Errno::EFAULT)?;
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    Ok((file, UserBuffer::new(buffers)))
+}
+
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> SysResult {
+    let (file, buf) = file_with_buffer(fd, buf, len, false)?;
+    file.write(buf)
+}
+
+pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> SysResult {
+    let (file, buf) = file_with_buffer(fd, buf, len, true)?;
+    Ok(file.read(buf))
+}
+
+/// Write at `offset`, the offset of the file is left unchanged.
+pub fn sys_pwrite(fd: usize, buf: *const u8, len: usize, offset: usize) -> SysResult {
+    let (file, buf) = file_with_buffer(fd, buf, len, false)?;
+    file.write_at(offset, buf)
 }
 
-pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
+/// Read from `offset`, the offset of the file is left unchanged.
+pub fn sys_pread(fd: usize, buf: *const u8, len: usize, offset: usize) -> SysResult {
+    let (file, buf) = file_with_buffer(fd, buf, len, true)?;
+    file.read_at(offset, buf)
+}
+
+/// Return the new offset of the file from its start.
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> SysResult {
+    let file = current_process().inner_exclusive_access().get_file(fd)?;
+    file.seek(offset, whence)
+}
+
+pub fn sys_fstat(fd: usize, stat: *mut Stat) -> SysResult {
     let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
-        if !file.readable() {
-            return -1;
-        }
-        // release current task TCB manually to avoid multi-borrow
-        drop(inner);
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+    let file = process.inner_exclusive_access().get_file(fd)?;
+    // read the inode without holding the PCB
+    let file_stat = file.stat();
+    process
+        .inner_exclusive_access()
+        .memory_set
+        .write_user(stat, file_stat)
+        .ok_or(Errno::EFAULT)?;
+    Ok(0)
+}
+
+/// Like `fstat` for the file at `path`.
+pub fn sys_fstatat(dirfd: isize, path: *const u8, stat: *mut Stat, flags: u32) -> SysResult {
+    if flags & !AT_SYMLINK_NOFOLLOW != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let path = read_user_str(path)?;
+    check_dirfd(dirfd, path.as_str())?;
+    let (cwd, cred) = cwd_and_cred();
+    let inode = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        find_link(&cwd, path.as_str(), &cred)?
     } else {
-        -1
+        find_inode(&cwd, path.as_str(), &cred)?
+    };
+    let file_stat = inode.stat();
+    current_process()
+        .inner_exclusive_access()
+        .memory_set
+        .write_user(stat, file_stat)
+        .ok_or(Errno::EFAULT)?;
+    Ok(0)
+}
+
+/// Write back all dirty blocks, file data is otherwise only cached in memory.
+pub fn sys_sync() -> SysResult {
+    sync_all();
+    Ok(0)
+}
+
+/// The whole block cache is written back, since it does not know the owner
+/// inode of each block.
+pub fn sys_fsync(fd: usize) -> SysResult {
+    let file = current_process().inner_exclusive_access().get_file(fd)?;
+    file.inode().ok_or(Errno::EINVAL)?.sync();
+    Ok(0)
+}
+
+/// Cut the file `fd` to `len` bytes or extend it with a hole reading as zeros,
+/// its offset is left unchanged.
+pub fn sys_ftruncate(fd: usize, len: usize) -> SysResult {
+    let file = current_process().inner_exclusive_access().get_file(fd)?;
+    // directories are never opened for writing
+    if !file.writable() {
+        return Err(Errno::EINVAL);
+    }
+    let inode = file.inode().ok_or(Errno::EINVAL)?;
+    if len > inode.max_file_size() {
+        return Err(Errno::EFBIG);
     }
+    inode.truncate(len);
+    Ok(0)
 }
 
-pub fn sys_open(path: *const u8, flags: u32) -> isize {
+/// `mode` is taken by a created file, whose owner is the current user.
+pub fn sys_open(path: *const u8, flags: u32, mode: u32) -> SysResult {
     let process = current_process();
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
-        let mut inner = process.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
-    } else {
-        -1
-    }
+    let path = read_user_str(path)?;
+    let flags = OpenFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    // do not hold the PCB during disk accesses which may block
+    let (cwd, cred) = cwd_and_cred();
+    let inode = open_file(&cwd, path.as_str(), flags, mode as u16, &cred)?;
+    let mut inner = process.inner_exclusive_access();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(inode);
+    Ok(fd)
 }
 
-pub fn sys_close(fd: usize) -> isize {
+pub fn sys_close(fd: usize) -> SysResult {
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if inner.fd_table[fd].is_none() {
-        return -1;
-    }
+    inner.get_file(fd)?;
     inner.fd_table[fd].take();
-    0
+    Ok(0)
 }
 
-pub fn sys_pipe(pipe: *mut usize) -> isize {
+pub fn sys_pipe(pipe: *mut usize) -> SysResult {
     let process = current_process();
-    let token = current_user_token();
     let mut inner = process.inner_exclusive_access();
     let (pipe_read, pipe_write) = make_pipe();
     let read_fd = inner.alloc_fd();
     inner.fd_table[read_fd] = Some(pipe_read);
     let write_fd = inner.alloc_fd();
     inner.fd_table[write_fd] = Some(pipe_write);
-    *translated_refmut(token, pipe) = read_fd;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
-    0
+    let fds = [read_fd, write_fd];
+    if inner
+        .memory_set
+        .write_user(pipe as *mut [usize; 2], fds)
+        .is_none()
+    {
+        inner.fd_table[read_fd] = None;
+        inner.fd_table[write_fd] = None;
+        return Err(Errno::EFAULT);
+    }
+    Ok(0)
 }
 
-pub fn sys_dup(fd: usize) -> isize {
+pub fn sys_dup(fd: usize) -> SysResult {
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
+    let file = inner.get_file(fd)?;
+    let new_fd = inner.alloc_fd();
+    inner.fd_table[new_fd] = Some(file);
+    Ok(new_fd)
+}
+
+/// Special value of `dirfd`, the path is relative to the working directory.
+const AT_FDCWD: isize = -100;
+/// Flag of `unlinkat`, remove a directory instead of a file.
+const AT_REMOVEDIR: u32 = 0x200;
+/// Flag of `fchownat` and `fstatat`, act on a symbolic link itself.
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+/// Flag of `linkat`, link to the target of a symbolic link.
+const AT_SYMLINK_FOLLOW: u32 = 0x400;
+
+/// Copy the working directory with an end `\0` into `buf`.
+/// Return the copied length, or `ERANGE` if `buf` is too small.
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> SysResult {
+    let (cwd, _) = cwd_and_cred();
+    let mut cwd = cwd.path()?.into_bytes();
+    if cwd.len() + 1 > len {
+        return Err(Errno::ERANGE);
     }
-    if inner.fd_table[fd].is_none() {
-        return -1;
+    cwd.push(0);
+    current_process()
+        .inner_exclusive_access()
+        .memory_set
+        .copy_to_user(buf as usize, &cwd)
+        .ok_or(Errno::EFAULT)?;
+    Ok(cwd.len())
+}
+
+/// Only `AT_FDCWD` is supported as `dirfd`, unless `path` is absolute.
+fn check_dirfd(dirfd: isize, path: &str) -> SysResult<()> {
+    if dirfd != AT_FDCWD && !path.starts_with('/') {
+        return Err(Errno::EBADF);
     }
-    let new_fd = inner.alloc_fd();
-    inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[fd].as_ref().unwrap()));
-    new_fd as isize
+    Ok(())
+}
+
+pub fn sys_mkdirat(dirfd: isize, path: *const u8, mode: u32) -> SysResult {
+    let path = read_user_str(path)?;
+    check_dirfd(dirfd, path.as_str())?;
+    let (cwd, cred) = cwd_and_cred();
+    create_dir(&cwd, path.as_str(), mode as u16, &cred)?;
+    Ok(0)
+}
+
+pub fn sys_chdir(path: *const u8) -> SysResult {
+    let process = current_process();
+    let path = read_user_str(path)?;
+    let (cwd, cred) = cwd_and_cred();
+    let new_cwd = cwd.change(path.as_str(), &cred)?;
+    // the old directory is released out of the PCB
+    let _old_cwd = core::mem::replace(&mut process.inner_exclusive_access().cwd, new_cwd);
+    Ok(0)
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: *const u8, flags: u32) -> SysResult {
+    let path = read_user_str(path)?;
+    check_dirfd(dirfd, path.as_str())?;
+    let (cwd, cred) = cwd_and_cred();
+    unlink_file(&cwd, path.as_str(), flags & AT_REMOVEDIR != 0, &cred)?;
+    Ok(0)
+}
+
+/// A symbolic link at `old_path` is linked itself unless `AT_SYMLINK_FOLLOW`
+/// is set.
+pub fn sys_linkat(
+    old_dirfd: isize,
+    old_path: *const u8,
+    new_dirfd: isize,
+    new_path: *const u8,
+    flags: u32,
+) -> SysResult {
+    if flags & !AT_SYMLINK_FOLLOW != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let old_path = read_user_str(old_path)?;
+    let new_path = read_user_str(new_path)?;
+    check_dirfd(old_dirfd, old_path.as_str())?;
+    check_dirfd(new_dirfd, new_path.as_str())?;
+    let (cwd, cred) = cwd_and_cred();
+    link_file(
+        &cwd,
+        old_path.as_str(),
+        new_path.as_str(),
+        flags & AT_SYMLINK_FOLLOW != 0,
+        &cred,
+    )?;
+    Ok(0)
+}
+
+pub fn sys_symlinkat(target: *const u8, new_dirfd: isize, link_path: *const u8) -> SysResult {
+    let target = read_user_str(target)?;
+    let link_path = read_user_str(link_path)?;
+    check_dirfd(new_dirfd, link_path.as_str())?;
+    let (cwd, cred) = cwd_and_cred();
+    symlink_file(&cwd, target.as_str(), link_path.as_str(), &cred)?;
+    Ok(0)
+}
+
+/// Copy the target of a symbolic link into `buf` without an end `\0`, and
+/// return the copied length. The target is cut to fit `buf`.
+pub fn sys_readlinkat(dirfd: isize, path: *const u8, buf: *mut u8, len: usize) -> SysResult {
+    let process = current_process();
+    let path = read_user_str(path)?;
+    check_dirfd(dirfd, path.as_str())?;
+    if len == 0 {
+        return Err(Errno::EINVAL);
+    }
+    let (cwd, cred) = cwd_and_cred();
+    let target = read_link(&cwd, path.as_str(), &cred)?;
+    let target = &target.as_bytes()[..target.len().min(len)];
+    process
+        .inner_exclusive_access()
+        .memory_set
+        .copy_to_user(buf as usize, target)
+        .ok_or(Errno::EFAULT)?;
+    Ok(target.len())
+}
+
+pub fn sys_renameat(
+    old_dirfd: isize,
+    old_path: *const u8,
+    new_dirfd: isize,
+    new_path: *const u8,
+) -> SysResult {
+    let old_path = read_user_str(old_path)?;
+    let new_path = read_user_str(new_path)?;
+    check_dirfd(old_dirfd, old_path.as_str())?;
+    check_dirfd(new_dirfd, new_path.as_str())?;
+    let (cwd, cred) = cwd_and_cred();
+    rename_file(&cwd, old_path.as_str(), new_path.as_str(), &cred)?;
+    Ok(0)
+}
+
+/// Only the owner of the file or root may change its mode, and only on file
+/// systems keeping modes.
+pub fn sys_fchmodat(dirfd: isize, path: *const u8, mode: u32, flags: u32) -> SysResult {
+    if flags != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let path = read_user_str(path)?;
+    check_dirfd(dirfd, path.as_str())?;
+    let (cwd, cred) = cwd_and_cred();
+    let inode = find_inode(&cwd, path.as_str(), &cred)?;
+    if !cred.owns(inode.as_ref()) {
+        return Err(Errno::EPERM);
+    }
+    if !inode.chmod(mode as u16) {
+        return Err(Errno::EPERM);
+    }
+    Ok(0)
+}
+
+/// Only root may change the user owning a file, its owner may change the
+/// group to its own. An id of `u32::MAX` is left unchanged. A change by
+/// anyone but root clears the setuid and setgid bits of a file.
+pub fn sys_fchownat(dirfd: isize, path: *const u8, uid: u32, gid: u32, flags: u32) -> SysResult {
+    if flags & !AT_SYMLINK_NOFOLLOW != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let path = read_user_str(path)?;
+    check_dirfd(dirfd, path.as_str())?;
+    let (cwd, cred) = cwd_and_cred();
+    let inode = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        find_link(&cwd, path.as_str(), &cred)?
+    } else {
+        find_inode(&cwd, path.as_str(), &cred)?
+    };
+    let (old_uid, old_gid) = inode.owner();
+    let id = |new: u32, old: u16| match new {
+        u32::MAX => Ok(old),
+        new => u16::try_from(new).map_err(|_| Errno::EINVAL),
+    };
+    let (uid, gid) = (id(uid, old_uid)?, id(gid, old_gid)?);
+    if !cred.is_root()
+        && (!cred.owns(inode.as_ref())
+            || uid != old_uid
+            || gid != old_gid && gid as u32 != cred.egid)
+    {
+        return Err(Errno::EPERM);
+    }
+    if !inode.chown(uid, gid) {
+        return Err(Errno::EPERM);
+    }
+    let mode = inode.mode();
+    if !cred.is_root() && !inode.is_dir() && mode & (S_ISUID | S_ISGID) != 0 {
+        inode.chmod(mode & !(S_ISUID | S_ISGID));
+    }
+    Ok(0)
+}
+
+/// Mount a file system of the type `fstype` on the directory `target`. The
+/// known types need no `source` device and no `data`, and `flags` must be 0.
+pub fn sys_mount(
+    _source: *const u8,
+    target: *const u8,
+    fstype: *const u8,
+    flags: u32,
+    _data: *const u8,
+) -> SysResult {
+    if flags != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let target = read_user_str(target)?;
+    let fstype = read_user_str(fstype)?;
+    let (cwd, cred) = cwd_and_cred();
+    mount_fs(&cwd, target.as_str(), fstype.as_str(), &cred)?;
+    Ok(0)
+}
+
+/// Unmount the file system mounted on `target`, no `flags` are supported.
+pub fn sys_umount2(target: *const u8, flags: u32) -> SysResult {
+    if flags != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let target = read_user_str(target)?;
+    let (cwd, cred) = cwd_and_cred();
+    umount_fs(&cwd, target.as_str(), &cred)?;
+    Ok(0)
 }
@@ -0,0 +1,145 @@
+use crate::config::{MMAP_TOP, PAGE_SIZE};
+use crate::errno::{Errno, SysResult};
+use crate::mm::{MapArea, MapFile, MapPermission, VirtAddr};
+use crate::task::current_process;
+
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const PROT_EXEC: usize = 0x4;
+
+const MAP_SHARED: usize = 0x01;
+const MAP_PRIVATE: usize = 0x02;
+const MAP_FIXED: usize = 0x10;
+const MAP_ANONYMOUS: usize = 0x20;
+
+fn prot_to_permission(prot: usize) -> Option<MapPermission> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        return None;
+    }
+    let mut permission = MapPermission::U;
+    if prot & PROT_READ != 0 {
+        permission |= MapPermission::R;
+    }
+    if prot & PROT_WRITE != 0 {
+        permission |= MapPermission::W;
+    }
+    if prot & PROT_EXEC != 0 {
+        permission |= MapPermission::X;
+    }
+    Some(permission)
+}
+
+/// Check that `[addr, addr + len)` is a page-aligned and non-empty user range.
+fn is_user_range(addr: usize, len: usize) -> bool {
+    addr % PAGE_SIZE == 0 && len > 0 && addr < MMAP_TOP && len <= MMAP_TOP - addr
+}
+
+/// Return the start address of the new mapping.
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> SysResult {
+    let process = current_process();
+    let permission = prot_to_permission(prot).ok_or(Errno::EINVAL)?;
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return Err(Errno::EINVAL),
+    };
+    if len == 0 || len > MMAP_TOP || offset % PAGE_SIZE != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let fixed = flags & MAP_FIXED != 0;
+    if fixed && !is_user_range(addr, len) {
+        return Err(Errno::EINVAL);
+    }
+    let mut inner = process.inner_exclusive_access();
+    let file = if flags & MAP_ANONYMOUS != 0 {
+        None
+    } else {
+        let file = inner.get_file(fd)?;
+        let inode = file.inode().ok_or(Errno::ENODEV)?;
+        // a shared writable mapping writes the file back
+        if !file.readable() || (shared && permission.contains(MapPermission::W) && !file.writable())
+        {
+            return Err(Errno::EACCES);
+        }
+        // pages are read by page faults
+        Some(MapFile::new(inode, offset, file.writable()))
+    };
+    let start_va = if fixed {
+        if !inner.memory_set.is_free(addr.into(), (addr + len).into()) {
+            return Err(Errno::EEXIST);
+        }
+        VirtAddr::from(addr)
+    } else {
+        inner.memory_set.find_free_area(len).ok_or(Errno::ENOMEM)?
+    };
+    let end_va = VirtAddr::from(usize::from(start_va) + len);
+    inner.memory_set.push(
+        MapArea::new_mmap(start_va, end_va, permission, shared, file),
+        None,
+    );
+    Ok(start_va.into())
+}
+
+/// Set the program break and return the new one, like Linux an `addr` of 0
+/// or out of range only returns the current break.
+pub fn sys_brk(addr: usize) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if addr != 0 {
+        inner.change_program_brk(addr);
+    }
+    Ok(inner.program_brk)
+}
+
+/// Move the program break by `increment` and return the old one.
+pub fn sys_sbrk(increment: isize) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let old_brk = inner.program_brk;
+    if inner.change_program_brk((old_brk as isize + increment) as usize) {
+        Ok(old_brk)
+    } else {
+        Err(Errno::ENOMEM)
+    }
+}
+
+/// Shared file mappings in the range are written back to their files.
+pub fn sys_munmap(addr: usize, len: usize) -> SysResult {
+    if !is_user_range(addr, len) {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let areas = process
+        .inner_exclusive_access()
+        .memory_set
+        .munmap(addr.into(), (addr + len).into())
+        .ok_or(Errno::EINVAL)?;
+    for area in areas.iter() {
+        area.write_back();
+    }
+    Ok(0)
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> SysResult {
+    let permission = prot_to_permission(prot).ok_or(Errno::EINVAL)?;
+    if !is_user_range(addr, len) {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner
+        .memory_set
+        .mprotect(addr.into(), (addr + len).into(), permission)
+    {
+        Ok(0)
+    } else {
+        Err(Errno::EACCES)
+    }
+}
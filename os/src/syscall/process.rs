@@ -1,12 +1,12 @@
-use crate::fs::{open_file, OpenFlags};
-use crate::mm::{translated_ref, translated_refmut, translated_str};
+use super::{cwd_and_cred, read_user_str};
+use crate::errno::{Errno, SysResult};
+use crate::fs::{find_inode, OSInode, S_ISGID, S_ISUID, X_OK};
 use crate::task::{
-    current_process, current_task, current_user_token, exit_current_and_run_next, pid2process,
-    suspend_current_and_run_next, SignalFlags,
+    current_process, current_task, exit_current_and_run_next, pid2process, schedule,
+    suspend_current_and_run_next, SignalAction, SignalFlags, MAX_PRIORITY, MIN_PRIORITY,
 };
 use crate::timer::get_time_ms;
 use alloc::string::String;
-use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 pub fn sys_exit(exit_code: i32) -> ! {
@@ -14,20 +14,79 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("Unreachable in sys_exit!");
 }
 
-pub fn sys_yield() -> isize {
+pub fn sys_yield() -> SysResult {
     suspend_current_and_run_next();
-    0
+    Ok(0)
 }
 
-pub fn sys_get_time() -> isize {
-    get_time_ms() as isize
+/// Sets the priority of the current thread, a larger one gets more CPU time.
+pub fn sys_set_priority(priority: isize) -> SysResult {
+    if !(MIN_PRIORITY as isize..=MAX_PRIORITY as isize).contains(&priority) {
+        return Err(Errno::EINVAL);
+    }
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().sched.priority = priority as usize;
+    Ok(priority as usize)
+}
+
+pub fn sys_get_time() -> SysResult {
+    Ok(get_time_ms())
+}
+
+pub fn sys_getpid() -> SysResult {
+    Ok(current_task().unwrap().process.upgrade().unwrap().getpid())
+}
+
+pub fn sys_getuid() -> SysResult {
+    Ok(current_process().inner_exclusive_access().cred.uid as usize)
 }
 
-pub fn sys_getpid() -> isize {
-    current_task().unwrap().process.upgrade().unwrap().getpid() as isize
+pub fn sys_geteuid() -> SysResult {
+    Ok(current_process().inner_exclusive_access().cred.euid as usize)
 }
 
-pub fn sys_fork() -> isize {
+pub fn sys_getgid() -> SysResult {
+    Ok(current_process().inner_exclusive_access().cred.gid as usize)
+}
+
+pub fn sys_getegid() -> SysResult {
+    Ok(current_process().inner_exclusive_access().cred.egid as usize)
+}
+
+/// Root sets both the real and the effective user, others may only switch
+/// the effective user between the two.
+pub fn sys_setuid(uid: u32) -> SysResult {
+    if uid > u16::MAX as u32 {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let cred = &mut process.inner_exclusive_access().cred;
+    if cred.is_root() {
+        cred.uid = uid;
+    } else if uid != cred.uid && uid != cred.euid {
+        return Err(Errno::EPERM);
+    }
+    cred.euid = uid;
+    Ok(0)
+}
+
+/// Like `sys_setuid` but for the group.
+pub fn sys_setgid(gid: u32) -> SysResult {
+    if gid > u16::MAX as u32 {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let cred = &mut process.inner_exclusive_access().cred;
+    if cred.is_root() {
+        cred.gid = gid;
+    } else if gid != cred.gid && gid != cred.egid {
+        return Err(Errno::EPERM);
+    }
+    cred.egid = gid;
+    Ok(0)
+}
+
+pub fn sys_fork() -> SysResult {
     let current_process = current_process();
     let new_process = current_process.fork();
     let new_pid = new_process.getpid();
@@ -38,80 +97,195 @@ pub fn sys_fork() -> isize {
     // we do not have to move to next instruction since we have done it before
     // for child process, fork returns 0
     trap_cx.x[10] = 0;
-    new_pid as isize
+    Ok(new_pid)
 }
 
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
+pub fn sys_exec(path: *const u8, mut args: *const usize) -> SysResult {
+    let path = read_user_str(path)?;
     let mut args_vec: Vec<String> = Vec::new();
     loop {
-        let arg_str_ptr = *translated_ref(token, args);
+        let arg_str_ptr = current_process()
+            .inner_exclusive_access()
+            .memory_set
+            .read_user(args)
+            .ok_or(Errno::EFAULT)?;
         if arg_str_ptr == 0 {
             break;
         }
-        args_vec.push(translated_str(token, arg_str_ptr as *const u8));
-        unsafe {
-            args = args.add(1);
-        }
+        args_vec.push(read_user_str(arg_str_ptr as *const u8)?);
+        args = args.wrapping_add(1);
     }
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        let process = current_process();
-        let argc = args_vec.len();
-        process.exec(all_data.as_slice(), args_vec);
-        // return argc because cx.x[10] will be covered with it later
-        argc as isize
-    } else {
-        -1
+    let process = current_process();
+    let (cwd, cred) = cwd_and_cred();
+    let inode = find_inode(&cwd, path.as_str(), &cred)?;
+    if inode.is_dir() {
+        return Err(Errno::EACCES);
+    }
+    cred.check(inode.as_ref(), X_OK)?;
+    let all_data = OSInode::new(true, false, inode.clone()).read_all();
+    let argc = args_vec.len();
+    process.write_back_shared_files();
+    process.exec(all_data.as_slice(), args_vec);
+    // set-user-ID and set-group-ID programs run as the owner of the file
+    let (uid, gid) = inode.owner();
+    let mode = inode.mode();
+    let mut inner = process.inner_exclusive_access();
+    if mode & S_ISUID != 0 {
+        inner.cred.euid = uid as u32;
+    }
+    if mode & S_ISGID != 0 {
+        inner.cred.egid = gid as u32;
     }
+    // return argc because cx.x[10] will be covered with it later
+    Ok(argc)
 }
 
-/// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// Option of `sys_waitpid`, return at once if no child has exited.
+const WNOHANG: u32 = 1;
+
+/// If there is not a child process whose pid is same as given, return `ECHILD`.
+/// Else if there is a child process but it is still running, block until it
+/// exits, or return `EAGAIN` with `WNOHANG` and `EINTR` if a signal arrives
+/// meanwhile.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: u32) -> SysResult {
     let process = current_process();
-    // find a child process
+    loop {
+        // find a child process
+        let mut inner = process.inner_exclusive_access();
+        if !inner
+            .children
+            .iter()
+            .any(|p| pid == -1 || pid as usize == p.getpid())
+        {
+            return Err(Errno::ECHILD);
+            // ---- release current PCB
+        }
+        let pair = inner.children.iter().enumerate().find(|(_, p)| {
+            // ++++ temporarily access child PCB exclusively
+            p.inner_exclusive_access().is_zombie && (pid == -1 || pid as usize == p.getpid())
+            // ++++ release child PCB
+        });
+        if let Some((idx, _)) = pair {
+            let child = &inner.children[idx];
+            let found_pid = child.getpid();
+            // ++++ temporarily access child PCB exclusively
+            let exit_code = child.inner_exclusive_access().exit_code;
+            // ++++ release child PCB
+            // the child stays a zombie if its exit code can not be stored
+            if !exit_code_ptr.is_null()
+                && inner
+                    .memory_set
+                    .write_user(exit_code_ptr, exit_code)
+                    .is_none()
+            {
+                return Err(Errno::EFAULT);
+            }
+            // the child is deallocated once its exit on another hart is done
+            inner.children.remove(idx);
+            return Ok(found_pid);
+        }
+        if options & WNOHANG != 0 {
+            return Err(Errno::EAGAIN);
+        }
+        // return to user mode so that the signals get handled
+        if !(inner.signals - inner.signal_mask).is_empty() {
+            return Err(Errno::EINTR);
+        }
+        // queue up before releasing the PCB, since a child exiting on another
+        // hart takes the PCB before waking us up
+        let task_cx_ptr = process.child_exit.wait_no_sched();
+        // ---- release current PCB before sleeping until a child exits
+        drop(inner);
+        schedule(task_cx_ptr);
+    }
+}
 
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+/// A `signum` of 0 only checks that the signal could be sent. Only root or
+/// a process whose user or effective user is the user of the receiver may
+/// send it.
+pub fn sys_kill(pid: usize, signum: usize) -> SysResult {
+    // take the credentials first, the receiver may be the sender
+    let cred = current_process().inner_exclusive_access().cred;
+    let process = pid2process(pid).ok_or(Errno::ESRCH)?;
     let mut inner = process.inner_exclusive_access();
-    if !inner
-        .children
-        .iter()
-        .any(|p| pid == -1 || pid as usize == p.getpid())
-    {
-        return -1;
-        // ---- release current PCB
-    }
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        // ++++ temporarily access child PCB exclusively
-        p.inner_exclusive_access().is_zombie && (pid == -1 || pid as usize == p.getpid())
-        // ++++ release child PCB
-    });
-    if let Some((idx, _)) = pair {
-        let child = inner.children.remove(idx);
-        // confirm that child will be deallocated after being removed from children list
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
-        // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
-        // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
-    } else {
-        -2
+    if !cred.is_root() && cred.uid != inner.cred.uid && cred.euid != inner.cred.uid {
+        return Err(Errno::EPERM);
+    }
+    if signum == 0 {
+        return Ok(0);
     }
-    // ---- release current PCB automatically
+    let signal = SignalFlags::from_signum(signum).ok_or(Errno::EINVAL)?;
+    // a stopped process continues even if SIGCONT is blocked
+    if signal == SignalFlags::SIGCONT {
+        inner.stopped = false;
+    }
+    inner.signals |= signal;
+    drop(inner);
+    // interrupt sys_waitpid
+    process.child_exit.signal_all();
+    Ok(0)
 }
 
-pub fn sys_kill(pid: usize, signal: u32) -> isize {
-    if let Some(process) = pid2process(pid) {
-        if let Some(flag) = SignalFlags::from_bits(signal) {
-            process.inner_exclusive_access().signals |= flag;
-            0
-        } else {
-            -1
-        }
+/// Either of `action` and `old_action` can be null.
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> SysResult {
+    let signal = SignalFlags::from_signum(signum).ok_or(Errno::EINVAL)?;
+    if !action.is_null() && SignalFlags::unmaskable().contains(signal) {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    // read the new action first, both may point to the same memory
+    let action = if action.is_null() {
+        None
     } else {
-        -1
+        Some(inner.memory_set.read_user(action).ok_or(Errno::EFAULT)?)
+    };
+    if !old_action.is_null() {
+        let old = inner.signal_actions[signum];
+        inner
+            .memory_set
+            .write_user(old_action, old)
+            .ok_or(Errno::EFAULT)?;
+    }
+    if let Some(mut action) = action {
+        action.mask = SignalFlags::from_bits_truncate(action.mask.bits());
+        inner.signal_actions[signum] = action;
     }
+    Ok(0)
+}
+
+/// Return the old mask, SIGKILL and SIGSTOP can not be blocked.
+pub fn sys_sigprocmask(how: usize, set: u32) -> SysResult {
+    let set = SignalFlags::from_bits_truncate(set) - SignalFlags::unmaskable();
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    inner.signal_mask = match how {
+        SIG_BLOCK => old_mask | set,
+        SIG_UNBLOCK => old_mask - set,
+        SIG_SETMASK => set,
+        _ => return Err(Errno::EINVAL),
+    };
+    Ok(old_mask.bits() as usize)
+}
+
+/// Resume the context interrupted by a signal handler.
+pub fn sys_sigreturn() -> SysResult {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let frame = task_inner.signal_frame.take().ok_or(Errno::EINVAL)?;
+    let trap_cx = task_inner.get_trap_cx();
+    *trap_cx = frame.trap_cx;
+    drop(task_inner);
+    current_process().inner_exclusive_access().signal_mask = frame.mask;
+    // a0 of the resumed context is overwritten by the return value
+    Ok(trap_cx.x[10])
 }
@@ -1,17 +1,23 @@
+use crate::errno::{Errno, SysResult};
 use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, Semaphore};
 use crate::task::{block_current_and_run_next, current_process, current_task};
 use crate::timer::{add_timer, get_time_ms};
 use alloc::sync::Arc;
 
-pub fn sys_sleep(ms: usize) -> isize {
+pub fn sys_sleep(ms: usize) -> SysResult {
     let expire_ms = get_time_ms() + ms;
     let task = current_task().unwrap();
     add_timer(expire_ms, task);
     block_current_and_run_next();
-    0
+    Ok(0)
 }
 
-pub fn sys_mutex_create(blocking: bool) -> isize {
+/// The object at `id` of one of the lists of the current process.
+fn get_object<T: ?Sized>(list: &[Option<Arc<T>>], id: usize) -> SysResult<Arc<T>> {
+    list.get(id).cloned().flatten().ok_or(Errno::EINVAL)
+}
+
+pub fn sys_mutex_create(blocking: bool) -> SysResult {
     let process = current_process();
     let mutex: Option<Arc<dyn Mutex>> = if !blocking {
         Some(Arc::new(MutexSpin::new()))
@@ -27,34 +33,34 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
         .map(|(id, _)| id)
     {
         process_inner.mutex_list[id] = mutex;
-        id as isize
+        Ok(id)
     } else {
         process_inner.mutex_list.push(mutex);
-        process_inner.mutex_list.len() as isize - 1
+        Ok(process_inner.mutex_list.len() - 1)
     }
 }
 
-pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+pub fn sys_mutex_lock(mutex_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let mutex = get_object(&process_inner.mutex_list, mutex_id)?;
     drop(process_inner);
     drop(process);
     mutex.lock();
-    0
+    Ok(0)
 }
 
-pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+pub fn sys_mutex_unlock(mutex_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let mutex = get_object(&process_inner.mutex_list, mutex_id)?;
     drop(process_inner);
     drop(process);
     mutex.unlock();
-    0
+    Ok(0)
 }
 
-pub fn sys_semaphore_create(res_count: usize) -> isize {
+pub fn sys_semaphore_create(res_count: usize) -> SysResult {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
     let id = if let Some(id) = process_inner
@@ -72,28 +78,28 @@ pub fn sys_semaphore_create(res_count: usize) -> isize {
             .push(Some(Arc::new(Semaphore::new(res_count))));
         process_inner.semaphore_list.len() - 1
     };
-    id as isize
+    Ok(id)
 }
 
-pub fn sys_semaphore_up(sem_id: usize) -> isize {
+pub fn sys_semaphore_up(sem_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+    let sem = get_object(&process_inner.semaphore_list, sem_id)?;
     drop(process_inner);
     sem.up();
-    0
+    Ok(0)
 }
 
-pub fn sys_semaphore_down(sem_id: usize) -> isize {
+pub fn sys_semaphore_down(sem_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+    let sem = get_object(&process_inner.semaphore_list, sem_id)?;
     drop(process_inner);
     sem.down();
-    0
+    Ok(0)
 }
 
-pub fn sys_condvar_create(_arg: usize) -> isize {
+pub fn sys_condvar_create(_arg: usize) -> SysResult {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
     let id = if let Some(id) = process_inner
@@ -111,24 +117,24 @@ pub fn sys_condvar_create(_arg: usize) -> isize {
             .push(Some(Arc::new(Condvar::new())));
         process_inner.condvar_list.len() - 1
     };
-    id as isize
+    Ok(id)
 }
 
-pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+pub fn sys_condvar_signal(condvar_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
+    let condvar = get_object(&process_inner.condvar_list, condvar_id)?;
     drop(process_inner);
     condvar.signal();
-    0
+    Ok(0)
 }
 
-pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> SysResult {
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let condvar = get_object(&process_inner.condvar_list, condvar_id)?;
+    let mutex = get_object(&process_inner.mutex_list, mutex_id)?;
     drop(process_inner);
     condvar.wait_with_mutex(mutex);
-    0
+    Ok(0)
 }
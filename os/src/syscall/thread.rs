@@ -1,11 +1,12 @@
 use crate::{
+    errno::{Errno, SysResult},
     mm::kernel_token,
     task::{add_task, current_task, TaskControlBlock},
     trap::{trap_handler, TrapContext},
 };
 use alloc::sync::Arc;
 
-pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+pub fn sys_thread_create(entry: usize, arg: usize) -> SysResult {
     let task = current_task().unwrap();
     let process = task.process.upgrade().unwrap();
     // create a new thread
@@ -18,8 +19,6 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
             .ustack_base,
         true,
     ));
-    // add new task to scheduler
-    add_task(Arc::clone(&new_task));
     let new_task_inner = new_task.inner_exclusive_access();
     let new_task_res = new_task_inner.res.as_ref().unwrap();
     let new_task_tid = new_task_res.tid;
@@ -39,47 +38,46 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
         trap_handler as usize,
     );
     (*new_task_trap_cx).x[10] = arg;
-    new_task_tid as isize
+    drop(process_inner);
+    drop(new_task_inner);
+    // add new task to scheduler once other harts can run it
+    add_task(new_task);
+    Ok(new_task_tid)
 }
 
-pub fn sys_gettid() -> isize {
-    current_task()
+pub fn sys_gettid() -> SysResult {
+    Ok(current_task()
         .unwrap()
         .inner_exclusive_access()
         .res
         .as_ref()
         .unwrap()
-        .tid as isize
+        .tid)
 }
 
-/// thread does not exist, return -1
-/// thread has not exited yet, return -2
+/// thread does not exist, return `ESRCH`
+/// thread has not exited yet, return `EAGAIN`
 /// otherwise, return thread's exit code
-pub fn sys_waittid(tid: usize) -> i32 {
+pub fn sys_waittid(tid: usize) -> SysResult {
     let task = current_task().unwrap();
     let process = task.process.upgrade().unwrap();
     let task_inner = task.inner_exclusive_access();
     let mut process_inner = process.inner_exclusive_access();
     // a thread cannot wait for itself
     if task_inner.res.as_ref().unwrap().tid == tid {
-        return -1;
-    }
-    let mut exit_code: Option<i32> = None;
-    let waited_task = process_inner.tasks[tid].as_ref();
-    if let Some(waited_task) = waited_task {
-        if let Some(waited_exit_code) = waited_task.inner_exclusive_access().exit_code {
-            exit_code = Some(waited_exit_code);
-        }
-    } else {
-        // waited thread does not exist
-        return -1;
-    }
-    if let Some(exit_code) = exit_code {
-        // dealloc the exited thread
-        process_inner.tasks[tid] = None;
-        exit_code
-    } else {
-        // waited thread has not exited
-        -2
+        return Err(Errno::EDEADLK);
     }
+    let waited_task = process_inner
+        .tasks
+        .get(tid)
+        .and_then(Option::as_ref)
+        .ok_or(Errno::ESRCH)?;
+    let exit_code = waited_task
+        .inner_exclusive_access()
+        .exit_code
+        .ok_or(Errno::EAGAIN)?;
+    // dealloc the exited thread
+    process_inner.tasks[tid] = None;
+    // negative exit codes are sign-extended
+    Ok(exit_code as usize)
 }
@@ -1,7 +1,8 @@
 use super::ProcessControlBlock;
 use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_STACK_SIZE};
 use crate::mm::{MapPermission, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPIntrFreeCell;
+use crate::sbi::remote_sfence_vma;
+use crate::sync::SpinIntrFreeCell;
 use alloc::{
     sync::{Arc, Weak},
     vec::Vec,
@@ -40,10 +41,10 @@ impl RecycleAllocator {
 }
 
 lazy_static! {
-    static ref PID_ALLOCATOR: UPIntrFreeCell<RecycleAllocator> =
-        unsafe { UPIntrFreeCell::new(RecycleAllocator::new()) };
-    static ref KSTACK_ALLOCATOR: UPIntrFreeCell<RecycleAllocator> =
-        unsafe { UPIntrFreeCell::new(RecycleAllocator::new()) };
+    static ref PID_ALLOCATOR: SpinIntrFreeCell<RecycleAllocator> =
+        SpinIntrFreeCell::new(RecycleAllocator::new());
+    static ref KSTACK_ALLOCATOR: SpinIntrFreeCell<RecycleAllocator> =
+        SpinIntrFreeCell::new(RecycleAllocator::new());
 }
 
 pub const IDLE_PID: usize = 0;
@@ -87,6 +88,9 @@ impl Drop for KernelStack {
         KERNEL_SPACE
             .exclusive_access()
             .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
+        // the stack may have run on other harts, which must not keep the
+        // translations to its freed frames
+        remote_sfence_vma(kernel_stack_bottom, KERNEL_STACK_SIZE);
     }
 }
 
@@ -147,7 +151,7 @@ impl TaskUserRes {
         // alloc user stack
         let ustack_bottom = ustack_bottom_from_tid(self.ustack_base, self.tid);
         let ustack_top = ustack_bottom + USER_STACK_SIZE;
-        process_inner.memory_set.insert_framed_area(
+        process_inner.memory_set.insert_lazy_area(
             ustack_bottom.into(),
             ustack_top.into(),
             MapPermission::R | MapPermission::W | MapPermission::U,
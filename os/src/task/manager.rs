@@ -1,37 +1,40 @@
-use super::{ProcessControlBlock, TaskControlBlock};
-use crate::sync::UPIntrFreeCell;
-use alloc::collections::{BTreeMap, VecDeque};
+use super::scheduler::{new_scheduler, Scheduler};
+use super::{wake_idle_hart, ProcessControlBlock, TaskControlBlock};
+use crate::sync::SpinIntrFreeCell;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use lazy_static::*;
 
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    scheduler: Box<dyn Scheduler>,
 }
 
-/// A simple FIFO scheduler.
+/// Holds the ready threads in the scheduling policy chosen at build time.
 impl TaskManager {
     pub fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            scheduler: new_scheduler(),
         }
     }
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+        self.scheduler.add(task);
     }
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
+        self.scheduler.fetch()
     }
 }
 
 lazy_static! {
-    pub static ref TASK_MANAGER: UPIntrFreeCell<TaskManager> =
-        unsafe { UPIntrFreeCell::new(TaskManager::new()) };
-    pub static ref PID2PCB: UPIntrFreeCell<BTreeMap<usize, Arc<ProcessControlBlock>>> =
-        unsafe { UPIntrFreeCell::new(BTreeMap::new()) };
+    pub static ref TASK_MANAGER: SpinIntrFreeCell<TaskManager> =
+        SpinIntrFreeCell::new(TaskManager::new());
+    pub static ref PID2PCB: SpinIntrFreeCell<BTreeMap<usize, Arc<ProcessControlBlock>>> =
+        SpinIntrFreeCell::new(BTreeMap::new());
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
     TASK_MANAGER.exclusive_access().add(task);
+    wake_idle_hart();
 }
 
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
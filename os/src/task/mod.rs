@@ -3,14 +3,17 @@ mod id;
 mod manager;
 mod process;
 mod processor;
+mod scheduler;
 mod signal;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
 use self::id::TaskUserRes;
-use crate::fs::{open_file, OpenFlags};
+use crate::fs::{open_file, Credentials, Cwd, OpenFlags};
 use alloc::{sync::Arc, vec::Vec};
+use core::hint::spin_loop;
+use core::sync::atomic::Ordering;
 use lazy_static::*;
 use manager::fetch_task;
 use process::ProcessControlBlock;
@@ -21,9 +24,12 @@ pub use id::{kstack_alloc, pid_alloc, KernelStack, PidHandle, IDLE_PID};
 pub use manager::{add_task, pid2process, remove_from_pid2process};
 pub use processor::{
     current_kstack_top, current_process, current_task, current_trap_cx, current_trap_cx_user_va,
-    current_user_token, run_tasks, schedule, take_current_task,
+    current_user_token, hart_id, run_tasks, schedule, take_current_task, wake_idle_hart,
+};
+pub use scheduler::{SchedEntity, MAX_PRIORITY, MIN_PRIORITY};
+pub use signal::{
+    SignalAction, SignalDefault, SignalFlags, SignalFrame, MAX_SIG, SIG_DFL, SIG_IGN,
 };
-pub use signal::SignalFlags;
 pub use task::{TaskControlBlock, TaskStatus};
 
 pub fn suspend_current_and_run_next() {
@@ -44,6 +50,16 @@ pub fn suspend_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
+/// The current thread has used up its time slice.
+pub fn preempt_current_and_run_next() {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .sched
+        .preempted = true;
+    suspend_current_and_run_next();
+}
+
 /// This function must be followed by a schedule
 pub fn block_current_task() -> *mut TaskContext {
     let task = take_current_task().unwrap();
@@ -59,26 +75,40 @@ pub fn block_current_and_run_next() {
 use crate::board::QEMUExit;
 
 pub fn exit_current_and_run_next(exit_code: i32) {
+    // write back file mappings while the main thread can still block on the disk
+    let task = current_task().unwrap();
+    // the user res is gone if the main thread has exited already
+    let tid = task
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .map(|res| res.tid);
+    if tid == Some(0) {
+        task.process.upgrade().unwrap().write_back_shared_files();
+    }
+    drop(task);
     let task = take_current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
     let process = task.process.upgrade().unwrap();
-    let tid = task_inner.res.as_ref().unwrap().tid;
     // record exit code
     task_inner.exit_code = Some(exit_code);
-    task_inner.res = None;
+    // the user res is freed through the PCB after releasing the TCB
+    let res = task_inner.res.take();
     // here we do not remove the thread since we are still using the kstack
     // it will be deallocated when sys_waittid is called
     drop(task_inner);
-    drop(task);
+    drop(res);
     // however, if this is the main thread of current process
     // the process should terminate at once
-    if tid == 0 {
+    if tid == Some(0) {
         let pid = process.getpid();
         if pid == IDLE_PID {
             println!(
                 "[kernel] Idle process exit with exit_code {} ...",
                 exit_code
             );
+            // nothing writes back the block cache after shutdown
+            crate::fs::sync_all();
             if exit_code != 0 {
                 //crate::sbi::shutdown(255); //255 == -1 for err hint
                 crate::board::QEMU_EXIT_HANDLE.exit_failure();
@@ -93,40 +123,63 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         process_inner.is_zombie = true;
         // record exit code of main process
         process_inner.exit_code = exit_code;
+        let parent = process_inner
+            .parent
+            .as_ref()
+            .and_then(|parent| parent.upgrade());
+        let children = core::mem::take(&mut process_inner.children);
+        let tasks: Vec<_> = process_inner.tasks.iter().flatten().cloned().collect();
+        // no other PCB is locked while holding this one, since their owners
+        // may be waiting for this PCB in the other order
+        drop(process_inner);
+
+        // other threads running on other harts exit when they trap next time
+        for other in tasks.iter().filter(|other| !Arc::ptr_eq(other, &task)) {
+            while other.on_cpu.load(Ordering::Acquire) {
+                spin_loop();
+            }
+        }
 
         {
             // move all child processes under init process
             let mut initproc_inner = INITPROC.inner_exclusive_access();
-            for child in process_inner.children.iter() {
+            for child in children.iter() {
                 child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
                 initproc_inner.children.push(child.clone());
             }
         }
+        // some of them may be zombies already
+        if !children.is_empty() {
+            INITPROC.child_exit.signal_all();
+        }
 
         // deallocate user res (including tid/trap_cx/ustack) of all threads
         // it has to be done before we dealloc the whole memory_set
         // otherwise they will be deallocated twice
         let mut recycle_res = Vec::<TaskUserRes>::new();
-        for task in process_inner.tasks.iter().filter(|t| t.is_some()) {
-            let task = task.as_ref().unwrap();
+        for task in tasks.iter() {
             let mut task_inner = task.inner_exclusive_access();
             if let Some(res) = task_inner.res.take() {
                 recycle_res.push(res);
             }
         }
-        // dealloc_tid and dealloc_user_res require access to PCB inner, so we
-        // need to collect those user res first, then release process_inner
-        // for now to avoid deadlock/double borrow problem.
-        drop(process_inner);
+        // dealloc_tid and dealloc_user_res require access to PCB inner
         recycle_res.clear();
 
         let mut process_inner = process.inner_exclusive_access();
-        process_inner.children.clear();
         // deallocate other data in user space i.e. program code/data section
         process_inner.memory_set.recycle_data_pages();
         // drop file descriptors
         process_inner.fd_table.clear();
+        drop(process_inner);
+
+        // notify the parent after the zombie is in place, see sys_waitpid
+        if let Some(parent) = parent {
+            parent.inner_exclusive_access().signals |= SignalFlags::SIGCHLD;
+            parent.child_exit.signal_all();
+        }
     }
+    drop(task);
     drop(process);
     // we do not have to save task context
     let mut _unused = TaskContext::zero_init();
@@ -135,7 +188,14 @@ pub fn exit_current_and_run_next(exit_code: i32) {
 
 lazy_static! {
     pub static ref INITPROC: Arc<ProcessControlBlock> = {
-        let inode = open_file("initproc", OpenFlags::RDONLY).unwrap();
+        let inode = open_file(
+            &Cwd::root(),
+            "initproc",
+            OpenFlags::RDONLY,
+            0,
+            &Credentials::root(),
+        )
+        .unwrap();
         let v = inode.read_all();
         ProcessControlBlock::new(v.as_slice())
     };
@@ -145,10 +205,79 @@ pub fn add_initproc() {
     let _initproc = INITPROC.clone();
 }
 
-pub fn check_signals_of_current() -> Option<(i32, &'static str)> {
-    let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    process_inner.signals.check_error()
+/// Act on the pending signals of the current process before returning to user
+/// mode, which may terminate or stop it, or redirect it into a user handler.
+pub fn handle_signals() {
+    loop {
+        let process = current_process();
+        let mut inner = process.inner_exclusive_access();
+        // the main thread has exited while this thread ran on another hart
+        if inner.is_zombie {
+            drop(inner);
+            drop(process);
+            exit_current_and_run_next(0);
+            return;
+        }
+        // faults are never blocked
+        let pending = inner.signals - (inner.signal_mask - SignalFlags::faults());
+        if inner.stopped && !pending.contains(SignalFlags::SIGKILL) {
+            drop(inner);
+            drop(process);
+            suspend_current_and_run_next();
+            continue;
+        }
+        let task = current_task().unwrap();
+        let in_handler = task.inner_exclusive_access().signal_frame.is_some();
+        let caught =
+            |signum: usize| !matches!(inner.signal_actions[signum].handler, SIG_DFL | SIG_IGN);
+        // a thread runs one handler at a time, other caught signals wait for it
+        let signum = match (1..=MAX_SIG).find(|signum| {
+            let signal = SignalFlags::from_signum(*signum).unwrap();
+            pending.contains(signal)
+                && (!in_handler || !caught(*signum) || SignalFlags::faults().contains(signal))
+        }) {
+            Some(signum) => signum,
+            None => return,
+        };
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        let action = inner.signal_actions[signum];
+        inner.signals.remove(signal);
+        let is_fault = SignalFlags::faults().contains(signal);
+        let default = match action.handler {
+            SIG_DFL => signal.default_action(),
+            SIG_IGN if !is_fault => SignalDefault::Ignore,
+            handler if !in_handler => {
+                // save the context for sys_sigreturn and enter the handler
+                let mut task_inner = task.inner_exclusive_access();
+                let trap_cx = task_inner.get_trap_cx();
+                task_inner.signal_frame = Some(SignalFrame {
+                    trap_cx: trap_cx.clone(),
+                    mask: inner.signal_mask,
+                });
+                inner.signal_mask |= (action.mask | signal) - SignalFlags::unmaskable();
+                trap_cx.sepc = handler;
+                trap_cx.x[1] = action.restorer;
+                trap_cx.x[10] = signum;
+                return;
+            }
+            // the faulting instruction would be executed again
+            _ => SignalDefault::Terminate,
+        };
+        match default {
+            SignalDefault::Ignore => {}
+            SignalDefault::Stop => inner.stopped = true,
+            SignalDefault::Terminate => {
+                drop(inner);
+                drop(process);
+                drop(task);
+                if let Some((_, msg)) = signal.check_error() {
+                    println!("[kernel] {}", msg);
+                }
+                exit_current_and_run_next(-(signum as i32));
+                return;
+            }
+        }
+    }
 }
 
 pub fn current_add_signal(signal: SignalFlags) {
@@ -1,11 +1,13 @@
 use super::id::RecycleAllocator;
 use super::manager::insert_into_pid2process;
 use super::TaskControlBlock;
-use super::{add_task, SignalFlags};
+use super::{add_task, SignalAction, SignalFlags, MAX_SIG, SIG_IGN};
 use super::{pid_alloc, PidHandle};
-use crate::fs::{File, Stdin, Stdout};
-use crate::mm::{translated_refmut, MemorySet, KERNEL_SPACE};
-use crate::sync::{Condvar, Mutex, Semaphore, UPIntrFreeCell, UPIntrRefMut};
+use crate::config::{MMAP_TOP, PAGE_SIZE, USER_HEAP_LIMIT};
+use crate::errno::{Errno, SysResult};
+use crate::fs::{Credentials, Cwd, File, Stdin, Stdout};
+use crate::mm::{MemorySet, VirtAddr, KERNEL_SPACE};
+use crate::sync::{Condvar, Mutex, Semaphore, SpinIntrFreeCell, SpinIntrRefMut};
 use crate::trap::{trap_handler, TrapContext};
 use alloc::string::String;
 use alloc::sync::{Arc, Weak};
@@ -15,18 +17,33 @@ use alloc::vec::Vec;
 pub struct ProcessControlBlock {
     // immutable
     pub pid: PidHandle,
+    /// waiters in sys_waitpid, woken up when a child exits
+    pub child_exit: Condvar,
     // mutable
-    inner: UPIntrFreeCell<ProcessControlBlockInner>,
+    inner: SpinIntrFreeCell<ProcessControlBlockInner>,
 }
 
 pub struct ProcessControlBlockInner {
     pub is_zombie: bool,
     pub memory_set: MemorySet,
+    /// the heap area covers `[heap_bottom, program_brk)`
+    pub heap_bottom: usize,
+    pub program_brk: usize,
     pub parent: Option<Weak<ProcessControlBlock>>,
     pub children: Vec<Arc<ProcessControlBlock>>,
     pub exit_code: i32,
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    /// the working directory
+    pub cwd: Cwd,
+    /// users and groups checked for accessing files
+    pub cred: Credentials,
+    /// pending signals
     pub signals: SignalFlags,
+    /// blocked signals
+    pub signal_mask: SignalFlags,
+    pub signal_actions: [SignalAction; MAX_SIG + 1],
+    /// stopped by SIGSTOP until SIGCONT
+    pub stopped: bool,
     pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
     pub task_res_allocator: RecycleAllocator,
     pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
@@ -40,6 +57,23 @@ impl ProcessControlBlockInner {
         self.memory_set.token()
     }
 
+    /// Move the program break by resizing the heap area.
+    pub fn change_program_brk(&mut self, new_brk: usize) -> bool {
+        if new_brk < self.heap_bottom || new_brk > self.heap_bottom + USER_HEAP_LIMIT {
+            return false;
+        }
+        let heap_bottom = self.heap_bottom.into();
+        let resized = if new_brk < self.program_brk {
+            self.memory_set.shrink_to(heap_bottom, new_brk.into())
+        } else {
+            self.memory_set.append_to(heap_bottom, new_brk.into())
+        };
+        if resized {
+            self.program_brk = new_brk;
+        }
+        resized
+    }
+
     pub fn alloc_fd(&mut self) -> usize {
         if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
             fd
@@ -49,6 +83,11 @@ impl ProcessControlBlockInner {
         }
     }
 
+    /// The file opened as `fd`.
+    pub fn get_file(&self, fd: usize) -> SysResult<Arc<dyn File + Send + Sync>> {
+        self.fd_table.get(fd).cloned().flatten().ok_or(Errno::EBADF)
+    }
+
     pub fn alloc_tid(&mut self) -> usize {
         self.task_res_allocator.alloc()
     }
@@ -67,40 +106,46 @@ impl ProcessControlBlockInner {
 }
 
 impl ProcessControlBlock {
-    pub fn inner_exclusive_access(&self) -> UPIntrRefMut<'_, ProcessControlBlockInner> {
+    pub fn inner_exclusive_access(&self) -> SpinIntrRefMut<'_, ProcessControlBlockInner> {
         self.inner.exclusive_access()
     }
 
     pub fn new(elf_data: &[u8]) -> Arc<Self> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, ustack_base, heap_bottom, entry_point) = MemorySet::from_elf(elf_data);
         // allocate a pid
         let pid_handle = pid_alloc();
         let process = Arc::new(Self {
             pid: pid_handle,
-            inner: unsafe {
-                UPIntrFreeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: vec![
-                        // 0 -> stdin
-                        Some(Arc::new(Stdin)),
-                        // 1 -> stdout
-                        Some(Arc::new(Stdout)),
-                        // 2 -> stderr
-                        Some(Arc::new(Stdout)),
-                    ],
-                    signals: SignalFlags::empty(),
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                })
-            },
+            child_exit: Condvar::new(),
+            inner: SpinIntrFreeCell::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                heap_bottom,
+                program_brk: heap_bottom,
+                parent: None,
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: vec![
+                    // 0 -> stdin
+                    Some(Arc::new(Stdin)),
+                    // 1 -> stdout
+                    Some(Arc::new(Stdout)),
+                    // 2 -> stderr
+                    Some(Arc::new(Stdout)),
+                ],
+                cwd: Cwd::root(),
+                cred: Credentials::root(),
+                signals: SignalFlags::empty(),
+                signal_mask: SignalFlags::empty(),
+                signal_actions: [SignalAction::default(); MAX_SIG + 1],
+                stopped: false,
+                tasks: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+            }),
         });
         // create a main thread, we should allocate ustack and trap_cx here
         let task = Arc::new(TaskControlBlock::new(
@@ -135,10 +180,19 @@ impl ProcessControlBlock {
     pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) {
         assert_eq!(self.inner_exclusive_access().thread_count(), 1);
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
-        let new_token = memory_set.token();
+        let (memory_set, ustack_base, heap_bottom, entry_point) = MemorySet::from_elf(elf_data);
         // substitute memory_set
-        self.inner_exclusive_access().memory_set = memory_set;
+        let mut inner = self.inner_exclusive_access();
+        inner.memory_set = memory_set;
+        inner.heap_bottom = heap_bottom;
+        inner.program_brk = heap_bottom;
+        // handlers are gone with the old image, ignored signals stay ignored
+        for action in inner.signal_actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+        drop(inner);
         // then we alloc user resource for main thread again
         // since memory_set has been changed
         let task = self.inner_exclusive_access().get_task(0);
@@ -146,29 +200,26 @@ impl ProcessControlBlock {
         task_inner.res.as_mut().unwrap().ustack_base = ustack_base;
         task_inner.res.as_mut().unwrap().alloc_user_res();
         task_inner.trap_cx_ppn = task_inner.res.as_mut().unwrap().trap_cx_ppn();
+        task_inner.signal_frame = None;
         // push arguments on user stack
         let mut user_sp = task_inner.res.as_mut().unwrap().ustack_top();
         user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
         let argv_base = user_sp;
-        let mut argv: Vec<_> = (0..=args.len())
-            .map(|arg| {
-                translated_refmut(
-                    new_token,
-                    (argv_base + arg * core::mem::size_of::<usize>()) as *mut usize,
-                )
-            })
-            .collect();
-        *argv[args.len()] = 0;
-        for i in 0..args.len() {
-            user_sp -= args[i].len() + 1;
-            *argv[i] = user_sp;
-            let mut p = user_sp;
-            for c in args[i].as_bytes() {
-                *translated_refmut(new_token, p as *mut u8) = *c;
-                p += 1;
-            }
-            *translated_refmut(new_token, p as *mut u8) = 0;
+        let mut argv = Vec::new();
+        let mut inner = self.inner_exclusive_access();
+        for arg in args.iter() {
+            user_sp -= arg.len() + 1;
+            argv.push(user_sp);
+            let mut bytes = arg.clone().into_bytes();
+            bytes.push(0);
+            inner.memory_set.copy_to_user(user_sp, &bytes).unwrap();
+        }
+        argv.push(0);
+        for (i, arg_ptr) in argv.into_iter().enumerate() {
+            let ptr = (argv_base + i * core::mem::size_of::<usize>()) as *mut usize;
+            inner.memory_set.write_user(ptr, arg_ptr).unwrap();
         }
+        drop(inner);
         // make the user_sp aligned to 8B for k210 platform
         user_sp -= user_sp % core::mem::size_of::<usize>();
         // initialize trap_cx
@@ -188,8 +239,9 @@ impl ProcessControlBlock {
     pub fn fork(self: &Arc<Self>) -> Arc<Self> {
         let mut parent = self.inner_exclusive_access();
         assert_eq!(parent.thread_count(), 1);
-        // clone parent's memory_set completely including trampoline/ustacks/trap_cxs
-        let memory_set = MemorySet::from_existed_user(&parent.memory_set);
+        // clone parent's memory_set including trampoline/ustacks/trap_cxs,
+        // user pages are shared copy-on-write
+        let memory_set = MemorySet::from_existed_user(&mut parent.memory_set);
         // alloc a pid
         let pid = pid_alloc();
         // copy fd table
@@ -204,22 +256,28 @@ impl ProcessControlBlock {
         // create child process pcb
         let child = Arc::new(Self {
             pid,
-            inner: unsafe {
-                UPIntrFreeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: new_fd_table,
-                    signals: SignalFlags::empty(),
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                })
-            },
+            child_exit: Condvar::new(),
+            inner: SpinIntrFreeCell::new(ProcessControlBlockInner {
+                is_zombie: false,
+                memory_set,
+                heap_bottom: parent.heap_bottom,
+                program_brk: parent.program_brk,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: new_fd_table,
+                cwd: parent.cwd.clone(),
+                cred: parent.cred,
+                signals: SignalFlags::empty(),
+                signal_mask: parent.signal_mask,
+                signal_actions: parent.signal_actions,
+                stopped: false,
+                tasks: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+            }),
         });
         // add child
         parent.children.push(Arc::clone(&child));
@@ -252,6 +310,47 @@ impl ProcessControlBlock {
         child
     }
 
+    /// Unmap and write back the `MAP_SHARED` file mappings before the address
+    /// space is replaced or freed, the process must not be borrowed since the
+    /// disk accesses may block.
+    pub fn write_back_shared_files(&self) {
+        let areas = self
+            .inner_exclusive_access()
+            .memory_set
+            .remove_shared_file_areas();
+        for area in areas.iter() {
+            area.write_back();
+        }
+    }
+
+    /// Read the pages of file mappings in `[start, start + len)` which are not
+    /// read yet, so that page faults and the kernel find them in memory. The
+    /// process must not be borrowed since the disk accesses may block.
+    /// Return false if out of memory.
+    pub fn load_file_pages(&self, start: usize, len: usize) -> bool {
+        let end = start.saturating_add(len).min(MMAP_TOP);
+        let mut va = start;
+        while va < end {
+            let page = match self
+                .inner_exclusive_access()
+                .memory_set
+                .first_unread_file_page(va.into(), end.into())
+            {
+                Some(page) => page,
+                None => break,
+            };
+            let frame = match page.read() {
+                Some(frame) => frame,
+                None => return false,
+            };
+            self.inner_exclusive_access()
+                .memory_set
+                .fill_file_page(&page, frame);
+            va = usize::from(VirtAddr::from(page.vpn())) + PAGE_SIZE;
+        }
+        true
+    }
+
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
@@ -1,10 +1,16 @@
 use super::__switch;
 use super::{fetch_task, TaskStatus};
 use super::{ProcessControlBlock, TaskContext, TaskControlBlock};
-use crate::sync::UPIntrFreeCell;
+use crate::config::MAX_HARTS;
+use crate::sbi::send_ipi;
+use crate::sync::SpinIntrFreeCell;
 use crate::trap::TrapContext;
 use alloc::sync::Arc;
+use core::arch::asm;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::*;
+use riscv::register::sstatus;
 
 pub struct Processor {
     current: Option<Arc<TaskControlBlock>>,
@@ -30,38 +36,85 @@ impl Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPIntrFreeCell<Processor> =
-        unsafe { UPIntrFreeCell::new(Processor::new()) };
+    static ref PROCESSORS: [SpinIntrFreeCell<Processor>; MAX_HARTS] =
+        core::array::from_fn(|_| SpinIntrFreeCell::new(Processor::new()));
+}
+
+/// Bit `i` is set while hart `i` looks for a task to run.
+static IDLE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// The id of the running hart, which the kernel keeps in `tp`.
+pub fn hart_id() -> usize {
+    let hart_id;
+    unsafe {
+        asm!("mv {}, tp", out(reg) hart_id);
+    }
+    hart_id
+}
+
+fn processor() -> &'static SpinIntrFreeCell<Processor> {
+    &PROCESSORS[hart_id()]
 }
 
 pub fn run_tasks() {
+    let idle_bit = 1 << hart_id();
     loop {
-        let mut processor = PROCESSOR.exclusive_access();
+        // set before fetching, so that a task added after a failed fetch
+        // always comes with an IPI
+        IDLE_HARTS.fetch_or(idle_bit, Ordering::SeqCst);
         if let Some(task) = fetch_task() {
+            IDLE_HARTS.fetch_and(!idle_bit, Ordering::SeqCst);
+            // the hart which ran the task last may not have saved its context yet
+            while task.on_cpu.swap(true, Ordering::Acquire) {
+                spin_loop();
+            }
+            let mut processor = processor().exclusive_access();
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // access coming task TCB exclusively
             let next_task_cx_ptr = task.inner.exclusive_session(|task_inner| {
                 task_inner.task_status = TaskStatus::Running;
                 &task_inner.task_cx as *const TaskContext
             });
-            processor.current = Some(task);
+            processor.current = Some(Arc::clone(&task));
             // release processor manually
             drop(processor);
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            // the task is off this hart's stack now, other harts may run it
+            task.on_cpu.store(false, Ordering::Release);
         } else {
-            println!("no tasks available in run_tasks");
+            wait_for_interrupt();
         }
     }
 }
 
+/// Sleep until an interrupt, such as the IPI of `wake_idle_hart`.
+fn wait_for_interrupt() {
+    let sie = sstatus::read().sie();
+    unsafe {
+        sstatus::set_sie();
+        riscv::asm::wfi();
+        if !sie {
+            sstatus::clear_sie();
+        }
+    }
+}
+
+/// Send an IPI to one of the idle harts after a task becomes ready.
+pub fn wake_idle_hart() {
+    let idle_harts = IDLE_HARTS.load(Ordering::SeqCst) & !(1 << hart_id());
+    if idle_harts != 0 {
+        send_ipi(1 << idle_harts.trailing_zeros());
+    }
+}
+
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    processor().exclusive_access().take_current()
 }
 
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    processor().exclusive_access().current()
 }
 
 pub fn current_process() -> Arc<ProcessControlBlock> {
@@ -96,7 +149,7 @@ pub fn current_kstack_top() -> usize {
 
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     let idle_task_cx_ptr =
-        PROCESSOR.exclusive_session(|processor| processor.get_idle_task_cx_ptr());
+        processor().exclusive_session(|processor| processor.get_idle_task_cx_ptr());
     unsafe {
         __switch(switched_task_cx_ptr, idle_task_cx_ptr);
     }
@@ -0,0 +1,201 @@
+use super::TaskControlBlock;
+use alloc::boxed::Box;
+use alloc::collections::{BinaryHeap, VecDeque};
+use alloc::sync::Arc;
+use core::cmp::{Ordering, Reverse};
+
+pub const DEFAULT_PRIORITY: usize = 16;
+pub const MIN_PRIORITY: usize = 2;
+pub const MAX_PRIORITY: usize = 1 << 16;
+
+/// Each task advances its stride by `BIG_STRIDE / priority` per time slice.
+const BIG_STRIDE: u64 = 1 << 32;
+/// Number of queues of the multilevel feedback queue, level 0 runs first.
+const MLFQ_LEVELS: usize = 4;
+/// Every task goes back to level 0 after this many time slices.
+const MLFQ_BOOST_INTERVAL: usize = 100;
+
+/// Scheduling state of a thread, owned by its TCB.
+pub struct SchedEntity {
+    pub priority: usize,
+    /// set when the thread is preempted for using up its time slice
+    pub preempted: bool,
+    stride: u64,
+    level: usize,
+}
+
+impl SchedEntity {
+    pub fn new() -> Self {
+        Self {
+            priority: DEFAULT_PRIORITY,
+            preempted: false,
+            stride: 0,
+            level: 0,
+        }
+    }
+}
+
+/// A scheduling policy of the ready threads.
+pub trait Scheduler: Send {
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>>;
+}
+
+/// The policy selected by the `sched-*` features, FIFO if none is enabled.
+pub fn new_scheduler() -> Box<dyn Scheduler> {
+    if cfg!(feature = "sched-stride") {
+        Box::new(StrideScheduler::new())
+    } else if cfg!(feature = "sched-mlfq") {
+        Box::new(MlfqScheduler::new())
+    } else {
+        Box::new(FifoScheduler::new())
+    }
+}
+
+#[cfg(all(feature = "sched-stride", feature = "sched-mlfq"))]
+compile_error!("at most one of the sched-* features can be enabled");
+
+/// Round robin in the order in which threads become ready.
+pub struct FifoScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl FifoScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for FifoScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+}
+
+struct StrideEntry {
+    stride: u64,
+    /// breaks ties in FIFO order
+    seq: u64,
+    task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for StrideEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for StrideEntry {}
+
+impl PartialOrd for StrideEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StrideEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.stride, self.seq).cmp(&(other.stride, other.seq))
+    }
+}
+
+/// Runs the thread with the smallest stride, so the share of CPU time of a
+/// thread is proportional to its priority.
+pub struct StrideScheduler {
+    ready_queue: BinaryHeap<Reverse<StrideEntry>>,
+    /// stride of the last fetched thread
+    min_stride: u64,
+    seq: u64,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: BinaryHeap::new(),
+            min_stride: 0,
+            seq: 0,
+        }
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let stride = {
+            let mut task_inner = task.inner_exclusive_access();
+            let sched = &mut task_inner.sched;
+            sched.preempted = false;
+            // new and long sleeping threads must not starve the others
+            sched.stride = sched.stride.max(self.min_stride);
+            sched.stride
+        };
+        self.seq += 1;
+        self.ready_queue.push(Reverse(StrideEntry {
+            stride,
+            seq: self.seq,
+            task,
+        }));
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let Reverse(entry) = self.ready_queue.pop()?;
+        self.min_stride = entry.stride;
+        let mut task_inner = entry.task.inner_exclusive_access();
+        let sched = &mut task_inner.sched;
+        sched.stride += BIG_STRIDE / sched.priority as u64;
+        drop(task_inner);
+        Some(entry.task)
+    }
+}
+
+/// Threads start at the highest level and move down each time they are
+/// preempted, so interactive threads which block early keep running first.
+/// All threads are boosted back to the top periodically to avoid starvation.
+/// The priority is not used since the levels follow the CPU usage.
+pub struct MlfqScheduler {
+    queues: [VecDeque<Arc<TaskControlBlock>>; MLFQ_LEVELS],
+    fetched: usize,
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            fetched: 0,
+        }
+    }
+
+    fn boost(&mut self) {
+        for level in 1..MLFQ_LEVELS {
+            while let Some(task) = self.queues[level].pop_front() {
+                task.inner_exclusive_access().sched.level = 0;
+                self.queues[0].push_back(task);
+            }
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let level = {
+            let mut task_inner = task.inner_exclusive_access();
+            let sched = &mut task_inner.sched;
+            if sched.preempted {
+                sched.preempted = false;
+                sched.level = (sched.level + 1).min(MLFQ_LEVELS - 1);
+            }
+            sched.level
+        };
+        self.queues[level].push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.fetched += 1;
+        if self.fetched % MLFQ_BOOST_INTERVAL == 0 {
+            self.boost();
+        }
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+}
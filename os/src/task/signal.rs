@@ -1,16 +1,64 @@
+use crate::trap::TrapContext;
 use bitflags::*;
 
+pub const MAX_SIG: usize = 31;
+
 bitflags! {
     pub struct SignalFlags: u32 {
+        const SIGHUP    = 1 << 1;
         const SIGINT    = 1 << 2;
+        const SIGQUIT   = 1 << 3;
         const SIGILL    = 1 << 4;
+        const SIGTRAP   = 1 << 5;
         const SIGABRT   = 1 << 6;
+        const SIGBUS    = 1 << 7;
         const SIGFPE    = 1 << 8;
+        const SIGKILL   = 1 << 9;
+        const SIGUSR1   = 1 << 10;
         const SIGSEGV   = 1 << 11;
+        const SIGUSR2   = 1 << 12;
+        const SIGPIPE   = 1 << 13;
+        const SIGALRM   = 1 << 14;
+        const SIGTERM   = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD   = 1 << 17;
+        const SIGCONT   = 1 << 18;
+        const SIGSTOP   = 1 << 19;
+        const SIGTSTP   = 1 << 20;
+        const SIGTTIN   = 1 << 21;
+        const SIGTTOU   = 1 << 22;
+        const SIGURG    = 1 << 23;
+        const SIGXCPU   = 1 << 24;
+        const SIGXFSZ   = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF   = 1 << 27;
+        const SIGWINCH  = 1 << 28;
+        const SIGIO     = 1 << 29;
+        const SIGPWR    = 1 << 30;
+        const SIGSYS    = 1 << 31;
     }
 }
 
 impl SignalFlags {
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if (1..=MAX_SIG).contains(&signum) {
+            Self::from_bits(1 << signum)
+        } else {
+            None
+        }
+    }
+
+    /// Signals which can neither be caught, ignored nor blocked.
+    pub fn unmaskable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+
+    /// Signals raised by faulting instructions, which kill the process if they
+    /// can not be handled since the instruction would fault again.
+    pub fn faults() -> Self {
+        Self::SIGILL | Self::SIGBUS | Self::SIGFPE | Self::SIGSEGV
+    }
+
     pub fn check_error(&self) -> Option<(i32, &'static str)> {
         if self.contains(Self::SIGINT) {
             Some((-2, "Killed, SIGINT=2"))
@@ -26,4 +74,52 @@ impl SignalFlags {
             None
         }
     }
+
+    pub fn default_action(&self) -> SignalDefault {
+        if self.intersects(Self::SIGCHLD | Self::SIGCONT | Self::SIGURG | Self::SIGWINCH) {
+            SignalDefault::Ignore
+        } else if self.intersects(Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU) {
+            SignalDefault::Stop
+        } else {
+            SignalDefault::Terminate
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SignalDefault {
+    Terminate,
+    Ignore,
+    Stop,
+}
+
+/// Values of `SignalAction::handler` besides user handlers.
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+/// The action of a signal shared with user space.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SignalAction {
+    pub handler: usize,
+    /// the handler returns to here, which is expected to call `sys_sigreturn`
+    pub restorer: usize,
+    /// signals blocked while the handler runs besides the signal itself
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            restorer: 0,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// What a thread saves when it enters a user handler.
+pub struct SignalFrame {
+    pub trap_cx: TrapContext,
+    pub mask: SignalFlags,
 }
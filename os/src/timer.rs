@@ -1,8 +1,9 @@
 use core::cmp::Ordering;
 
+use crate::board::VIRT_RTC;
 use crate::config::CLOCK_FREQ;
 use crate::sbi::set_timer;
-use crate::sync::UPIntrFreeCell;
+use crate::sync::SpinIntrFreeCell;
 use crate::task::{add_task, TaskControlBlock};
 use alloc::collections::BinaryHeap;
 use alloc::sync::Arc;
@@ -11,6 +12,7 @@ use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
 const MSEC_PER_SEC: usize = 1000;
+const NSEC_PER_SEC: usize = 1_000_000_000;
 
 pub fn get_time() -> usize {
     time::read()
@@ -20,6 +22,14 @@ pub fn get_time_ms() -> usize {
     time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
 }
 
+/// Seconds since the Unix epoch, read from the goldfish RTC of the board.
+pub fn get_real_time_sec() -> usize {
+    // reading the low word latches the high one
+    let low = unsafe { (VIRT_RTC as *const u32).read_volatile() } as usize;
+    let high = unsafe { ((VIRT_RTC + 4) as *const u32).read_volatile() } as usize;
+    ((high << 32) | low) / NSEC_PER_SEC
+}
+
 pub fn set_next_trigger() {
     set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
 }
@@ -50,8 +60,8 @@ impl Ord for TimerCondVar {
 }
 
 lazy_static! {
-    static ref TIMERS: UPIntrFreeCell<BinaryHeap<TimerCondVar>> =
-        unsafe { UPIntrFreeCell::new(BinaryHeap::<TimerCondVar>::new()) };
+    static ref TIMERS: SpinIntrFreeCell<BinaryHeap<TimerCondVar>> =
+        SpinIntrFreeCell::new(BinaryHeap::<TimerCondVar>::new());
 }
 
 pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
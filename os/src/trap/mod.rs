@@ -1,17 +1,19 @@
 mod context;
 
 use crate::config::TRAMPOLINE;
+use crate::fs::sync_periodically;
+use crate::mm::VirtAddr;
 use crate::syscall::syscall;
 use crate::task::{
-    check_signals_of_current, current_add_signal, current_trap_cx, current_trap_cx_user_va,
-    current_user_token, exit_current_and_run_next, suspend_current_and_run_next, SignalFlags,
+    current_add_signal, current_process, current_trap_cx, current_trap_cx_user_va,
+    current_user_token, handle_signals, preempt_current_and_run_next, SignalFlags,
 };
 use crate::timer::{check_timer, set_next_trigger};
 use core::arch::{asm, global_asm};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, sscratch, sstatus, stval, stvec,
+    sie, sip, sscratch, sstatus, stval, stvec,
 };
 
 global_asm!(include_str!("trap.S"));
@@ -44,6 +46,13 @@ pub fn enable_timer_interrupt() {
     }
 }
 
+/// IPIs from other harts arrive as supervisor software interrupts.
+pub fn enable_software_interrupt() {
+    unsafe {
+        sie::set_ssoft();
+    }
+}
+
 fn enable_supervisor_interrupt() {
     unsafe {
         sstatus::set_sie();
@@ -70,18 +79,41 @@ pub fn trap_handler() -> ! {
 
             enable_supervisor_interrupt();
 
-            // get system call return value
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]);
+            // get system call return value, the arguments are in a0-a5
+            let args = [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]];
+            let result = syscall(cx.x[17], args);
             // cx is changed during sys_exec, so we have to call it again
             cx = current_trap_cx();
             cx.x[10] = result as usize;
         }
+        Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
+            // allocate a lazy page or duplicate a copy-on-write page
+            let write = matches!(scause.cause(), Trap::Exception(Exception::StorePageFault));
+            let vpn = VirtAddr::from(stval).floor();
+            let process = current_process();
+            // a page of a file is read first since the disk may block, out of
+            // memory it is left unmapped to fault below
+            process.load_file_pages(stval, 1);
+            let mut inner = process.inner_exclusive_access();
+            // another thread of the process may have resolved it on another hart
+            let resolved = inner.memory_set.translate(vpn).map_or(false, |pte| {
+                pte.is_valid()
+                    && match scause.cause() {
+                        Trap::Exception(Exception::StorePageFault) => pte.writable(),
+                        Trap::Exception(Exception::LoadPageFault) => pte.readable(),
+                        _ => pte.executable(),
+                    }
+            });
+            if !resolved && !inner.memory_set.handle_page_fault(vpn, write) {
+                drop(inner);
+                current_add_signal(SignalFlags::SIGSEGV);
+            }
+        }
         Trap::Exception(Exception::StoreFault)
-        | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::InstructionFault)
-        | Trap::Exception(Exception::InstructionPageFault)
-        | Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
+        | Trap::Exception(Exception::LoadFault) => {
             /*
             println!(
                 "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
@@ -98,11 +130,20 @@ pub fn trap_handler() -> ! {
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
             check_timer();
-            suspend_current_and_run_next();
+            // write back the block cache like a syscall, the disk may block
+            enable_supervisor_interrupt();
+            sync_periodically();
+            preempt_current_and_run_next();
         }
         Trap::Interrupt(Interrupt::SupervisorExternal) => {
             crate::board::irq_handler();
         }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            // an IPI only wakes up the hart
+            unsafe {
+                sip::clear_ssoft();
+            }
+        }
         _ => {
             panic!(
                 "Unsupported trap {:?}, stval = {:#x}!",
@@ -111,11 +152,7 @@ pub fn trap_handler() -> ! {
             );
         }
     }
-    // check signals
-    if let Some((errno, msg)) = check_signals_of_current() {
-        println!("[kernel] {}", msg);
-        exit_current_and_run_next(errno);
-    }
+    handle_signals();
     trap_return();
 }
 
@@ -156,6 +193,9 @@ pub fn trap_from_kernel(_trap_cx: &TrapContext) {
             check_timer();
             // do not schedule now
         }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => unsafe {
+            sip::clear_ssoft();
+        },
         _ => {
             panic!(
                 "Unsupported trap from kernel: {:?}, stval = {:#x}!",
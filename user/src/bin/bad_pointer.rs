@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::{ptr, slice, str};
+use user_lib::{
+    close, exec, exit, fork, getcwd, mmap, munmap, pipe, read, waitpid, write, Errno, MapFlags,
+    ProtFlags, SysResult,
+};
+
+const EFAULT: SysResult = Err(Errno::EFAULT);
+const PAGE_SIZE: usize = 4096;
+/// The trampoline is mapped into every process, but only for the kernel.
+const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
+
+/// The memory is only handed to the kernel and never touched here.
+fn bad_buffer(addr: usize, len: usize) -> &'static mut [u8] {
+    unsafe { slice::from_raw_parts_mut(addr as *mut u8, len) }
+}
+
+fn bad_str(addr: usize) -> &'static str {
+    unsafe { str::from_utf8_unchecked(bad_buffer(addr, 1)) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a read-only page followed by an unmapped one
+    let read_only = mmap(
+        0,
+        2 * PAGE_SIZE,
+        ProtFlags::READ,
+        MapFlags::PRIVATE | MapFlags::ANONYMOUS,
+        0,
+        0,
+    )
+    .unwrap();
+    let unmapped = read_only + PAGE_SIZE;
+    munmap(unmapped, PAGE_SIZE).unwrap();
+
+    // the kernel reads from these buffers
+    assert_eq!(write(1, bad_buffer(0, 8)), EFAULT);
+    assert_eq!(write(1, bad_buffer(unmapped, 8)), EFAULT);
+    assert_eq!(write(1, bad_buffer(unmapped - 4, 8)), EFAULT);
+    assert_eq!(write(1, bad_buffer(TRAMPOLINE, 8)), EFAULT);
+    assert_eq!(write(1, bad_buffer(read_only, 0)), Ok(0));
+
+    // the kernel writes to these buffers
+    let mut fds = [0usize; 2];
+    pipe(&mut fds).unwrap();
+    assert_eq!(write(fds[1], b"hello"), Ok(5));
+    assert_eq!(read(fds[0], bad_buffer(read_only, 5)), EFAULT);
+    let mut buf = [0u8; 5];
+    assert_eq!(read(fds[0], &mut buf), Ok(5));
+    assert_eq!(&buf, b"hello");
+    close(fds[0]).unwrap();
+    close(fds[1]).unwrap();
+    let bad_fds = unsafe { slice::from_raw_parts_mut(read_only as *mut usize, 2) };
+    assert_eq!(pipe(bad_fds), Err(Errno::EFAULT));
+    assert_eq!(getcwd(bad_buffer(read_only, 64)), EFAULT);
+
+    // strings and arrays of pointers
+    assert_eq!(exec(bad_str(unmapped), &[ptr::null()]), EFAULT);
+    assert_eq!(
+        exec("bad_pointer\0", &[unmapped as *const u8, ptr::null()]),
+        EFAULT
+    );
+    let bad_args = unsafe { slice::from_raw_parts(unmapped as *const *const u8, 1) };
+    assert_eq!(exec("bad_pointer\0", bad_args), EFAULT);
+
+    // the child is not reaped if its exit code can not be stored
+    let pid = fork();
+    if pid == 0 {
+        exit(7);
+    }
+    let bad_exit_code = unsafe { &mut *(read_only as *mut i32) };
+    assert_eq!(waitpid(pid as usize, bad_exit_code), EFAULT);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), Ok(pid as usize));
+    assert_eq!(exit_code, 7);
+    println!("bad_pointer passed!");
+    0
+}
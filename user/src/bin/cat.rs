@@ -5,7 +5,8 @@
 extern crate user_lib;
 extern crate alloc;
 
-use user_lib::{close, open, read, OpenFlags};
+use alloc::vec;
+use user_lib::{close, fstat, open, read, OpenFlags, Stat};
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
@@ -14,19 +15,22 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
         println!("argv[{}] = {}", i, arg);
     }
     assert!(argc == 2);
-    let fd = open(argv[1], OpenFlags::RDONLY);
-    if fd == -1 {
-        panic!("Error occurred when opening file");
-    }
-    let fd = fd as usize;
-    let mut buf = [0u8; 256];
-    loop {
-        let size = read(fd, &mut buf) as usize;
-        if size == 0 {
-            break;
+    let fd = match open(argv[1], OpenFlags::RDONLY) {
+        Ok(fd) => fd,
+        Err(errno) => panic!("Error occurred when opening file: {:?}", errno),
+    };
+    // read the whole file at once, so that no character is split
+    let mut stat = Stat::default();
+    fstat(fd, &mut stat).unwrap();
+    let mut buf = vec![0u8; stat.size as usize];
+    let mut size = 0;
+    while size < buf.len() {
+        match read(fd, &mut buf[size..]).unwrap() {
+            0 => break,
+            len => size += len,
         }
-        print!("{}", core::str::from_utf8(&buf[..size]).unwrap());
     }
-    close(fd);
+    print!("{}", core::str::from_utf8(&buf[..size]).unwrap());
+    close(fd).unwrap();
     0
 }
@@ -4,15 +4,26 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::read;
+extern crate alloc;
+
+use alloc::vec;
+use user_lib::{fstat, read, Stat, StatMode};
 
 #[no_mangle]
 pub fn main(_argc: usize, _argv: &[&str]) -> i32 {
-    let mut buf = [0u8; 256];
+    // a redirected file is read at once, a pipe in pieces
+    let mut stat = Stat::default();
+    fstat(0, &mut stat).unwrap();
+    let len = if stat.mode == StatMode::FILE {
+        (stat.size as usize).max(1)
+    } else {
+        256
+    };
+    let mut buf = vec![0u8; len];
     let mut lines = 0usize;
     let mut total_size = 0usize;
     loop {
-        let len = read(0, &mut buf) as usize;
+        let len = read(0, &mut buf).unwrap();
         if len == 0 {
             break;
         }
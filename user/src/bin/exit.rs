@@ -22,8 +22,8 @@ pub fn main() -> i32 {
     }
     println!("I am the parent, waiting now..");
     let mut xstate: i32 = 0;
-    assert!(waitpid(pid as usize, &mut xstate) == pid && xstate == MAGIC);
-    assert!(waitpid(pid as usize, &mut xstate) < 0 && wait(&mut xstate) <= 0);
+    assert!(waitpid(pid as usize, &mut xstate) == Ok(pid as usize) && xstate == MAGIC);
+    assert!(waitpid(pid as usize, &mut xstate).is_err() && wait(&mut xstate).is_err());
     println!("waitpid {} ok.", pid);
     println!("exit pass.");
     0
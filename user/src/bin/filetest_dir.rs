@@ -0,0 +1,104 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chdir, close, getcwd, mkdir, open, read, rename, rmdir, symlink, unlink, write, Errno,
+    OpenFlags,
+};
+
+fn cwd_is(expected: &str) -> bool {
+    let mut buffer = [0u8; 64];
+    let len = getcwd(&mut buffer).unwrap();
+    // drop the end `\0`
+    core::str::from_utf8(&buffer[..len - 1]).unwrap() == expected
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert!(cwd_is("/"));
+    assert_eq!(mkdir("dira\0"), Ok(()));
+    assert_eq!(mkdir("dira\0"), Err(Errno::EEXIST));
+    assert_eq!(mkdir("/dira/dirb\0"), Ok(()));
+    assert_eq!(mkdir("nodir/dirc\0"), Err(Errno::ENOENT));
+
+    // create a file through a relative path
+    assert_eq!(chdir("dira/dirb\0"), Ok(()));
+    assert!(cwd_is("/dira/dirb"));
+    let test_str = "Hello, directory!";
+    let fd = open("filec\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    write(fd, test_str.as_bytes()).unwrap();
+    close(fd).unwrap();
+
+    // then read it back through `..` and an absolute path
+    assert_eq!(chdir("..\0"), Ok(()));
+    assert!(cwd_is("/dira"));
+    for path in [
+        "dirb/filec\0",
+        "./dirb/../dirb/filec\0",
+        "/dira/dirb/filec\0",
+    ] {
+        let fd = open(path, OpenFlags::RDONLY).unwrap();
+        let mut buffer = [0u8; 100];
+        let read_len = read(fd, &mut buffer).unwrap();
+        close(fd).unwrap();
+        assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    }
+
+    // directories can not be written or entered through a file
+    assert_eq!(open("dirb\0", OpenFlags::WRONLY), Err(Errno::EISDIR));
+    assert_eq!(
+        open("dirb\0", OpenFlags::RDONLY | OpenFlags::TRUNC),
+        Err(Errno::EISDIR)
+    );
+    assert_eq!(open("dirb\0", OpenFlags::CREATE), Err(Errno::EISDIR));
+    assert_eq!(chdir("dirb/filec\0"), Err(Errno::ENOTDIR));
+    assert_eq!(
+        open("dirb/filec/x\0", OpenFlags::RDONLY),
+        Err(Errno::ENOTDIR)
+    );
+    assert_eq!(
+        open("dirb/filec/x\0", OpenFlags::CREATE),
+        Err(Errno::ENOTDIR)
+    );
+    assert_eq!(mkdir("dirb/filec/x\0"), Err(Errno::ENOTDIR));
+    assert_eq!(chdir("/..\0"), Ok(()));
+    assert!(cwd_is("/"));
+
+    // the working directory is kept when a directory above it is renamed
+    assert_eq!(chdir("dira/dirb\0"), Ok(()));
+    assert_eq!(rename("/dira\0", "/dirc\0"), Ok(()));
+    assert!(cwd_is("/dirc/dirb"));
+    // `..` after a symbolic link is the parent of its target
+    assert_eq!(symlink("/dirc/dirb\0", "/dirlink\0"), Ok(()));
+    assert_eq!(chdir("/dirlink/..\0"), Ok(()));
+    assert!(cwd_is("/dirc"));
+    assert_eq!(chdir("/\0"), Ok(()));
+    assert_eq!(unlink("dirlink\0"), Ok(()));
+    assert_eq!(rename("dirc\0", "dira\0"), Ok(()));
+
+    // a removed working directory has no path and takes no new files
+    assert_eq!(mkdir("dird\0"), Ok(()));
+    assert_eq!(chdir("dird\0"), Ok(()));
+    assert_eq!(rmdir("/dird\0"), Ok(()));
+    assert_eq!(getcwd(&mut [0u8; 64]), Err(Errno::ENOENT));
+    assert_eq!(
+        open("filed\0", OpenFlags::CREATE | OpenFlags::WRONLY),
+        Err(Errno::ENOENT)
+    );
+    assert_eq!(chdir("/\0"), Ok(()));
+
+    // names take up to 255 bytes
+    let mut name = [b'x'; 257];
+    name[256] = 0;
+    let long_name = core::str::from_utf8(&name).unwrap();
+    assert_eq!(mkdir(long_name), Err(Errno::ENAMETOOLONG));
+    assert_eq!(mkdir(&long_name[1..]), Ok(()));
+    assert_eq!(chdir(&long_name[1..]), Ok(()));
+    assert_eq!(chdir("..\0"), Ok(()));
+    assert_eq!(rmdir(&long_name[1..]), Ok(()));
+    println!("filetest_dir passed!");
+    0
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, link, mkdir, open, read, rename, rmdir, unlink, write, Errno, OpenFlags};
+
+fn read_str<'a>(path: &str, buffer: &'a mut [u8]) -> Option<&'a str> {
+    let fd = open(path, OpenFlags::RDONLY).ok()?;
+    let read_len = read(fd, buffer).unwrap();
+    close(fd).unwrap();
+    Some(core::str::from_utf8(&buffer[..read_len]).unwrap())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, link!";
+    let fd = open("linka\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    write(fd, test_str.as_bytes()).unwrap();
+    close(fd).unwrap();
+
+    // a hard link shares the content and outlives the original name
+    let mut buffer = [0u8; 100];
+    assert_eq!(link("linka\0", "linkb\0"), Ok(()));
+    assert_eq!(link("linka\0", "linkb\0"), Err(Errno::EEXIST));
+    assert_eq!(unlink("linka\0"), Ok(()));
+    assert_eq!(unlink("linka\0"), Err(Errno::ENOENT));
+    assert!(read_str("linka\0", &mut buffer).is_none());
+    assert_eq!(read_str("linkb\0", &mut buffer), Some(test_str));
+
+    // the last link removed, an open file is kept until it is closed
+    let fd = open("linkb\0", OpenFlags::RDONLY).unwrap();
+    assert_eq!(unlink("linkb\0"), Ok(()));
+    let new_fd = open("linkb\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    write(new_fd, b"Hello, again!").unwrap();
+    close(new_fd).unwrap();
+    let read_len = read(fd, &mut buffer).unwrap();
+    assert_eq!(&buffer[..read_len], test_str.as_bytes());
+    close(fd).unwrap();
+    let fd = open("linkb\0", OpenFlags::TRUNC | OpenFlags::WRONLY).unwrap();
+    write(fd, test_str.as_bytes()).unwrap();
+    close(fd).unwrap();
+
+    // rename across directories
+    assert_eq!(mkdir("linkdir\0"), Ok(()));
+    assert_eq!(link("linkdir\0", "linkdir2\0"), Err(Errno::EPERM));
+    assert_eq!(rename("linkb\0", "linkdir/linkc\0"), Ok(()));
+    assert!(read_str("linkb\0", &mut buffer).is_none());
+    assert_eq!(read_str("linkdir/linkc\0", &mut buffer), Some(test_str));
+    assert_eq!(rename("linkdir\0", "linkdir/sub\0"), Err(Errno::EINVAL));
+
+    // directories must be empty and removed with rmdir
+    assert_eq!(unlink("linkdir\0"), Err(Errno::EISDIR));
+    assert_eq!(rmdir("linkdir\0"), Err(Errno::ENOTEMPTY));
+    assert_eq!(rmdir("linkdir/linkc\0"), Err(Errno::ENOTDIR));
+    assert_eq!(unlink("linkdir/linkc\0"), Ok(()));
+    assert_eq!(rmdir("linkdir\0"), Ok(()));
+    assert!(read_str("linkdir/linkc\0", &mut buffer).is_none());
+    println!("filetest_link passed!");
+    0
+}
@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fork, fstat, link, mkdir, mount, open, rename, rmdir, setuid, umount, waitpid, Errno,
+    OpenFlags, Stat,
+};
+
+/// Inode number of the file.
+fn ino_of(path: &str) -> Result<u64, Errno> {
+    let fd = open(path, OpenFlags::RDONLY)?;
+    let mut stat = Stat::default();
+    fstat(fd, &mut stat).unwrap();
+    close(fd).unwrap();
+    Ok(stat.ino)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("mntparent\0"), Ok(()));
+    assert_eq!(mkdir("mntparent/mntdir\0"), Ok(()));
+    let point = ino_of("mntparent/mntdir\0").unwrap();
+    assert_eq!(mount("mntparent/mntdir\0", "nofs\0"), Err(Errno::ENODEV));
+    assert_eq!(mount("mntparent/nodir\0", "tmpfs\0"), Err(Errno::ENOENT));
+    // the block device only holds the root file system
+    assert_eq!(mount("mntparent/mntdir\0", "easyfs\0"), Err(Errno::EBUSY));
+
+    // an empty file system covers the mount point
+    assert_eq!(mount("mntparent/mntdir\0", "tmpfs\0"), Ok(()));
+    assert_ne!(ino_of("mntparent/mntdir\0"), Ok(point));
+    assert_eq!(ino_of("mntparent/mntdir/hello_world\0"), Err(Errno::ENOENT));
+    // `..` leaves the mount for the parent of the mount point
+    assert_eq!(ino_of("mntparent/mntdir/..\0"), ino_of("mntparent\0"));
+    let fd = open("mntparent/mntdir/mntfile\0", OpenFlags::CREATE).unwrap();
+    close(fd).unwrap();
+    assert!(ino_of("mntparent/mntdir/mntfile\0").is_ok());
+
+    // mount points are busy, and names do not move across mounts
+    assert_eq!(rmdir("mntparent/mntdir\0"), Err(Errno::EBUSY));
+    assert_eq!(rename("mntparent\0", "mntparent2\0"), Ok(()));
+    assert_eq!(rename("mntparent2\0", "mntparent\0"), Ok(()));
+    assert_eq!(
+        rename("mntparent/mntdir\0", "mntparent/mntdir2\0"),
+        Err(Errno::EBUSY)
+    );
+    assert_eq!(mount("mntparent/mntdir\0", "tmpfs\0"), Err(Errno::EBUSY));
+    assert_eq!(mount("/\0", "tmpfs\0"), Err(Errno::EBUSY));
+    assert_eq!(
+        link("mntparent/mntdir/mntfile\0", "mntparent/mntlink\0"),
+        Err(Errno::EXDEV)
+    );
+    assert_eq!(
+        rename("mntparent/mntdir/mntfile\0", "mntparent/mntfile\0"),
+        Err(Errno::EXDEV)
+    );
+    assert_eq!(umount("mntparent\0"), Err(Errno::EINVAL));
+    assert_eq!(umount("/\0"), Err(Errno::EBUSY));
+
+    // only root mounts and unmounts
+    let pid = fork();
+    if pid == 0 {
+        setuid(1000).unwrap();
+        assert_eq!(umount("mntparent/mntdir\0"), Err(Errno::EPERM));
+        assert_eq!(mount("mntparent\0", "tmpfs\0"), Err(Errno::EPERM));
+        return 0;
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), Ok(pid as usize));
+    assert_eq!(exit_code, 0);
+
+    assert_eq!(umount("mntparent/mntdir\0"), Ok(()));
+    assert_eq!(ino_of("mntparent/mntdir\0"), Ok(point));
+    assert_eq!(ino_of("mntparent/mntdir/mntfile\0"), Err(Errno::ENOENT));
+    assert_eq!(rmdir("mntparent/mntdir\0"), Ok(()));
+    assert_eq!(rmdir("mntparent\0"), Ok(()));
+    println!("filetest_mount passed!");
+    0
+}
@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chmod, chown, close, exec, fork, fstat, getegid, geteuid, getpid, getuid, kill, mkdir, open,
+    rmdir, setgid, setuid, unlink, waitpid, Errno, OpenFlags, Stat, S_ISGID, S_ISUID,
+};
+
+/// Mode and owner of the file.
+fn owner_of(path: &str) -> (u32, u32, u32) {
+    let fd = open(path, OpenFlags::RDONLY).unwrap();
+    let mut stat = Stat::default();
+    fstat(fd, &mut stat).unwrap();
+    close(fd).unwrap();
+    (stat.perm, stat.uid, stat.gid)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!((getuid(), geteuid()), (0, 0));
+    // a directory shared by all, where only owners remove their files
+    assert_eq!(mkdir("permdir\0"), Ok(()));
+    assert_eq!(owner_of("permdir\0"), (0o755, 0, 0));
+    assert_eq!(chmod("permdir\0", 0o1777), Ok(()));
+    let fd = open("permdir/rootfile\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    close(fd).unwrap();
+    assert_eq!(owner_of("permdir/rootfile\0"), (0o644, 0, 0));
+
+    let parent = getpid() as usize;
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(setgid(100), Ok(()));
+        assert_eq!(setuid(1000), Ok(()));
+        assert_eq!((getuid(), geteuid(), getegid()), (1000, 1000, 100));
+        assert_eq!(setuid(0), Err(Errno::EPERM));
+
+        // apps can be read and run but not overwritten
+        assert_eq!(open("/user_shell\0", OpenFlags::WRONLY), Err(Errno::EACCES));
+        assert_eq!(
+            open("/user_shell\0", OpenFlags::CREATE | OpenFlags::TRUNC),
+            Err(Errno::EACCES)
+        );
+        close(open("/user_shell\0", OpenFlags::RDONLY).unwrap()).unwrap();
+        assert_eq!(unlink("/user_shell\0"), Err(Errno::EACCES));
+        assert_eq!(chmod("/user_shell\0", 0o777), Err(Errno::EPERM));
+        assert_eq!(chown("/user_shell\0", 1000, 100), Err(Errno::EPERM));
+        assert_eq!(
+            open("/permfile\0", OpenFlags::CREATE | OpenFlags::WRONLY),
+            Err(Errno::EACCES)
+        );
+
+        // files created belong to the user
+        let fd = open("permdir/userfile\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+        close(fd).unwrap();
+        assert_eq!(owner_of("permdir/userfile\0"), (0o644, 1000, 100));
+        assert_eq!(chmod("permdir/userfile\0", 0o600), Ok(()));
+        assert_eq!(chown("permdir/userfile\0", 0, 0), Err(Errno::EPERM));
+        assert_eq!(chown("permdir/userfile\0", u32::MAX, 0), Err(Errno::EPERM));
+        // the owner gives the file to its group, which drops setuid and setgid
+        assert_eq!(
+            chmod("permdir/userfile\0", 0o600 | S_ISUID | S_ISGID),
+            Ok(())
+        );
+        assert_eq!(chown("permdir/userfile\0", 1000, 100), Ok(()));
+        assert_eq!(owner_of("permdir/userfile\0"), (0o600, 1000, 100));
+        // signals go to the processes of the same user only
+        assert_eq!(kill(getpid() as usize, 0), Ok(()));
+        assert_eq!(kill(parent, 0), Err(Errno::EPERM));
+        assert_eq!(
+            open("permdir/rootfile\0", OpenFlags::WRONLY),
+            Err(Errno::EACCES)
+        );
+        assert_eq!(unlink("permdir/rootfile\0"), Err(Errno::EPERM));
+        exec("/hello_world\0", &[core::ptr::null::<u8>()]).unwrap();
+        unreachable!();
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), Ok(pid as usize));
+    assert_eq!(exit_code, 0);
+
+    // root passes the checks
+    assert_eq!(owner_of("permdir/userfile\0"), (0o600, 1000, 100));
+    assert_eq!(chown("permdir/userfile\0", u32::MAX, 0), Ok(()));
+    assert_eq!(owner_of("permdir/userfile\0"), (0o600, 1000, 0));
+    assert_eq!(unlink("permdir/userfile\0"), Ok(()));
+    assert_eq!(unlink("permdir/rootfile\0"), Ok(()));
+    assert_eq!(rmdir("permdir\0"), Ok(()));
+    println!("filetest_perm passed!");
+    0
+}
@@ -4,26 +4,27 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{close, open, read, write, OpenFlags};
+use user_lib::{close, open, read, write, Errno, OpenFlags};
 
 #[no_mangle]
 pub fn main() -> i32 {
     let test_str = "Hello, world!";
     let filea = "filea\0";
-    let fd = open(filea, OpenFlags::CREATE | OpenFlags::WRONLY);
-    assert!(fd > 0);
-    let fd = fd as usize;
-    write(fd, test_str.as_bytes());
-    close(fd);
+    let fd = open(filea, OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    write(fd, test_str.as_bytes()).unwrap();
+    close(fd).unwrap();
 
-    let fd = open(filea, OpenFlags::RDONLY);
-    assert!(fd > 0);
-    let fd = fd as usize;
+    let fd = open(filea, OpenFlags::RDONLY).unwrap();
     let mut buffer = [0u8; 100];
-    let read_len = read(fd, &mut buffer) as usize;
-    close(fd);
+    let read_len = read(fd, &mut buffer).unwrap();
+    close(fd).unwrap();
 
     assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap(),);
+
+    // a missing file and a closed fd fail differently
+    assert_eq!(open("nofile\0", OpenFlags::RDONLY), Err(Errno::ENOENT));
+    assert_eq!(read(fd, &mut buffer), Err(Errno::EBADF));
+    assert_eq!(close(fd), Err(Errno::EBADF));
     println!("file_test passed!");
     0
 }
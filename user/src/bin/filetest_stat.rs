@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, fsync, lseek, open, pipe, pread, pwrite, read, unlink, write, Errno, OpenFlags,
+    Stat, StatMode, SEEK_CUR, SEEK_END, SEEK_SET,
+};
+
+fn stat_of(fd: usize) -> Stat {
+    let mut stat = Stat::default();
+    fstat(fd, &mut stat).unwrap();
+    stat
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let name = "statfile\0";
+    let fd = open(name, OpenFlags::CREATE | OpenFlags::RDWR).unwrap();
+    let stat = stat_of(fd);
+    assert_eq!(stat.mode, StatMode::FILE);
+    assert_eq!((stat.size, stat.blocks, stat.nlink), (0, 0, 1));
+    assert!(stat.ino > 0);
+    assert!(stat.mtime > 0 && stat.ctime == stat.mtime);
+
+    // the offset moves with reads and writes, and can be moved past the end
+    assert_eq!(write(fd, b"0123456789"), Ok(10));
+    assert_eq!(lseek(fd, 0, SEEK_CUR), Ok(10));
+    assert_eq!(lseek(fd, -4, SEEK_END), Ok(6));
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fd, &mut buf), Ok(4));
+    assert_eq!(&buf[..4], b"6789");
+    assert_eq!(lseek(fd, -1, SEEK_SET), Err(Errno::EINVAL));
+    assert_eq!(lseek(fd, 0, 3), Err(Errno::EINVAL));
+    assert_eq!(lseek(fd, 1000, SEEK_SET), Ok(1000));
+    assert_eq!(write(fd, b"end"), Ok(3));
+    let stat = stat_of(fd);
+    assert_eq!((stat.size, stat.blocks), (1003, 2));
+    assert_eq!(pread(fd, &mut buf, 10), Ok(16));
+    assert!(buf.iter().all(|byte| *byte == 0));
+
+    // positional accesses leave the offset unchanged
+    assert_eq!(lseek(fd, 2, SEEK_SET), Ok(2));
+    assert_eq!(pwrite(fd, b"ab", 0), Ok(2));
+    assert_eq!(pread(fd, &mut buf[..4], 999), Ok(4));
+    assert_eq!(&buf[..4], b"\0end");
+    assert_eq!(pread(fd, &mut buf, 2000), Ok(0));
+    assert_eq!(read(fd, &mut buf[..2]), Ok(2));
+    assert_eq!(&buf[..2], b"23");
+    let fd_read = open(name, OpenFlags::RDONLY).unwrap();
+    assert_eq!(pread(fd_read, &mut buf[..2], 0), Ok(2));
+    assert_eq!(&buf[..2], b"ab");
+    assert_eq!(pwrite(fd_read, b"ab", 0), Err(Errno::EBADF));
+    assert_eq!(stat_of(fd_read).ino, stat_of(fd).ino);
+    assert_eq!(fsync(fd_read), Ok(()));
+    close(fd_read).unwrap();
+    close(fd).unwrap();
+
+    // directories, pipes and stdio
+    let fd = open(".\0", OpenFlags::RDONLY).unwrap();
+    assert_eq!(stat_of(fd).mode, StatMode::DIR);
+    close(fd).unwrap();
+    let mut fds = [0usize; 2];
+    pipe(&mut fds).unwrap();
+    assert_eq!(stat_of(fds[0]).mode, StatMode::FIFO);
+    assert_eq!(lseek(fds[0], 0, SEEK_SET), Err(Errno::ESPIPE));
+    assert_eq!(pread(fds[0], &mut buf, 0), Err(Errno::ESPIPE));
+    assert_eq!(fsync(fds[1]), Err(Errno::EINVAL));
+    close(fds[0]).unwrap();
+    close(fds[1]).unwrap();
+    assert_eq!(stat_of(1).mode, StatMode::CHR);
+    assert_eq!(fstat(fds[0], &mut Stat::default()), Err(Errno::EBADF));
+    unlink(name).unwrap();
+    println!("filetest_stat passed!");
+    0
+}
@@ -0,0 +1,124 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exec, fork, link, lstat, mkdir, open, read, readlink, rmdir, symlink, unlink, waitpid,
+    write, Errno, OpenFlags, Stat, StatMode,
+};
+
+fn read_str<'a>(path: &str, buffer: &'a mut [u8]) -> Result<&'a str, Errno> {
+    let fd = open(path, OpenFlags::RDONLY)?;
+    let read_len = read(fd, buffer).unwrap();
+    close(fd).unwrap();
+    Ok(core::str::from_utf8(&buffer[..read_len]).unwrap())
+}
+
+/// The type of the file at `path`, or of the link there unless `follow`.
+fn mode_of(path: &str, follow: bool) -> Result<StatMode, Errno> {
+    let mut stat = Stat::default();
+    if follow {
+        user_lib::stat(path, &mut stat)?;
+    } else {
+        lstat(path, &mut stat)?;
+    }
+    Ok(stat.mode)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, symlink!";
+    let fd = open("symfile\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    write(fd, test_str.as_bytes()).unwrap();
+    close(fd).unwrap();
+
+    // a link is followed on open, and holds its target as it is
+    let mut buffer = [0u8; 100];
+    assert_eq!(symlink("symfile\0", "symlinka\0"), Ok(()));
+    assert_eq!(symlink("symfile\0", "symlinka\0"), Err(Errno::EEXIST));
+    assert_eq!(symlink("\0", "symlinkb\0"), Err(Errno::ENOENT));
+    assert_eq!(read_str("symlinka\0", &mut buffer), Ok(test_str));
+    assert_eq!(readlink("symlinka\0", &mut buffer), Ok(7));
+    assert_eq!(&buffer[..7], b"symfile");
+    assert_eq!(readlink("symlinka\0", &mut buffer[..3]), Ok(3));
+    assert_eq!(&buffer[..3], b"sym");
+    assert_eq!(readlink("symfile\0", &mut buffer), Err(Errno::EINVAL));
+    assert_eq!(readlink("symlinkb\0", &mut buffer), Err(Errno::ENOENT));
+
+    // a hard link to a symbolic link is a link to the same target
+    assert_eq!(link("symlinka\0", "symlinkb\0"), Ok(()));
+    assert_eq!(readlink("symlinkb\0", &mut buffer), Ok(7));
+    assert_eq!(unlink("symlinkb\0"), Ok(()));
+
+    // links to directories are followed in the middle of a path
+    assert_eq!(mkdir("symdir\0"), Ok(()));
+    assert_eq!(symlink("symdir\0", "symdirlink\0"), Ok(()));
+    let fd = open("symdirlink/file\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    write(fd, test_str.as_bytes()).unwrap();
+    close(fd).unwrap();
+    assert_eq!(read_str("symdir/file\0", &mut buffer), Ok(test_str));
+    assert_eq!(symlink("../symfile\0", "symdir/up\0"), Ok(()));
+    assert_eq!(read_str("symdirlink/up\0", &mut buffer), Ok(test_str));
+
+    // dangling links and loops
+    assert_eq!(symlink("nowhere\0", "symdangling\0"), Ok(()));
+    assert_eq!(read_str("symdangling\0", &mut buffer), Err(Errno::ENOENT));
+    assert_eq!(symlink("symloopb\0", "symloopa\0"), Ok(()));
+    assert_eq!(symlink("symloopa\0", "symloopb\0"), Ok(()));
+    assert_eq!(read_str("symloopa\0", &mut buffer), Err(Errno::ELOOP));
+    assert_eq!(read_str("symloopa/file\0", &mut buffer), Err(Errno::ELOOP));
+    assert_eq!(mode_of("symloopa\0", false), Ok(StatMode::LNK));
+    assert_eq!(mode_of("symloopa\0", true), Err(Errno::ELOOP));
+    assert_eq!(open("symloopa\0", OpenFlags::CREATE), Err(Errno::ELOOP));
+
+    // creating through a dangling link creates its target, if the directory
+    // of the target exists
+    assert_eq!(mode_of("symdangling\0", false), Ok(StatMode::LNK));
+    assert_eq!(mode_of("symdangling\0", true), Err(Errno::ENOENT));
+    let fd = open("symdangling\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    write(fd, test_str.as_bytes()).unwrap();
+    close(fd).unwrap();
+    assert_eq!(read_str("nowhere\0", &mut buffer), Ok(test_str));
+    assert_eq!(mode_of("symdangling\0", false), Ok(StatMode::LNK));
+    assert_eq!(mode_of("symdangling\0", true), Ok(StatMode::FILE));
+    assert_eq!(symlink("nodir/nowhere\0", "symdangling2\0"), Ok(()));
+    assert_eq!(
+        open("symdangling2\0", OpenFlags::CREATE),
+        Err(Errno::ENOENT)
+    );
+
+    // another name of an app
+    assert_eq!(symlink("/hello_world\0", "symhello\0"), Ok(()));
+    let pid = fork();
+    if pid == 0 {
+        exec("symhello\0", &[core::ptr::null::<u8>()]).unwrap();
+        unreachable!();
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), Ok(pid as usize));
+    assert_eq!(exit_code, 0);
+
+    // removing a link leaves its target
+    for path in [
+        "symlinka\0",
+        "symdirlink\0",
+        "symdir/up\0",
+        "symdir/file\0",
+        "symdangling\0",
+        "symdangling2\0",
+        "nowhere\0",
+        "symloopa\0",
+        "symloopb\0",
+        "symhello\0",
+    ] {
+        assert_eq!(unlink(path), Ok(()));
+    }
+    assert_eq!(read_str("symfile\0", &mut buffer), Ok(test_str));
+    assert_eq!(read_str("symdir/up\0", &mut buffer), Err(Errno::ENOENT));
+    assert_eq!(rmdir("symdir\0"), Ok(()));
+    assert_eq!(unlink("symfile\0"), Ok(()));
+    println!("filetest_symlink passed!");
+    0
+}
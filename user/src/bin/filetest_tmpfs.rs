@@ -0,0 +1,150 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chdir, close, fstat, ftruncate, link, lstat, mkdir, mount, open, pread, pwrite, readlink,
+    rename, rmdir, symlink, umount, unlink, write, Errno, OpenFlags, Stat, StatMode,
+};
+
+fn stat_of(fd: usize) -> Stat {
+    let mut stat = Stat::default();
+    fstat(fd, &mut stat).unwrap();
+    stat
+}
+
+/// Size and blocks of the file.
+fn size_of(fd: usize) -> (u64, u64) {
+    let stat = stat_of(fd);
+    (stat.size, stat.blocks)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // initproc mounts a tmpfs on /tmp which anyone may write
+    let fd = open("/tmp\0", OpenFlags::RDONLY).unwrap();
+    assert_eq!(stat_of(fd).perm, 0o1777);
+    close(fd).unwrap();
+
+    // data in pages of 4096 bytes, holes take none
+    let fd = open("/tmp/tmpfile\0", OpenFlags::CREATE | OpenFlags::RDWR).unwrap();
+    let mut data = [0u8; 10000];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    assert_eq!(pwrite(fd, &data, 0), Ok(10000));
+    assert_eq!(size_of(fd), (10000, 24));
+    let mut buf = [0xffu8; 10000];
+    assert_eq!(pread(fd, &mut buf, 0), Ok(10000));
+    assert_eq!(buf, data);
+    assert_eq!(pwrite(fd, b"x", 1 << 20), Ok(1));
+    assert_eq!(size_of(fd), ((1 << 20) + 1, 32));
+    assert_eq!(pread(fd, &mut buf[..100], 1 << 19), Ok(100));
+    assert!(buf[..100].iter().all(|byte| *byte == 0));
+
+    // the part cut off reads as zeros when the file is extended again
+    assert_eq!(ftruncate(fd, 5000), Ok(()));
+    assert_eq!(size_of(fd), (5000, 16));
+    assert_eq!(ftruncate(fd, 10000), Ok(()));
+    assert_eq!(pread(fd, &mut buf, 0), Ok(10000));
+    assert_eq!(buf[..5000], data[..5000]);
+    assert!(buf[5000..].iter().all(|byte| *byte == 0));
+    assert_eq!(ftruncate(fd, 1 << 40), Err(Errno::EFBIG));
+
+    // an unlinked file is kept until it is closed
+    assert_eq!(unlink("/tmp/tmpfile\0"), Ok(()));
+    assert_eq!(pread(fd, &mut buf[..10], 0), Ok(10));
+    assert_eq!(buf[..10], data[..10]);
+    close(fd).unwrap();
+    assert_eq!(
+        open("/tmp/tmpfile\0", OpenFlags::RDONLY),
+        Err(Errno::ENOENT)
+    );
+
+    // directories and links
+    assert_eq!(mkdir("/tmp/tmpdir\0"), Ok(()));
+    let fd = open("/tmp/tmpdir/a\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    assert_eq!(pwrite(fd, b"hello", 0), Ok(5));
+    close(fd).unwrap();
+    assert_eq!(rmdir("/tmp/tmpdir\0"), Err(Errno::ENOTEMPTY));
+    assert_eq!(link("/tmp/tmpdir/a\0", "/tmp/b\0"), Ok(()));
+    let fd = open("/tmp/b\0", OpenFlags::RDONLY).unwrap();
+    assert_eq!(stat_of(fd).nlink, 2);
+    close(fd).unwrap();
+    assert_eq!(symlink("tmpdir/a\0", "/tmp/c\0"), Ok(()));
+    assert_eq!(readlink("/tmp/c\0", &mut buf), Ok(8));
+    assert_eq!(&buf[..8], b"tmpdir/a");
+    let mut stat = Stat::default();
+    assert_eq!(lstat("/tmp/c\0", &mut stat), Ok(()));
+    assert_eq!(stat.mode, StatMode::LNK);
+    let fd = open("/tmp/c\0", OpenFlags::RDONLY).unwrap();
+    assert_eq!(pread(fd, &mut buf[..5], 0), Ok(5));
+    assert_eq!(&buf[..5], b"hello");
+    close(fd).unwrap();
+    assert_eq!(rename("/tmp/tmpdir/a\0", "/tmp/a\0"), Ok(()));
+    assert_eq!(rename("/tmp/a\0", "/tmpfile_moved\0"), Err(Errno::EXDEV));
+    assert_eq!(
+        rename("/tmp/tmpdir\0", "/tmp/tmpdir/sub\0"),
+        Err(Errno::EINVAL)
+    );
+    assert_eq!(rmdir("/tmp/tmpdir\0"), Ok(()));
+    for path in ["/tmp/a\0", "/tmp/b\0", "/tmp/c\0"] {
+        assert_eq!(unlink(path), Ok(()));
+    }
+
+    // open files and working directories keep a file system mounted, even
+    // files unlinked
+    assert_eq!(mkdir("/tmp/inner\0"), Ok(()));
+    assert_eq!(mount("/tmp/inner\0", "tmpfs\0"), Ok(()));
+    let fd = open("/tmp/inner/file\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    assert_eq!(unlink("/tmp/inner/file\0"), Ok(()));
+    assert_eq!(umount("/tmp/inner\0"), Err(Errno::EBUSY));
+    close(fd).unwrap();
+    let fd = open("/tmp/inner\0", OpenFlags::RDONLY).unwrap();
+    assert_eq!(umount("/tmp/inner\0"), Err(Errno::EBUSY));
+    close(fd).unwrap();
+    assert_eq!(mkdir("/tmp/inner/dir\0"), Ok(()));
+    assert_eq!(chdir("/tmp/inner/dir\0"), Ok(()));
+    assert_eq!(umount("/tmp/inner\0"), Err(Errno::EBUSY));
+    assert_eq!(chdir("/\0"), Ok(()));
+
+    // each tmpfs holds a limited number of pages, the write filling it is cut
+    // short and later ones fail
+    let fd = open("/tmp/inner/big\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    let mut size = 0;
+    let errno = loop {
+        match write(fd, &data) {
+            Ok(written) => size += written,
+            Err(errno) => break errno,
+        }
+    };
+    assert_eq!(errno, Errno::ENOSPC);
+    assert_eq!(size % 4096, 0);
+    assert_eq!(pwrite(fd, &data[..10], 0), Ok(10));
+    let fd2 = open("/tmp/inner/small\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    assert_eq!(pwrite(fd2, b"x", 0), Err(Errno::ENOSPC));
+    assert_eq!(size_of(fd2), (0, 0));
+    // pages cut off or unlinked are given back
+    assert_eq!(ftruncate(fd, size - 4096), Ok(()));
+    assert_eq!(pwrite(fd2, b"x", 0), Ok(1));
+    close(fd).unwrap();
+    close(fd2).unwrap();
+    assert_eq!(unlink("/tmp/inner/big\0"), Ok(()));
+    assert_eq!(unlink("/tmp/inner/small\0"), Ok(()));
+
+    // everything is gone with the file system once it is unmounted
+    let fd = open("/tmp/inner/file\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    assert_eq!(pwrite(fd, &data, 0), Ok(10000));
+    close(fd).unwrap();
+    assert_eq!(umount("/tmp\0"), Err(Errno::EBUSY));
+    assert_eq!(umount("/tmp/inner\0"), Ok(()));
+    assert_eq!(
+        open("/tmp/inner/file\0", OpenFlags::RDONLY),
+        Err(Errno::ENOENT)
+    );
+    assert_eq!(rmdir("/tmp/inner\0"), Ok(()));
+    println!("filetest_tmpfs passed!");
+    0
+}
@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, ftruncate, lseek, open, pipe, pread, pwrite, unlink, write, Errno, OpenFlags,
+    Stat, SEEK_CUR,
+};
+
+/// Size and blocks of the file.
+fn size_of(fd: usize) -> (u64, u64) {
+    let mut stat = Stat::default();
+    fstat(fd, &mut stat).unwrap();
+    (stat.size, stat.blocks)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let name = "truncfile\0";
+    let fd = open(name, OpenFlags::CREATE | OpenFlags::RDWR).unwrap();
+    assert_eq!(write(fd, b"0123456789"), Ok(10));
+
+    // the part cut off reads as zeros when the file is extended again
+    assert_eq!(ftruncate(fd, 4), Ok(()));
+    assert_eq!(size_of(fd), (4, 1));
+    assert_eq!(ftruncate(fd, 10), Ok(()));
+    let mut buf = [0xffu8; 16];
+    assert_eq!(pread(fd, &mut buf, 0), Ok(10));
+    assert_eq!(&buf[..10], b"0123\0\0\0\0\0\0");
+    assert_eq!(lseek(fd, 0, SEEK_CUR), Ok(10));
+
+    // only the blocks written are allocated
+    assert_eq!(ftruncate(fd, 1 << 20), Ok(()));
+    assert_eq!(size_of(fd), (1 << 20, 1));
+    assert_eq!(pwrite(fd, b"x", 1 << 20), Ok(1));
+    // the data block with an indirect2 and an indirect1 block
+    assert_eq!(size_of(fd), ((1 << 20) + 1, 4));
+    assert_eq!(pread(fd, &mut buf, 100 * 512), Ok(16));
+    assert!(buf.iter().all(|byte| *byte == 0));
+    assert_eq!(pwrite(fd, b"y", 100 * 512), Ok(1));
+    assert_eq!(size_of(fd), ((1 << 20) + 1, 6));
+    assert_eq!(ftruncate(fd, 101 * 512), Ok(()));
+    assert_eq!(size_of(fd), (101 * 512, 3));
+    assert_eq!(pread(fd, &mut buf[..1], 100 * 512), Ok(1));
+    assert_eq!(buf[0], b'y');
+    assert_eq!(ftruncate(fd, 1 << 40), Err(Errno::EFBIG));
+    assert_eq!(ftruncate(fd, 0), Ok(()));
+    assert_eq!(size_of(fd), (0, 0));
+
+    // only files opened for writing
+    let fd_read = open(name, OpenFlags::RDONLY).unwrap();
+    assert_eq!(ftruncate(fd_read, 0), Err(Errno::EINVAL));
+    close(fd_read).unwrap();
+    let mut fds = [0usize; 2];
+    pipe(&mut fds).unwrap();
+    assert_eq!(ftruncate(fds[1], 0), Err(Errno::EINVAL));
+    close(fds[0]).unwrap();
+    close(fds[1]).unwrap();
+    assert_eq!(ftruncate(fds[1], 0), Err(Errno::EBADF));
+    close(fd).unwrap();
+    unlink(name).unwrap();
+    println!("filetest_truncate passed!");
+    0
+}
@@ -22,11 +22,11 @@ pub fn main() -> i32 {
     }
     let mut exit_code: i32 = 0;
     for _ in 0..MAX_CHILD {
-        if wait(&mut exit_code) <= 0 {
+        if wait(&mut exit_code).is_err() {
             panic!("wait stopped early");
         }
     }
-    if wait(&mut exit_code) > 0 {
+    if wait(&mut exit_code).is_ok() {
         panic!("wait got too many");
     }
     println!("forktest pass.");
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, pipe, read, wait, waitpid, write};
+
+const PAGES: usize = 16;
+const PAGE_SIZE: usize = 4096;
+
+static mut DATA: [u8; PAGES * PAGE_SIZE] = [0u8; PAGES * PAGE_SIZE];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let data = unsafe { &mut DATA };
+    for (i, page) in data.chunks_mut(PAGE_SIZE).enumerate() {
+        page.fill(i as u8);
+    }
+
+    // stores of the child are invisible to the parent and vice versa
+    let pid = fork();
+    if pid == 0 {
+        for page in data.chunks_mut(PAGE_SIZE).step_by(2) {
+            page.fill(0xff);
+        }
+        for (i, page) in data.chunks(PAGE_SIZE).enumerate() {
+            let expected = if i % 2 == 0 { 0xff } else { i as u8 };
+            assert!(page.iter().all(|byte| *byte == expected));
+        }
+        exit(0);
+    }
+    data[PAGE_SIZE] = 0xee;
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), Ok(pid as usize));
+    assert_eq!(exit_code, 0);
+    assert_eq!(data[PAGE_SIZE], 0xee);
+    data[PAGE_SIZE] = 1;
+    for (i, page) in data.chunks(PAGE_SIZE).enumerate() {
+        assert!(page.iter().all(|byte| *byte == i as u8));
+    }
+
+    // the kernel also writes a private copy of a shared page
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd).unwrap();
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[1]).unwrap();
+        assert_eq!(read(pipe_fd[0], &mut data[..PAGE_SIZE]), Ok(4));
+        close(pipe_fd[0]).unwrap();
+        assert_eq!(&data[..4], b"cow!");
+        exit(0);
+    }
+    close(pipe_fd[0]).unwrap();
+    write(pipe_fd[1], b"cow!").unwrap();
+    close(pipe_fd[1]).unwrap();
+    assert_eq!(wait(&mut exit_code), Ok(pid as usize));
+    assert_eq!(exit_code, 0);
+    assert!(data[..PAGE_SIZE].iter().all(|byte| *byte == 0));
+    println!("forktest_cow passed!");
+    0
+}
@@ -29,7 +29,7 @@ fn fork_tree(cur: &str) {
     fork_child(cur, '1');
     let mut exit_code: i32 = 0;
     for _ in 0..2 {
-        wait(&mut exit_code);
+        wait(&mut exit_code).unwrap();
     }
 }
 
@@ -38,7 +38,7 @@ pub fn main() -> i32 {
     fork_tree("");
     let mut exit_code: i32 = 0;
     for _ in 0..2 {
-        wait(&mut exit_code);
+        wait(&mut exit_code).unwrap();
     }
     sleep(3000);
     0
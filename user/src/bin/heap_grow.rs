@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use user_lib::{brk, exit, fork, sbrk, waitpid, Errno};
+
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // far more than the old 32 KiB static heap
+    let mut large: Vec<usize> = (0..256 * 1024).collect();
+    assert!(large.iter().enumerate().all(|(i, value)| i == *value));
+    let boxes: Vec<Box<[u8; 1000]>> = (0..200).map(|i| Box::new([i as u8; 1000])).collect();
+    assert!(boxes
+        .iter()
+        .enumerate()
+        .all(|(i, boxed)| boxed.iter().all(|byte| *byte == i as u8)));
+
+    // the grown heap is copied by fork
+    let pid = fork();
+    if pid == 0 {
+        large.iter_mut().for_each(|value| *value = 0);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), Ok(pid as usize));
+    assert_eq!(exit_code, 0);
+    assert_eq!(large[1000], 1000);
+    drop(large);
+    drop(boxes);
+
+    // grow and shrink the break by hand
+    let old_brk = sbrk(0).unwrap();
+    assert!(old_brk > 0);
+    assert_eq!(sbrk(2 * PAGE_SIZE as isize), Ok(old_brk));
+    let page = unsafe { core::slice::from_raw_parts_mut(old_brk as *mut u8, 2 * PAGE_SIZE) };
+    page.fill(0x42);
+    assert_eq!(brk(old_brk), old_brk as isize);
+    assert_eq!(sbrk(0), Ok(old_brk));
+    assert_eq!(sbrk(PAGE_SIZE as isize), Ok(old_brk));
+    let page = unsafe { core::slice::from_raw_parts(old_brk as *const u8, PAGE_SIZE) };
+    assert!(page.iter().all(|byte| *byte == 0));
+    assert_eq!(brk(0), (old_brk + PAGE_SIZE) as isize);
+    assert_eq!(sbrk(isize::MIN / 2), Err(Errno::ENOMEM));
+    println!("heap_grow passed!");
+    0
+}
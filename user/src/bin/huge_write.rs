@@ -4,7 +4,7 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{close, get_time, open, write, OpenFlags};
+use user_lib::{close, fsync, get_time, open, write, OpenFlags};
 
 #[no_mangle]
 pub fn main() -> i32 {
@@ -12,17 +12,17 @@ pub fn main() -> i32 {
     for (i, ch) in buffer.iter_mut().enumerate() {
         *ch = i as u8;
     }
-    let f = open("testf\0", OpenFlags::CREATE | OpenFlags::WRONLY);
-    if f < 0 {
-        panic!("Open test file failed!");
-    }
-    let f = f as usize;
+    let f = match open("testf\0", OpenFlags::CREATE | OpenFlags::WRONLY) {
+        Ok(f) => f,
+        Err(errno) => panic!("Open test file failed: {:?}", errno),
+    };
     let start = get_time();
     let size_mb = 1usize;
     for _ in 0..1024 * size_mb {
-        write(f, &buffer);
+        write(f, &buffer).unwrap();
     }
-    close(f);
+    fsync(f).unwrap();
+    close(f).unwrap();
     let time_ms = (get_time() - start) as usize;
     let speed_kbs = (size_mb << 20) / time_ms;
     println!(
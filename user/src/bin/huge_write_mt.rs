@@ -15,15 +15,14 @@ fn worker(size_kib: usize) {
         *ch = i as u8;
     }
     let filename = format(format_args!("testf{}\0", gettid()));
-    let f = open(filename.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY);
-    if f < 0 {
-        panic!("Open test file failed!");
-    }
-    let f = f as usize;
+    let f = match open(filename.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY) {
+        Ok(f) => f,
+        Err(errno) => panic!("Open test file failed: {:?}", errno),
+    };
     for _ in 0..size_kib {
-        write(f, &buffer);
+        write(f, &buffer).unwrap();
     }
-    close(f);
+    close(f).unwrap();
     exit(0)
 }
 
@@ -1,19 +1,25 @@
 #![no_std]
 #![no_main]
 
+#[macro_use]
 extern crate user_lib;
 
-use user_lib::{exec, fork, wait, yield_};
+use user_lib::{exec, fork, mkdir, mount, wait, yield_};
 
 #[no_mangle]
 fn main() -> i32 {
+    // scratch files in /tmp stay in memory and are gone on the next boot
+    let _ = mkdir("tmp\0");
+    if let Err(errno) = mount("tmp\0", "tmpfs\0") {
+        println!("[initproc] failed to mount /tmp: {:?}", errno);
+    }
     if fork() == 0 {
-        exec("user_shell\0", &[core::ptr::null::<u8>()]);
+        exec("user_shell\0", &[core::ptr::null::<u8>()]).unwrap();
     } else {
         loop {
             let mut exit_code: i32 = 0;
             let pid = wait(&mut exit_code);
-            if pid == -1 {
+            if pid.is_err() {
                 yield_();
                 continue;
             }
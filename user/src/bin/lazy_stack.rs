@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::mem::MaybeUninit;
+use user_lib::{close, pipe, read, write};
+
+/// Use about 48 KiB of stack, far more than the pages touched at startup.
+#[inline(never)]
+fn deep(depth: usize) -> usize {
+    let buffer = [depth as u8; 512];
+    // keep the buffer on the stack
+    let last = unsafe { core::ptr::read_volatile(&buffer[511]) };
+    if depth == 0 {
+        last as usize
+    } else {
+        deep(depth - 1) + last as usize
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(deep(80), (1..=80).sum::<usize>());
+
+    // the kernel writes into stack pages never touched by the program
+    let mut buffer: MaybeUninit<[u8; 16384]> = MaybeUninit::uninit();
+    let buffer = unsafe { &mut *buffer.as_mut_ptr() };
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd).unwrap();
+    assert_eq!(write(pipe_fd[1], b"lazy"), Ok(4));
+    close(pipe_fd[1]).unwrap();
+    assert_eq!(read(pipe_fd[0], &mut buffer[12288..]), Ok(4));
+    close(pipe_fd[0]).unwrap();
+    assert_eq!(&buffer[12288..12292], b"lazy");
+    println!("lazy_stack passed!");
+    0
+}
@@ -0,0 +1,172 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::slice;
+use user_lib::{
+    close, exit, fork, mmap, mprotect, munmap, open, pread, read, unlink, waitpid, write, Errno,
+    MapFlags, OpenFlags, ProtFlags,
+};
+
+const PAGE_SIZE: usize = 4096;
+
+fn as_bytes(addr: usize, len: usize) -> &'static mut [u8] {
+    assert!(addr > 0);
+    unsafe { slice::from_raw_parts_mut(addr as *mut u8, len) }
+}
+
+fn wait_exit_code(pid: isize) -> i32 {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), Ok(pid as usize));
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let rw = ProtFlags::READ | ProtFlags::WRITE;
+    let anonymous = MapFlags::PRIVATE | MapFlags::ANONYMOUS;
+
+    // anonymous private pages start zeroed and are copied by fork
+    let addr = mmap(0, 4 * PAGE_SIZE, rw, anonymous, 0, 0).unwrap();
+    let private = as_bytes(addr, 4 * PAGE_SIZE);
+    assert!(private.iter().all(|byte| *byte == 0));
+    private.fill(0x5a);
+    let pid = fork();
+    if pid == 0 {
+        private.fill(0xa5);
+        exit(0);
+    }
+    assert_eq!(wait_exit_code(pid), 0);
+    assert!(private.iter().all(|byte| *byte == 0x5a));
+
+    // unmap the middle of the area, then map it again at a fixed address
+    let hole = addr + PAGE_SIZE;
+    assert_eq!(munmap(hole, PAGE_SIZE), Ok(()));
+    assert_eq!(
+        mmap(
+            hole - PAGE_SIZE,
+            PAGE_SIZE,
+            rw,
+            anonymous | MapFlags::FIXED,
+            0,
+            0
+        ),
+        Err(Errno::EEXIST)
+    );
+    assert_eq!(
+        mmap(hole, PAGE_SIZE, rw, anonymous | MapFlags::FIXED, 0, 0),
+        Ok(hole)
+    );
+    assert!(private[PAGE_SIZE..2 * PAGE_SIZE]
+        .iter()
+        .all(|byte| *byte == 0));
+    assert!(private[2 * PAGE_SIZE..].iter().all(|byte| *byte == 0x5a));
+
+    // a read-only page faults on writes
+    assert_eq!(mprotect(addr, PAGE_SIZE, ProtFlags::READ), Ok(()));
+    assert_eq!(private[0], 0x5a);
+    let pid = fork();
+    if pid == 0 {
+        private[0] = 0;
+        exit(0);
+    }
+    assert_eq!(wait_exit_code(pid), -11);
+    assert_eq!(munmap(addr, 4 * PAGE_SIZE), Ok(()));
+
+    // anonymous shared pages are seen by both processes
+    let addr = mmap(
+        0,
+        PAGE_SIZE,
+        rw,
+        MapFlags::SHARED | MapFlags::ANONYMOUS,
+        0,
+        0,
+    )
+    .unwrap();
+    let shared = as_bytes(addr, PAGE_SIZE);
+    let pid = fork();
+    if pid == 0 {
+        shared[..6].copy_from_slice(b"shared");
+        exit(0);
+    }
+    assert_eq!(wait_exit_code(pid), 0);
+    assert_eq!(&shared[..6], b"shared");
+    assert_eq!(munmap(addr, PAGE_SIZE), Ok(()));
+
+    // a shared file mapping is written back on unmap without growing the file
+    let content = [b'm'; PAGE_SIZE + 100];
+    let fd = open("mmapfile\0", OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    assert_eq!(write(fd, &content), Ok(content.len()));
+    close(fd).unwrap();
+    let fd = open("mmapfile\0", OpenFlags::RDONLY).unwrap();
+    assert_eq!(
+        mmap(0, 2 * PAGE_SIZE, rw, MapFlags::SHARED, fd, 0),
+        Err(Errno::EACCES)
+    );
+    close(fd).unwrap();
+    let fd = open("mmapfile\0", OpenFlags::RDWR).unwrap();
+    let addr = mmap(0, 2 * PAGE_SIZE, rw, MapFlags::SHARED, fd, 0).unwrap();
+    close(fd).unwrap();
+    let file = as_bytes(addr, 2 * PAGE_SIZE);
+    assert_eq!(&file[..content.len()], &content);
+    assert!(file[content.len()..].iter().all(|byte| *byte == 0));
+    file[..4].copy_from_slice(b"mmap");
+    file[PAGE_SIZE + 96..PAGE_SIZE + 104].copy_from_slice(b"tailtail");
+    assert_eq!(munmap(addr, 2 * PAGE_SIZE), Ok(()));
+    let fd = open("mmapfile\0", OpenFlags::RDONLY).unwrap();
+    let mut buffer = [0u8; 2 * PAGE_SIZE];
+    assert_eq!(read(fd, &mut buffer), Ok(content.len()));
+    close(fd).unwrap();
+    assert_eq!(&buffer[..4], b"mmap");
+    assert_eq!(&buffer[PAGE_SIZE + 96..PAGE_SIZE + 100], b"tail");
+
+    // file pages are read on first use, also when the kernel uses them first
+    let fd = open("mmapfile\0", OpenFlags::RDONLY).unwrap();
+    let addr = mmap(0, 2 * PAGE_SIZE, ProtFlags::READ, MapFlags::PRIVATE, fd, 0).unwrap();
+    assert_eq!(
+        mmap(
+            addr,
+            PAGE_SIZE,
+            rw,
+            MapFlags::PRIVATE | MapFlags::FIXED,
+            fd,
+            0
+        ),
+        Err(Errno::EEXIST)
+    );
+    close(fd).unwrap();
+    let copy = open("mmapcopy\0", OpenFlags::CREATE | OpenFlags::RDWR).unwrap();
+    let file = as_bytes(addr, content.len());
+    assert_eq!(write(copy, file), Ok(content.len()));
+    let mut copied = [0u8; PAGE_SIZE + 100];
+    assert_eq!(pread(copy, &mut copied, 0), Ok(content.len()));
+    assert_eq!(copied, buffer[..content.len()]);
+    close(copy).unwrap();
+    assert_eq!(munmap(addr, 2 * PAGE_SIZE), Ok(()));
+    assert_eq!(unlink("mmapcopy\0"), Ok(()));
+
+    // pages of a shared file mapping read after fork are still shared
+    let fd = open("mmapfile\0", OpenFlags::RDWR).unwrap();
+    let addr = mmap(0, 2 * PAGE_SIZE, rw, MapFlags::SHARED, fd, 0).unwrap();
+    close(fd).unwrap();
+    let file = as_bytes(addr, 2 * PAGE_SIZE);
+    let pid = fork();
+    if pid == 0 {
+        file[PAGE_SIZE..PAGE_SIZE + 4].copy_from_slice(b"fork");
+        exit(0);
+    }
+    assert_eq!(wait_exit_code(pid), 0);
+    assert_eq!(&file[PAGE_SIZE..PAGE_SIZE + 4], b"fork");
+    assert_eq!(munmap(addr, 2 * PAGE_SIZE), Ok(()));
+    assert_eq!(unlink("mmapfile\0"), Ok(()));
+
+    // the kernel-only trap context is out of reach
+    assert_eq!(
+        munmap(usize::MAX - PAGE_SIZE + 1, PAGE_SIZE),
+        Err(Errno::EINVAL)
+    );
+    println!("mmap_test passed!");
+    0
+}
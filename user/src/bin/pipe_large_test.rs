@@ -17,46 +17,43 @@ pub fn main() -> i32 {
     let mut down_pipe_fd = [0usize; 2];
     // child write to parent
     let mut up_pipe_fd = [0usize; 2];
-    pipe(&mut down_pipe_fd);
-    pipe(&mut up_pipe_fd);
+    pipe(&mut down_pipe_fd).unwrap();
+    pipe(&mut up_pipe_fd).unwrap();
     let mut random_str = [0u8; LENGTH];
     if fork() == 0 {
         // close write end of down pipe
-        close(down_pipe_fd[1]);
+        close(down_pipe_fd[1]).unwrap();
         // close read end of up pipe
-        close(up_pipe_fd[0]);
-        assert_eq!(read(down_pipe_fd[0], &mut random_str) as usize, LENGTH);
-        close(down_pipe_fd[0]);
+        close(up_pipe_fd[0]).unwrap();
+        assert_eq!(read(down_pipe_fd[0], &mut random_str), Ok(LENGTH));
+        close(down_pipe_fd[0]).unwrap();
         let sum: usize = random_str.iter().map(|v| *v as usize).sum::<usize>();
         println!("sum = {}(child)", sum);
         let sum_str = format!("{}", sum);
-        write(up_pipe_fd[1], sum_str.as_bytes());
-        close(up_pipe_fd[1]);
+        write(up_pipe_fd[1], sum_str.as_bytes()).unwrap();
+        close(up_pipe_fd[1]).unwrap();
         println!("Child process exited!");
         0
     } else {
         // close read end of down pipe
-        close(down_pipe_fd[0]);
+        close(down_pipe_fd[0]).unwrap();
         // close write end of up pipe
-        close(up_pipe_fd[1]);
+        close(up_pipe_fd[1]).unwrap();
         // generate a long random string
         for ch in random_str.iter_mut() {
             *ch = get_time() as u8;
         }
         // send it
-        assert_eq!(
-            write(down_pipe_fd[1], &random_str) as usize,
-            random_str.len()
-        );
+        assert_eq!(write(down_pipe_fd[1], &random_str), Ok(random_str.len()));
         // close write end of down pipe
-        close(down_pipe_fd[1]);
+        close(down_pipe_fd[1]).unwrap();
         // calculate sum(parent)
         let sum: usize = random_str.iter().map(|v| *v as usize).sum::<usize>();
         println!("sum = {}(parent)", sum);
         // recv sum(child)
         let mut child_result = [0u8; 32];
-        let result_len = read(up_pipe_fd[0], &mut child_result) as usize;
-        close(up_pipe_fd[0]);
+        let result_len = read(up_pipe_fd[0], &mut child_result).unwrap();
+        close(up_pipe_fd[0]).unwrap();
         // check
         assert_eq!(
             sum,
@@ -64,7 +61,7 @@ pub fn main() -> i32 {
                 .unwrap()
         );
         let mut _unused: i32 = 0;
-        wait(&mut _unused);
+        wait(&mut _unused).unwrap();
         println!("pipe_large_test passed!");
         0
     }
@@ -12,7 +12,7 @@ static STR: &str = "Hello, world!";
 pub fn main() -> i32 {
     // create pipe
     let mut pipe_fd = [0usize; 2];
-    pipe(&mut pipe_fd);
+    pipe(&mut pipe_fd).unwrap();
     // read end
     assert_eq!(pipe_fd[0], 3);
     // write end
@@ -20,23 +20,23 @@ pub fn main() -> i32 {
     if fork() == 0 {
         // child process, read from parent
         // close write_end
-        close(pipe_fd[1]);
+        close(pipe_fd[1]).unwrap();
         let mut buffer = [0u8; 32];
-        let len_read = read(pipe_fd[0], &mut buffer) as usize;
+        let len_read = read(pipe_fd[0], &mut buffer).unwrap();
         // close read_end
-        close(pipe_fd[0]);
+        close(pipe_fd[0]).unwrap();
         assert_eq!(core::str::from_utf8(&buffer[..len_read]).unwrap(), STR);
         println!("Read OK, child process exited!");
         0
     } else {
         // parent process, write to child
         // close read end
-        close(pipe_fd[0]);
-        assert_eq!(write(pipe_fd[1], STR.as_bytes()), STR.len() as isize);
+        close(pipe_fd[0]).unwrap();
+        assert_eq!(write(pipe_fd[1], STR.as_bytes()), Ok(STR.len()));
         // close write end
-        close(pipe_fd[1]);
+        close(pipe_fd[1]).unwrap();
         let mut child_exit_code: i32 = 0;
-        wait(&mut child_exit_code);
+        wait(&mut child_exit_code).unwrap();
         assert_eq!(child_exit_code, 0);
         println!("pipetest passed!");
         0
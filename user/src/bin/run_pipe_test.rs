@@ -10,10 +10,10 @@ use user_lib::{exec, fork, wait};
 pub fn main() -> i32 {
     for i in 0..5 {
         if fork() == 0 {
-            exec("pipe_large_test\0", &[core::ptr::null::<u8>()]);
+            exec("pipe_large_test\0", &[core::ptr::null::<u8>()]).unwrap();
         } else {
             let mut _unused: i32 = 0;
-            wait(&mut _unused);
+            wait(&mut _unused).unwrap();
             println!("Iter {} OK.", i);
         }
     }
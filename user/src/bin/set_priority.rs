@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, get_time, set_priority, waitpid, Errno};
+
+const PRIORITIES: [isize; 4] = [4, 8, 16, 32];
+const RUN_MS: isize = 500;
+
+/// Spins until `end` and returns how many rounds were done.
+fn spin_until(end: isize) -> usize {
+    let mut rounds = 0;
+    while get_time() < end {
+        rounds += 1;
+    }
+    rounds
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(set_priority(0), Err(Errno::EINVAL));
+    assert_eq!(set_priority(1), Err(Errno::EINVAL));
+    assert_eq!(set_priority(-16), Err(Errno::EINVAL));
+    assert_eq!(set_priority(1 << 20), Err(Errno::EINVAL));
+    assert_eq!(set_priority(16), Ok(16));
+
+    // with the stride scheduler the rounds grow with the priority
+    let end = get_time() + RUN_MS;
+    let mut pids = [0isize; PRIORITIES.len()];
+    for (i, priority) in PRIORITIES.iter().enumerate() {
+        pids[i] = fork();
+        if pids[i] == 0 {
+            assert_eq!(set_priority(*priority), Ok(*priority as usize));
+            let rounds = spin_until(end);
+            println!("priority {}: {} rounds", priority, rounds);
+            exit(0);
+        }
+    }
+    for pid in pids {
+        let mut exit_code: i32 = -1;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), Ok(pid as usize));
+        assert_eq!(exit_code, 0);
+    }
+    println!("set_priority passed!");
+    0
+}
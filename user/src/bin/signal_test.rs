@@ -0,0 +1,129 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    exit, fork, getpid, kill, sigaction, sigprocmask, sleep, waitpid, yield_, Errno, SignalAction,
+    SignalFlags, SIGALRM, SIGCHLD, SIGCONT, SIGKILL, SIGSTOP, SIGTERM, SIGUSR1, SIGUSR2, SIG_BLOCK,
+    SIG_DFL, SIG_IGN, SIG_SETMASK, SIG_UNBLOCK,
+};
+
+/// Bit `signum` is set once the handler of `signum` has run.
+static CAUGHT: AtomicUsize = AtomicUsize::new(0);
+static NESTED: AtomicUsize = AtomicUsize::new(0);
+
+fn caught(signum: i32) -> bool {
+    CAUGHT.load(Ordering::SeqCst) & (1 << signum) != 0
+}
+
+fn handler(signum: i32) {
+    CAUGHT.fetch_or(1 << signum, Ordering::SeqCst);
+}
+
+/// SIGUSR2 is blocked while this runs, so it is delivered after the return.
+fn nesting_handler(_signum: i32) {
+    kill(getpid() as usize, SIGUSR2).unwrap();
+    NESTED.store(caught(SIGUSR2) as usize + 1, Ordering::SeqCst);
+}
+
+fn catch(signum: i32, handler: fn(i32), mask: SignalFlags) {
+    let action = SignalAction::new(handler as usize, mask);
+    assert_eq!(sigaction(signum, Some(&action), None), Ok(()));
+}
+
+fn wait_exit_code(pid: isize) -> i32 {
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), Ok(pid as usize));
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+
+    // a handler runs when the syscall returns, and the registers survive it
+    catch(SIGUSR1, handler, SignalFlags::empty());
+    let values = [1usize, 2, 3, 4];
+    assert_eq!(kill(pid, SIGUSR1), Ok(()));
+    assert!(caught(SIGUSR1));
+    assert_eq!(values.iter().sum::<usize>(), 10);
+    let mut old_action = SignalAction::default();
+    assert_eq!(sigaction(SIGUSR1, None, Some(&mut old_action)), Ok(()));
+    assert_eq!(old_action.handler, handler as usize);
+
+    // blocked signals wait until they are unblocked
+    CAUGHT.store(0, Ordering::SeqCst);
+    assert_eq!(
+        sigprocmask(SIG_BLOCK, SignalFlags::SIGUSR1),
+        Ok(SignalFlags::empty())
+    );
+    kill(pid, SIGUSR1).unwrap();
+    assert!(!caught(SIGUSR1));
+    assert_eq!(
+        sigprocmask(SIG_UNBLOCK, SignalFlags::SIGUSR1),
+        Ok(SignalFlags::SIGUSR1)
+    );
+    assert!(caught(SIGUSR1));
+
+    // the handler mask defers signals raised inside the handler
+    catch(SIGUSR2, handler, SignalFlags::empty());
+    catch(SIGALRM, nesting_handler, SignalFlags::SIGUSR2);
+    kill(pid, SIGALRM).unwrap();
+    assert_eq!(NESTED.load(Ordering::SeqCst), 1);
+    assert!(caught(SIGUSR2));
+    assert_eq!(
+        sigprocmask(SIG_SETMASK, SignalFlags::empty()),
+        Ok(SignalFlags::empty())
+    );
+
+    // SIGCHLD is ignored by default but can be caught
+    let child = fork();
+    if child == 0 {
+        exit(0);
+    }
+    assert_eq!(wait_exit_code(child), 0);
+    assert!(!caught(SIGCHLD));
+    catch(SIGCHLD, handler, SignalFlags::empty());
+    let child = fork();
+    if child == 0 {
+        exit(0);
+    }
+    assert_eq!(wait_exit_code(child), 0);
+    assert!(caught(SIGCHLD));
+
+    // SIGTERM terminates by default unless ignored
+    let ignore = SignalAction::new(SIG_IGN, SignalFlags::empty());
+    assert_eq!(sigaction(SIGTERM, Some(&ignore), None), Ok(()));
+    kill(pid, SIGTERM).unwrap();
+    let default = SignalAction::new(SIG_DFL, SignalFlags::empty());
+    assert_eq!(sigaction(SIGTERM, Some(&default), None), Ok(()));
+    let child = fork();
+    if child == 0 {
+        loop {
+            yield_();
+        }
+    }
+    kill(child as usize, SIGTERM).unwrap();
+    assert_eq!(wait_exit_code(child), -SIGTERM);
+
+    // SIGKILL and SIGSTOP can not be caught, a stopped child only dies by SIGKILL
+    assert_eq!(sigaction(SIGKILL, Some(&ignore), None), Err(Errno::EINVAL));
+    assert_eq!(sigaction(SIGSTOP, Some(&ignore), None), Err(Errno::EINVAL));
+    let child = fork();
+    if child == 0 {
+        loop {
+            yield_();
+        }
+    }
+    kill(child as usize, SIGSTOP).unwrap();
+    kill(child as usize, SIGCONT).unwrap();
+    kill(child as usize, SIGSTOP).unwrap();
+    sleep(10);
+    kill(child as usize, SIGKILL).unwrap();
+    assert_eq!(wait_exit_code(child), -SIGKILL);
+    println!("signal_test passed!");
+    0
+}
@@ -13,10 +13,11 @@ const DL: u8 = 0x7fu8;
 const BS: u8 = 0x08u8;
 const LINE_START: &str = ">> ";
 
+use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 use user_lib::console::getchar;
-use user_lib::{close, dup, exec, fork, open, pipe, waitpid, OpenFlags};
+use user_lib::{chdir, close, dup, exec, fork, open, pipe, waitpid, Errno, OpenFlags};
 
 #[derive(Debug)]
 struct ProcessArguments {
@@ -84,6 +85,18 @@ pub fn main() -> i32 {
         match c {
             LF | CR => {
                 println!("");
+                let words: Vec<_> = line.split(' ').filter(|word| !word.is_empty()).collect();
+                if words.first() == Some(&"cd") {
+                    // builtin command, change the working directory of the shell itself
+                    let mut path = String::from(*words.get(1).unwrap_or(&"/"));
+                    path.push('\0');
+                    if let Err(errno) = chdir(path.as_str()) {
+                        println!("cd: {}: {:?}", words[1], errno);
+                    }
+                    line.clear();
+                    print!("{}", LINE_START);
+                    continue;
+                }
                 if !line.is_empty() {
                     let splited: Vec<_> = line.as_str().split('|').collect();
                     let process_arguments_list: Vec<_> = splited
@@ -116,7 +129,7 @@ pub fn main() -> i32 {
                         if !process_arguments_list.is_empty() {
                             for _ in 0..process_arguments_list.len() - 1 {
                                 let mut pipe_fd = [0usize; 2];
-                                pipe(&mut pipe_fd);
+                                pipe(&mut pipe_fd).unwrap();
                                 pipes_fd.push(pipe_fd);
                             }
                         }
@@ -130,51 +143,65 @@ pub fn main() -> i32 {
                                 let args_addr = &process_argument.args_addr;
                                 // redirect input
                                 if !input.is_empty() {
-                                    let input_fd = open(input.as_str(), OpenFlags::RDONLY);
-                                    if input_fd == -1 {
-                                        println!("Error when opening file {}", input);
-                                        return -4;
-                                    }
-                                    let input_fd = input_fd as usize;
-                                    close(0);
-                                    assert_eq!(dup(input_fd), 0);
-                                    close(input_fd);
+                                    let input_fd = match open(input.as_str(), OpenFlags::RDONLY) {
+                                        Ok(fd) => fd,
+                                        Err(errno) => {
+                                            println!(
+                                                "Error when opening file {}: {:?}",
+                                                input, errno
+                                            );
+                                            return -4;
+                                        }
+                                    };
+                                    close(0).unwrap();
+                                    assert_eq!(dup(input_fd), Ok(0));
+                                    close(input_fd).unwrap();
                                 }
                                 // redirect output
                                 if !output.is_empty() {
-                                    let output_fd = open(
+                                    let output_fd = match open(
                                         output.as_str(),
                                         OpenFlags::CREATE | OpenFlags::WRONLY,
-                                    );
-                                    if output_fd == -1 {
-                                        println!("Error when opening file {}", output);
-                                        return -4;
-                                    }
-                                    let output_fd = output_fd as usize;
-                                    close(1);
-                                    assert_eq!(dup(output_fd), 1);
-                                    close(output_fd);
+                                    ) {
+                                        Ok(fd) => fd,
+                                        Err(errno) => {
+                                            println!(
+                                                "Error when opening file {}: {:?}",
+                                                output, errno
+                                            );
+                                            return -4;
+                                        }
+                                    };
+                                    close(1).unwrap();
+                                    assert_eq!(dup(output_fd), Ok(1));
+                                    close(output_fd).unwrap();
                                 }
                                 // receive input from the previous process
                                 if i > 0 {
-                                    close(0);
+                                    close(0).unwrap();
                                     let read_end = pipes_fd.get(i - 1).unwrap()[0];
-                                    assert_eq!(dup(read_end), 0);
+                                    assert_eq!(dup(read_end), Ok(0));
                                 }
                                 // send output to the next process
                                 if i < process_arguments_list.len() - 1 {
-                                    close(1);
+                                    close(1).unwrap();
                                     let write_end = pipes_fd.get(i).unwrap()[1];
-                                    assert_eq!(dup(write_end), 1);
+                                    assert_eq!(dup(write_end), Ok(1));
                                 }
                                 // close all pipe ends inherited from the parent process
                                 for pipe_fd in pipes_fd.iter() {
-                                    close(pipe_fd[0]);
-                                    close(pipe_fd[1]);
+                                    close(pipe_fd[0]).unwrap();
+                                    close(pipe_fd[1]).unwrap();
                                 }
                                 // execute new application
-                                if exec(args_copy[0].as_str(), args_addr.as_slice()) == -1 {
-                                    println!("Error when executing!");
+                                let mut ret = exec(args_copy[0].as_str(), args_addr.as_slice());
+                                if ret == Err(Errno::ENOENT) && !args_copy[0].contains('/') {
+                                    // applications live in the root directory
+                                    let app = format!("/{}", args_copy[0]);
+                                    ret = exec(app.as_str(), args_addr.as_slice());
+                                }
+                                if let Err(errno) = ret {
+                                    println!("Error when executing: {:?}", errno);
                                     return -4;
                                 }
                                 unreachable!();
@@ -183,13 +210,13 @@ pub fn main() -> i32 {
                             }
                         }
                         for pipe_fd in pipes_fd.iter() {
-                            close(pipe_fd[0]);
-                            close(pipe_fd[1]);
+                            close(pipe_fd[0]).unwrap();
+                            close(pipe_fd[1]).unwrap();
                         }
                         let mut exit_code: i32 = 0;
                         for pid in children.into_iter() {
                             let exit_pid = waitpid(pid as usize, &mut exit_code);
-                            assert_eq!(pid, exit_pid);
+                            assert_eq!(exit_pid, Ok(pid as usize));
                             //println!("Shell: Process {} exited with code {}", pid, exit_code);
                         }
                     }
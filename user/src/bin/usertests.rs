@@ -10,6 +10,14 @@ extern crate user_lib;
 // item of TESTS : app_name(argv_0), argv_1, argv_2, argv_3, exit_code
 static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("filetest_simple\0", "\0", "\0", "\0", 0),
+    ("filetest_dir\0", "\0", "\0", "\0", 0),
+    ("filetest_link\0", "\0", "\0", "\0", 0),
+    ("filetest_stat\0", "\0", "\0", "\0", 0),
+    ("filetest_truncate\0", "\0", "\0", "\0", 0),
+    ("filetest_symlink\0", "\0", "\0", "\0", 0),
+    ("filetest_perm\0", "\0", "\0", "\0", 0),
+    ("filetest_mount\0", "\0", "\0", "\0", 0),
+    ("filetest_tmpfs\0", "\0", "\0", "\0", 0),
     ("cat\0", "filea\0", "\0", "\0", 0),
     ("cmdline_args\0", "1\0", "2\0", "3\0", 0),
     ("eisenberg\0", "\0", "\0", "\0", 0),
@@ -18,6 +26,14 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("forktest_simple\0", "\0", "\0", "\0", 0),
     ("forktest\0", "\0", "\0", "\0", 0),
     ("forktest2\0", "\0", "\0", "\0", 0),
+    ("forktest_cow\0", "\0", "\0", "\0", 0),
+    ("lazy_stack\0", "\0", "\0", "\0", 0),
+    ("mmap_test\0", "\0", "\0", "\0", 0),
+    ("heap_grow\0", "\0", "\0", "\0", 0),
+    ("signal_test\0", "\0", "\0", "\0", 0),
+    ("waitpid_block\0", "\0", "\0", "\0", 0),
+    ("set_priority\0", "\0", "\0", "\0", 0),
+    ("bad_pointer\0", "\0", "\0", "\0", 0),
     ("forktree\0", "\0", "\0", "\0", 0),
     ("hello_world\0", "\0", "\0", "\0", 0),
     ("huge_write\0", "\0", "\0", "\0", 0),
@@ -92,12 +108,12 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
 
         let pid = fork();
         if pid == 0 {
-            exec(test.0, &arr[..]);
+            exec(test.0, &arr[..]).unwrap();
             panic!("unreachable!");
         } else {
             let mut exit_code: i32 = Default::default();
             let wait_pid = waitpid(pid as usize, &mut exit_code);
-            assert_eq!(pid, wait_pid);
+            assert_eq!(wait_pid, Ok(pid as usize));
             if exit_code == test.4 {
                 // summary apps with  exit_code
                 pass_num = pass_num + 1;
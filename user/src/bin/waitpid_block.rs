@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use user_lib::{
+    exit, fork, getpid, kill, sigaction, sleep, waitpid, waitpid_nb, Errno, SignalAction,
+    SignalFlags, SIGUSR1,
+};
+
+static CAUGHT: AtomicBool = AtomicBool::new(false);
+
+fn handler(_signum: i32) {
+    CAUGHT.store(true, Ordering::SeqCst);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let parent = getpid() as usize;
+    let action = SignalAction::new(handler as usize, SignalFlags::empty());
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), Ok(()));
+    let child = fork();
+    if child == 0 {
+        sleep(100);
+        exit(3);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(
+        waitpid_nb(child as usize, &mut exit_code),
+        Err(Errno::EAGAIN)
+    );
+
+    // a signal wakes up the parent, which goes on waiting after the handler
+    let signaller = fork();
+    if signaller == 0 {
+        sleep(20);
+        kill(parent, SIGUSR1).unwrap();
+        exit(0);
+    }
+    assert_eq!(waitpid(child as usize, &mut exit_code), Ok(child as usize));
+    assert_eq!(exit_code, 3);
+    assert!(CAUGHT.load(Ordering::SeqCst));
+    assert_eq!(
+        waitpid(signaller as usize, &mut exit_code),
+        Ok(signaller as usize)
+    );
+    assert_eq!(exit_code, 0);
+    assert_eq!(waitpid(child as usize, &mut exit_code), Err(Errno::ECHILD));
+    println!("waitpid_block passed!");
+    0
+}
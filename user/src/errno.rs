@@ -0,0 +1,99 @@
+/// Error numbers returned by the kernel, which take the values of Linux.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(isize)]
+pub enum Errno {
+    /// Operation not permitted
+    EPERM = 1,
+    /// No such file or directory
+    ENOENT = 2,
+    /// No such process or thread
+    ESRCH = 3,
+    /// Interrupted by a signal
+    EINTR = 4,
+    /// Bad file descriptor
+    EBADF = 9,
+    /// No such child process
+    ECHILD = 10,
+    /// Try again, nothing is ready yet
+    EAGAIN = 11,
+    /// Out of memory
+    ENOMEM = 12,
+    /// Permission denied
+    EACCES = 13,
+    /// Bad address
+    EFAULT = 14,
+    /// Device or resource busy
+    EBUSY = 16,
+    /// File exists
+    EEXIST = 17,
+    /// Cross-device link
+    EXDEV = 18,
+    /// The file does not support the operation
+    ENODEV = 19,
+    /// Not a directory
+    ENOTDIR = 20,
+    /// Is a directory
+    EISDIR = 21,
+    /// Invalid argument
+    EINVAL = 22,
+    /// File too large
+    EFBIG = 27,
+    /// No space left on device
+    ENOSPC = 28,
+    /// The file is a pipe or a device which can not seek
+    ESPIPE = 29,
+    /// Result out of range
+    ERANGE = 34,
+    /// Resource deadlock would occur
+    EDEADLK = 35,
+    /// File name too long
+    ENAMETOOLONG = 36,
+    /// Function not implemented
+    ENOSYS = 38,
+    /// Directory not empty
+    ENOTEMPTY = 39,
+    /// Too many levels of symbolic links
+    ELOOP = 40,
+}
+
+/// The result of a syscall.
+pub type SysResult<T = usize> = Result<T, Errno>;
+
+impl Errno {
+    /// Split the return value of a syscall into its value or the negated errno.
+    pub fn from_ret(ret: isize) -> SysResult {
+        if ret >= 0 {
+            return Ok(ret as usize);
+        }
+        Err(match -ret {
+            1 => Self::EPERM,
+            2 => Self::ENOENT,
+            3 => Self::ESRCH,
+            4 => Self::EINTR,
+            9 => Self::EBADF,
+            10 => Self::ECHILD,
+            11 => Self::EAGAIN,
+            12 => Self::ENOMEM,
+            13 => Self::EACCES,
+            14 => Self::EFAULT,
+            16 => Self::EBUSY,
+            17 => Self::EEXIST,
+            18 => Self::EXDEV,
+            19 => Self::ENODEV,
+            20 => Self::ENOTDIR,
+            21 => Self::EISDIR,
+            22 => Self::EINVAL,
+            27 => Self::EFBIG,
+            28 => Self::ENOSPC,
+            29 => Self::ESPIPE,
+            34 => Self::ERANGE,
+            35 => Self::EDEADLK,
+            36 => Self::ENAMETOOLONG,
+            38 => Self::ENOSYS,
+            39 => Self::ENOTEMPTY,
+            40 => Self::ELOOP,
+            errno => panic!("unknown errno {}", errno),
+        })
+    }
+}
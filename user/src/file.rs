@@ -10,21 +10,163 @@ bitflags! {
     }
 }
 
-pub fn dup(fd: usize) -> isize {
-    sys_dup(fd)
+bitflags! {
+    /// The type of a file.
+    #[derive(Default)]
+    pub struct StatMode: u32 {
+        const FIFO = 0o010000;
+        const CHR = 0o020000;
+        const DIR = 0o040000;
+        const FILE = 0o100000;
+        const LNK = 0o120000;
+    }
+}
+
+/// Status of a file filled by `fstat`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stat {
+    /// Inode number, 0 if the file is not on the disk.
+    pub ino: u64,
+    pub mode: StatMode,
+    /// Permission bits together with the setuid, setgid and sticky bits.
+    pub perm: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    /// Number of 512-byte blocks taken on the disk.
+    pub blocks: u64,
+    /// Seconds since the Unix epoch of the last access.
+    pub atime: u64,
+    /// Seconds since the Unix epoch of the last change of the data.
+    pub mtime: u64,
+    /// Seconds since the Unix epoch of the last change of the data or the inode.
+    pub ctime: u64,
+}
+
+/// Options of `lseek`, the offset is relative to the start, the current
+/// offset or the end of the file.
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+/// Special value of `dirfd`, the path is relative to the working directory.
+pub const AT_FDCWD: isize = -100;
+/// Flag of `unlinkat`, remove a directory instead of a file.
+pub const AT_REMOVEDIR: u32 = 0x200;
+/// Flag of `fchownat` and `fstatat`, act on a symbolic link itself.
+pub const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+
+/// Bits of `chmod` besides the permissions of the owner, the group and others.
+pub const S_ISUID: u32 = 0o4000;
+pub const S_ISGID: u32 = 0o2000;
+pub const S_ISVTX: u32 = 0o1000;
+
+/// Return the length of the working directory with its end `\0`.
+pub fn getcwd(buf: &mut [u8]) -> SysResult {
+    Errno::from_ret(sys_getcwd(buf))
+}
+pub fn mkdir(path: &str) -> SysResult<()> {
+    Errno::from_ret(sys_mkdirat(AT_FDCWD, path, 0o755)).map(|_| ())
+}
+pub fn unlink(path: &str) -> SysResult<()> {
+    Errno::from_ret(sys_unlinkat(AT_FDCWD, path, 0)).map(|_| ())
+}
+pub fn rmdir(path: &str) -> SysResult<()> {
+    Errno::from_ret(sys_unlinkat(AT_FDCWD, path, AT_REMOVEDIR)).map(|_| ())
+}
+pub fn link(old_path: &str, new_path: &str) -> SysResult<()> {
+    Errno::from_ret(sys_linkat(AT_FDCWD, old_path, AT_FDCWD, new_path, 0)).map(|_| ())
+}
+/// Create a symbolic link at `link_path` to `target`, which need not exist.
+pub fn symlink(target: &str, link_path: &str) -> SysResult<()> {
+    Errno::from_ret(sys_symlinkat(target, AT_FDCWD, link_path)).map(|_| ())
+}
+/// Return the length of the target copied into `buf` without an end `\0`.
+pub fn readlink(path: &str, buf: &mut [u8]) -> SysResult {
+    Errno::from_ret(sys_readlinkat(AT_FDCWD, path, buf))
+}
+pub fn rename(old_path: &str, new_path: &str) -> SysResult<()> {
+    Errno::from_ret(sys_renameat(AT_FDCWD, old_path, AT_FDCWD, new_path)).map(|_| ())
+}
+pub fn chdir(path: &str) -> SysResult<()> {
+    Errno::from_ret(sys_chdir(path)).map(|_| ())
+}
+/// Return the new fd.
+pub fn dup(fd: usize) -> SysResult {
+    Errno::from_ret(sys_dup(fd))
+}
+/// Return the new fd.
+/// A file created is readable by all and writable by its owner.
+pub fn open(path: &str, flags: OpenFlags) -> SysResult {
+    Errno::from_ret(sys_open(path, flags.bits, 0o644))
+}
+/// Only the owner of the file or root may change its mode.
+pub fn chmod(path: &str, mode: u32) -> SysResult<()> {
+    Errno::from_ret(sys_fchmodat(AT_FDCWD, path, mode, 0)).map(|_| ())
+}
+/// Only root may change the owner, `u32::MAX` leaves an id unchanged.
+pub fn chown(path: &str, uid: u32, gid: u32) -> SysResult<()> {
+    Errno::from_ret(sys_fchownat(AT_FDCWD, path, uid, gid, 0)).map(|_| ())
+}
+/// Mount a new file system of the type `fstype` on the directory `target`,
+/// which only root may do. `tmpfs` is a new empty one in memory, `easyfs` is
+/// busy as the root file system.
+pub fn mount(target: &str, fstype: &str) -> SysResult<()> {
+    Errno::from_ret(sys_mount("\0", target, fstype, 0)).map(|_| ())
+}
+/// Unmount the file system whose root directory is at `target`.
+pub fn umount(target: &str) -> SysResult<()> {
+    Errno::from_ret(sys_umount2(target, 0)).map(|_| ())
+}
+pub fn close(fd: usize) -> SysResult<()> {
+    Errno::from_ret(sys_close(fd)).map(|_| ())
+}
+/// Store the read end and then the write end into `pipe_fd`.
+pub fn pipe(pipe_fd: &mut [usize]) -> SysResult<()> {
+    Errno::from_ret(sys_pipe(pipe_fd)).map(|_| ())
+}
+/// Return the number of bytes read, 0 at the end of the file.
+pub fn read(fd: usize, buf: &mut [u8]) -> SysResult {
+    Errno::from_ret(sys_read(fd, buf))
+}
+/// Return the number of bytes written.
+pub fn write(fd: usize, buf: &[u8]) -> SysResult {
+    Errno::from_ret(sys_write(fd, buf))
+}
+/// Read from `offset` without moving the offset of the file.
+pub fn pread(fd: usize, buf: &mut [u8], offset: usize) -> SysResult {
+    Errno::from_ret(sys_pread(fd, buf, offset))
+}
+/// Write at `offset` without moving the offset of the file.
+pub fn pwrite(fd: usize, buf: &[u8], offset: usize) -> SysResult {
+    Errno::from_ret(sys_pwrite(fd, buf, offset))
+}
+/// Return the new offset from the start of the file.
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> SysResult {
+    Errno::from_ret(sys_lseek(fd, offset, whence))
+}
+pub fn fstat(fd: usize, stat: &mut Stat) -> SysResult<()> {
+    Errno::from_ret(sys_fstat(fd, stat)).map(|_| ())
+}
+/// Like `fstat` for the file at `path`, following symbolic links.
+pub fn stat(path: &str, stat: &mut Stat) -> SysResult<()> {
+    Errno::from_ret(sys_fstatat(AT_FDCWD, path, stat, 0)).map(|_| ())
 }
-pub fn open(path: &str, flags: OpenFlags) -> isize {
-    sys_open(path, flags.bits)
+/// Like `stat`, but a symbolic link at the end of `path` is described itself.
+pub fn lstat(path: &str, stat: &mut Stat) -> SysResult<()> {
+    Errno::from_ret(sys_fstatat(AT_FDCWD, path, stat, AT_SYMLINK_NOFOLLOW)).map(|_| ())
 }
-pub fn close(fd: usize) -> isize {
-    sys_close(fd)
+/// Write the file back to the disk, writes are otherwise only cached.
+pub fn fsync(fd: usize) -> SysResult<()> {
+    Errno::from_ret(sys_fsync(fd)).map(|_| ())
 }
-pub fn pipe(pipe_fd: &mut [usize]) -> isize {
-    sys_pipe(pipe_fd)
+/// Cut the file to `len` bytes, or extend it with zeros.
+pub fn ftruncate(fd: usize, len: usize) -> SysResult<()> {
+    Errno::from_ret(sys_ftruncate(fd, len)).map(|_| ())
 }
-pub fn read(fd: usize, buf: &mut [u8]) -> isize {
-    sys_read(fd, buf)
+/// Write all cached file data back to the disk.
+pub fn sync() {
+    sys_sync();
 }
-pub fn write(fd: usize, buf: &[u8]) -> isize {
-    sys_write(fd, buf)
-}
\ No newline at end of file
@@ -8,8 +8,11 @@
 pub mod console;
 mod lang_items;
 mod syscall;
+mod errno;
 mod file;
 mod task;
+mod mm;
+mod signal;
 mod sync;
 mod io;
 
@@ -19,18 +22,47 @@ extern crate bitflags;
 
 use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::NonNull;
 use syscall::*;
+pub use errno::*;
 pub use file::*;
 pub use task::*;
+pub use mm::*;
+pub use signal::*;
 pub use sync::*;
 pub use io::*;
 
-const USER_HEAP_SIZE: usize = 32768;
+/// The heap grows through `sbrk` by at least this size.
+const USER_HEAP_GROW_SIZE: usize = 32768;
 
-static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
+/// A buddy system heap which is extended by `sbrk` when it runs out of memory.
+struct GrowableHeap(LockedHeap);
+
+unsafe impl GlobalAlloc for GrowableHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.0.lock();
+        loop {
+            if let Ok(ptr) = heap.alloc(layout) {
+                return ptr.as_ptr();
+            }
+            // a range twice the block size always holds an aligned block
+            let block_size = layout.size().max(layout.align()).next_power_of_two();
+            let grow_size = (2 * block_size).max(USER_HEAP_GROW_SIZE);
+            let start = match sbrk(grow_size as isize) {
+                Ok(start) => start,
+                Err(_) => return core::ptr::null_mut(),
+            };
+            heap.add_to_heap(start, start + grow_size);
+        }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.lock().dealloc(NonNull::new_unchecked(ptr), layout)
+    }
+}
 
 #[global_allocator]
-static HEAP: LockedHeap = LockedHeap::empty();
+static HEAP: GrowableHeap = GrowableHeap(LockedHeap::empty());
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
@@ -40,10 +72,6 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
 #[no_mangle]
 #[link_section = ".text.entry"]
 pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
-    unsafe {
-        HEAP.lock()
-            .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
-    }
     let mut v: Vec<&'static str> = Vec::new();
     for i in 0..argc {
         let str_start =
@@ -0,0 +1,44 @@
+use super::*;
+
+bitflags! {
+    pub struct ProtFlags: usize {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    pub struct MapFlags: usize {
+        const SHARED = 1 << 0;
+        const PRIVATE = 1 << 1;
+        const FIXED = 1 << 4;
+        const ANONYMOUS = 1 << 5;
+    }
+}
+
+/// Return the new program break, which stays unchanged on failure.
+pub fn brk(addr: usize) -> isize {
+    sys_brk(addr)
+}
+/// Return the old program break.
+pub fn sbrk(increment: isize) -> SysResult {
+    Errno::from_ret(sys_sbrk(increment))
+}
+/// Return the start address of the mapping.
+pub fn mmap(
+    addr: usize,
+    len: usize,
+    prot: ProtFlags,
+    flags: MapFlags,
+    fd: usize,
+    offset: usize,
+) -> SysResult {
+    Errno::from_ret(sys_mmap(addr, len, prot.bits, flags.bits, fd, offset))
+}
+pub fn munmap(addr: usize, len: usize) -> SysResult<()> {
+    Errno::from_ret(sys_munmap(addr, len)).map(|_| ())
+}
+pub fn mprotect(addr: usize, len: usize, prot: ProtFlags) -> SysResult<()> {
+    Errno::from_ret(sys_mprotect(addr, len, prot.bits)).map(|_| ())
+}
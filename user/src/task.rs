@@ -6,58 +6,70 @@ pub fn exit(exit_code: i32) -> ! {
 pub fn yield_() -> isize {
     sys_yield()
 }
+/// Priorities range from 2 to 65536 and default to 16, a thread with a larger
+/// one runs more often under the stride scheduler.
+pub fn set_priority(priority: isize) -> SysResult {
+    Errno::from_ret(sys_set_priority(priority))
+}
 pub fn get_time() -> isize {
     sys_get_time()
 }
 pub fn getpid() -> isize {
     sys_getpid()
 }
+/// The real and the effective user and group, files are accessed with the
+/// effective ones.
+pub fn getuid() -> u32 {
+    sys_getuid() as u32
+}
+pub fn geteuid() -> u32 {
+    sys_geteuid() as u32
+}
+pub fn getgid() -> u32 {
+    sys_getgid() as u32
+}
+pub fn getegid() -> u32 {
+    sys_getegid() as u32
+}
+/// Root sets both the real and the effective user, others may only switch
+/// between the two.
+pub fn setuid(uid: u32) -> SysResult<()> {
+    Errno::from_ret(sys_setuid(uid)).map(|_| ())
+}
+pub fn setgid(gid: u32) -> SysResult<()> {
+    Errno::from_ret(sys_setgid(gid)).map(|_| ())
+}
 pub fn fork() -> isize {
     sys_fork()
 }
-pub fn exec(path: &str, args: &[*const u8]) -> isize {
-    sys_exec(path, args)
-}
-pub fn wait(exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
-        }
-    }
+/// Only return on failure.
+pub fn exec(path: &str, args: &[*const u8]) -> SysResult {
+    Errno::from_ret(sys_exec(path, args))
 }
+/// Option of `waitpid`, fail with `EAGAIN` at once if no child has exited.
+pub const WNOHANG: u32 = 1;
 
-pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
-        }
-    }
+/// Return the pid of the exited child, or `ECHILD` if there is none.
+pub fn wait(exit_code: &mut i32) -> SysResult {
+    waitpid_blocking(-1, exit_code)
 }
 
-pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> isize {
-    sys_waitpid(pid as isize, exit_code as *mut _)
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> SysResult {
+    waitpid_blocking(pid as isize, exit_code)
 }
 
-bitflags! {
-    pub struct SignalFlags: i32 {
-        const SIGINT    = 1 << 2;
-        const SIGILL    = 1 << 4;
-        const SIGABRT   = 1 << 6;
-        const SIGFPE    = 1 << 8;
-        const SIGSEGV   = 1 << 11;
-    }
+pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> SysResult {
+    Errno::from_ret(sys_waitpid(pid as isize, exit_code as *mut _, WNOHANG))
 }
 
-pub fn kill(pid: usize, signal: i32) -> isize {
-    sys_kill(pid, signal)
+fn waitpid_blocking(pid: isize, exit_code: &mut i32) -> SysResult {
+    loop {
+        match Errno::from_ret(sys_waitpid(pid, exit_code as *mut _, 0)) {
+            // interrupted by a signal, which has been handled now
+            Err(Errno::EINTR) => continue,
+            result => return result,
+        }
+    }
 }
 
 pub fn sleep(sleep_ms: usize) {
@@ -70,13 +82,15 @@ pub fn thread_create(entry: usize, arg: usize) -> isize {
 pub fn gettid() -> isize {
     sys_gettid()
 }
+/// Return the exit code of the thread, or the negated `ESRCH` if it does not
+/// exist, which can not be told apart from the same exit code.
 pub fn waittid(tid: usize) -> isize {
     loop {
         match sys_waittid(tid) {
-            -2 => {
+            ret if ret == -(Errno::EAGAIN as isize) => {
                 yield_();
             }
             exit_code => return exit_code,
         }
     }
-}
\ No newline at end of file
+}